@@ -0,0 +1,64 @@
+#!/usr/bin/env python3
+# Prints the text selected in the active window, as reported by AT-SPI.
+# Exits with 1 if no accessible in the active window has a selection.
+
+import sys
+
+import gi
+
+gi.require_version("Atspi", "2.0")
+from gi.repository import Atspi  # noqa: E402
+
+
+def get_selection(accessible):
+    text_iface = accessible.get_text_iface()
+    if text_iface is None:
+        return None
+    for i in range(text_iface.get_n_selections()):
+        selection = text_iface.get_selection(i)
+        if selection.end_offset > selection.start_offset:
+            return text_iface.get_text(selection.start_offset, selection.end_offset)
+    return None
+
+
+def find_selection(accessible, depth=0):
+    if accessible is None or depth > 64:
+        return None
+    state_set = accessible.get_state_set()
+    if not state_set.contains(Atspi.StateType.SHOWING):
+        return None
+    selection = get_selection(accessible)
+    if selection:
+        return selection
+    for i in range(accessible.get_child_count()):
+        found = find_selection(accessible.get_child_at_index(i), depth + 1)
+        if found:
+            return found
+    return None
+
+
+def find_active_window():
+    desktop = Atspi.get_desktop(0)
+    for i in range(desktop.get_child_count()):
+        app = desktop.get_child_at_index(i)
+        if app is None:
+            continue
+        for j in range(app.get_child_count()):
+            window = app.get_child_at_index(j)
+            if window is not None and window.get_state_set().contains(Atspi.StateType.ACTIVE):
+                return window
+    return None
+
+
+def main():
+    window = find_active_window()
+    if window is None:
+        sys.exit("no active window")
+    selection = find_selection(window)
+    if not selection:
+        sys.exit("no selected text")
+    sys.stdout.write(selection)
+
+
+if __name__ == "__main__":
+    main()
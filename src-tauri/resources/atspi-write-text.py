@@ -0,0 +1,62 @@
+#!/usr/bin/env python3
+# Inserts the text read from stdin into the focused editable accessible,
+# replacing the current selection. Exits with 1 if there is none.
+
+import sys
+
+import gi
+
+gi.require_version("Atspi", "2.0")
+from gi.repository import Atspi  # noqa: E402
+
+
+def find_focused(accessible, depth=0):
+    if accessible is None or depth > 64:
+        return None
+    state_set = accessible.get_state_set()
+    if state_set.contains(Atspi.StateType.FOCUSED) and state_set.contains(Atspi.StateType.EDITABLE):
+        return accessible
+    for i in range(accessible.get_child_count()):
+        found = find_focused(accessible.get_child_at_index(i), depth + 1)
+        if found is not None:
+            return found
+    return None
+
+
+def find_focused_in_active_window():
+    desktop = Atspi.get_desktop(0)
+    for i in range(desktop.get_child_count()):
+        app = desktop.get_child_at_index(i)
+        if app is None:
+            continue
+        for j in range(app.get_child_count()):
+            window = app.get_child_at_index(j)
+            if window is not None and window.get_state_set().contains(Atspi.StateType.ACTIVE):
+                return find_focused(window)
+    return None
+
+
+def main():
+    text = sys.stdin.read()
+    focused = find_focused_in_active_window()
+    if focused is None:
+        sys.exit("no focused editable text")
+    editable = focused.get_editable_text_iface()
+    text_iface = focused.get_text_iface()
+    if editable is None or text_iface is None:
+        sys.exit("focused element is not editable")
+
+    caret = text_iface.get_caret_offset()
+    if text_iface.get_n_selections() > 0:
+        selection = text_iface.get_selection(0)
+        if selection.end_offset > selection.start_offset:
+            editable.delete_text(selection.start_offset, selection.end_offset)
+            caret = selection.start_offset
+
+    if not editable.insert_text(caret, text, len(text.encode("utf-8"))):
+        sys.exit("failed to insert text")
+    text_iface.set_caret_offset(caret + len(text))
+
+
+if __name__ == "__main__":
+    main()
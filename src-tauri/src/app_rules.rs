@@ -0,0 +1,259 @@
+use glob::{MatchOptions, Pattern};
+use parking_lot::Mutex;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{get_config, set_config_value, Config};
+
+// Matches the foreground window by app name and/or window title. A rule with
+// neither pattern matches nothing.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AppRule {
+    pub app: Option<String>,
+    pub title: Option<String>,
+    // "glob" (the default, case-insensitive) or "regex"
+    pub syntax: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ForegroundApp {
+    pub app_name: String,
+    pub title: String,
+}
+
+// The last app other than ours the thumb was considered for, which is what
+// "Disable Thumb for Current App" in the tray refers to: by the time the tray
+// menu is open, the tray itself has the focus on some platforms.
+static LAST_FOREGROUND_APP: Mutex<Option<ForegroundApp>> = Mutex::new(None);
+
+fn pattern_matches(pattern: &str, syntax: Option<&str>, value: &str) -> Result<bool, String> {
+    match syntax.unwrap_or("glob") {
+        "regex" => Regex::new(pattern)
+            .map(|regex| regex.is_match(value))
+            .map_err(|e| e.to_string()),
+        "glob" => Pattern::new(pattern)
+            .map(|glob| {
+                glob.matches_with(
+                    value,
+                    MatchOptions {
+                        case_sensitive: false,
+                        require_literal_separator: false,
+                        require_literal_leading_dot: false,
+                    },
+                )
+            })
+            .map_err(|e| e.to_string()),
+        syntax => Err(format!("unknown pattern syntax \"{}\"", syntax)),
+    }
+}
+
+impl AppRule {
+    pub fn validate(&self) -> Result<(), String> {
+        for pattern in [&self.app, &self.title].iter().copied().flatten() {
+            pattern_matches(pattern, self.syntax.as_deref(), "")?;
+        }
+        Ok(())
+    }
+
+    pub fn matches(&self, app: &ForegroundApp) -> bool {
+        if self.app.is_none() && self.title.is_none() {
+            return false;
+        }
+        let syntax = self.syntax.as_deref();
+        let matches = |pattern: &Option<String>, value: &str| match pattern {
+            Some(pattern) => pattern_matches(pattern, syntax, value).unwrap_or(false),
+            None => true,
+        };
+        matches(&self.app, &app.app_name) && matches(&self.title, &app.title)
+    }
+}
+
+// A denylist match hides the thumb; a non-empty allowlist hides it in every
+// app that doesn't match it.
+pub fn is_thumb_enabled_for(config: &Config, app: &ForegroundApp) -> bool {
+    let matches_any = |rules: &Option<Vec<AppRule>>| {
+        rules
+            .as_ref()
+            .map_or(false, |rules| rules.iter().any(|rule| rule.matches(app)))
+    };
+    if matches_any(&config.thumb_denied_apps) {
+        return false;
+    }
+    match &config.thumb_allowed_apps {
+        Some(rules) if !rules.is_empty() => matches_any(&config.thumb_allowed_apps),
+        _ => true,
+    }
+}
+
+pub fn get_foreground_app() -> Option<ForegroundApp> {
+    let window = active_win_pos_rs::get_active_window().ok()?;
+    if window.process_id == std::process::id() as u64 {
+        return None;
+    }
+    Some(ForegroundApp {
+        app_name: window.app_name,
+        title: window.title,
+    })
+}
+
+pub fn is_own_app_foreground() -> bool {
+    active_win_pos_rs::get_active_window().map_or(false, |window| {
+        window.process_id == std::process::id() as u64
+    })
+}
+
+// Whether the thumb may show for the selection just made, remembering the app
+// for the tray.
+pub fn is_thumb_enabled_for_foreground_app() -> bool {
+    let app = match get_foreground_app() {
+        Some(app) => app,
+        None => return true,
+    };
+    let enabled = match get_config() {
+        Ok(config) => is_thumb_enabled_for(&config, &app),
+        Err(_) => true,
+    };
+    *LAST_FOREGROUND_APP.lock() = Some(app);
+    enabled
+}
+
+// Adds a denylist rule for the last foreground app and returns its name.
+pub fn disable_thumb_for_current_app() -> Result<String, String> {
+    let app = LAST_FOREGROUND_APP
+        .lock()
+        .clone()
+        .or_else(get_foreground_app)
+        .ok_or_else(|| "No current app".to_string())?;
+    let config = get_config().map_err(|e| e.to_string())?;
+    let rule = AppRule {
+        app: Some(Pattern::escape(&app.app_name)),
+        title: None,
+        syntax: None,
+    };
+    let mut denied_apps = config.thumb_denied_apps.unwrap_or_default();
+    if !denied_apps.contains(&rule) {
+        denied_apps.push(rule);
+    }
+    let value = serde_json::to_value(denied_apps).map_err(|e| e.to_string())?;
+    set_config_value("thumbDeniedApps", value)?;
+    crate::windows::close_thumb();
+    Ok(app.app_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn app(app_name: &str, title: &str) -> ForegroundApp {
+        ForegroundApp {
+            app_name: app_name.to_string(),
+            title: title.to_string(),
+        }
+    }
+
+    fn rule(app: Option<&str>, title: Option<&str>, syntax: Option<&str>) -> AppRule {
+        AppRule {
+            app: app.map(String::from),
+            title: title.map(String::from),
+            syntax: syntax.map(String::from),
+        }
+    }
+
+    #[test]
+    fn glob_and_regex_rules() {
+        let terminal = app("Terminal", "~/src — zsh");
+        let editor = app("Code", "main.rs — Visual Studio Code");
+        let cases = [
+            (rule(Some("terminal"), None, None), &terminal, true),
+            (rule(Some("Term*"), None, None), &terminal, true),
+            (rule(Some("Term?nal"), None, Some("glob")), &terminal, true),
+            (rule(Some("Term"), None, None), &terminal, false),
+            (rule(None, Some("*Visual Studio Code"), None), &editor, true),
+            (rule(None, Some("*.rs*"), None), &editor, true),
+            (rule(Some("Code"), Some("*.py*"), None), &editor, false),
+            (rule(Some("^Term"), None, Some("regex")), &terminal, true),
+            (rule(Some("^term"), None, Some("regex")), &terminal, false),
+            (
+                rule(Some("(?i)^term"), None, Some("regex")),
+                &terminal,
+                true,
+            ),
+            (rule(None, Some(r"\.rs\b"), Some("regex")), &editor, true),
+            (
+                rule(Some("Code|Terminal"), None, Some("regex")),
+                &terminal,
+                true,
+            ),
+            // A rule without patterns matches nothing
+            (rule(None, None, None), &terminal, false),
+            // Invalid patterns and syntaxes never match
+            (rule(Some("("), None, Some("regex")), &terminal, false),
+            (
+                rule(Some("Terminal"), None, Some("regexp")),
+                &terminal,
+                false,
+            ),
+        ];
+        for (rule, app, expected) in cases.iter() {
+            assert_eq!(rule.matches(app), *expected, "{:?} for {:?}", rule, app);
+        }
+    }
+
+    #[test]
+    fn invalid_rules_are_reported() {
+        assert!(rule(Some("Term*"), Some("*"), None).validate().is_ok());
+        assert!(rule(Some("[a-"), None, None).validate().is_err());
+        assert!(rule(Some("("), None, Some("regex")).validate().is_err());
+        assert!(rule(Some("a"), None, Some("wildcard")).validate().is_err());
+    }
+
+    #[test]
+    fn denylist_wins_over_allowlist() {
+        let terminal = app("Terminal", "zsh");
+        let editor = app("Code", "main.rs");
+        let browser = app("Firefox", "Docs");
+        let cases = [
+            // No rules: the thumb shows everywhere
+            (json!({}), [true, true, true]),
+            (json!({ "thumbAllowedApps": [] }), [true, true, true]),
+            (
+                json!({ "thumbDeniedApps": [{ "app": "Terminal" }] }),
+                [false, true, true],
+            ),
+            // A non-empty allowlist hides it everywhere else
+            (
+                json!({ "thumbAllowedApps": [{ "app": "Code" }, { "app": "Firefox" }] }),
+                [false, true, true],
+            ),
+            // Denied and allowed: denied
+            (
+                json!({
+                    "thumbDeniedApps": [{ "title": "Docs" }],
+                    "thumbAllowedApps": [{ "app": "*" }],
+                }),
+                [true, true, false],
+            ),
+            (
+                json!({
+                    "thumbDeniedApps": [{ "app": "^(Code|Terminal)$", "syntax": "regex" }],
+                    "thumbAllowedApps": [{ "app": "Code" }],
+                }),
+                [false, false, false],
+            ),
+        ];
+        for (config, expected) in cases.iter() {
+            let config: Config = serde_json::from_value(config.clone()).unwrap();
+            for (app, expected) in [&terminal, &editor, &browser].iter().zip(expected) {
+                assert_eq!(
+                    is_thumb_enabled_for(&config, app),
+                    *expected,
+                    "{:?} with {:?}",
+                    app,
+                    config.thumb_denied_apps
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,823 @@
+use debug_print::debug_println;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const DEFAULT_TIMEOUT_MS: u64 = 500;
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+// Bumped when a transaction begins and when it ends, so that the clipboard
+// watcher can tell the changes we made from the ones the user made.
+static TRANSACTION_GENERATION: AtomicUsize = AtomicUsize::new(0);
+static ACTIVE_TRANSACTIONS: AtomicUsize = AtomicUsize::new(0);
+
+// One representation of one clipboard item, e.g. the HTML of a copied web
+// page. `format` is whatever the platform uses to name it: a clipboard format
+// id on Windows, a UTI on macOS, a MIME type on Linux.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClipboardFormat {
+    pub item: usize,
+    pub format: String,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClipboardSnapshot {
+    pub formats: Vec<ClipboardFormat>,
+}
+
+// The operations a clipboard transaction needs. `SystemClipboard` implements
+// them for the real clipboard; anything else (e.g. an in-memory clipboard)
+// can be plugged in to exercise the transaction logic.
+pub trait ClipboardBackend {
+    // A value that changes every time the clipboard content changes
+    fn change_count(&mut self) -> Result<u64, String>;
+    fn snapshot(&mut self) -> Result<ClipboardSnapshot, String>;
+    fn restore(&mut self, snapshot: &ClipboardSnapshot) -> Result<(), String>;
+    fn clear(&mut self) -> Result<(), String>;
+    fn get_text(&mut self) -> Result<Option<String>, String>;
+    fn set_text(&mut self, text: &str) -> Result<(), String>;
+}
+
+// Borrows the clipboard for an operation such as copying the selection or
+// pasting text. Everything that was on the clipboard before is put back when
+// the transaction is dropped, whether the operation succeeded or not.
+pub struct ClipboardTransaction<'a, B: ClipboardBackend> {
+    backend: &'a mut B,
+    snapshot: ClipboardSnapshot,
+}
+
+impl<'a, B: ClipboardBackend> ClipboardTransaction<'a, B> {
+    pub fn begin(backend: &'a mut B) -> Result<Self, String> {
+        let snapshot = backend.snapshot()?;
+        debug_println!(
+            "clipboard snapshot: {:?}",
+            snapshot
+                .formats
+                .iter()
+                .map(|format| &format.format)
+                .collect::<Vec<_>>()
+        );
+        TRANSACTION_GENERATION.fetch_add(1, Ordering::AcqRel);
+        ACTIVE_TRANSACTIONS.fetch_add(1, Ordering::AcqRel);
+        Ok(Self { backend, snapshot })
+    }
+
+    // Runs `copy` and waits until the clipboard changes, for at most
+    // `timeout`. Returns None when nothing was copied, e.g. because nothing
+    // was selected.
+    pub fn copy_text<F: FnOnce()>(
+        &mut self,
+        copy: F,
+        timeout: Duration,
+    ) -> Result<Option<String>, String> {
+        self.backend.clear()?;
+        let change_count = self.backend.change_count()?;
+        copy();
+        let started_at = Instant::now();
+        loop {
+            if self.backend.change_count()? != change_count {
+                return self.backend.get_text();
+            }
+            if started_at.elapsed() >= timeout {
+                debug_println!("clipboard did not change within {:?}", timeout);
+                return Ok(None);
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    // Puts `text` on the clipboard and runs `paste`. The clipboard keeps the
+    // text until the transaction ends, so the target app has `settle` to
+    // read it.
+    pub fn paste_text<F: FnOnce()>(
+        &mut self,
+        text: &str,
+        paste: F,
+        settle: Duration,
+    ) -> Result<(), String> {
+        self.backend.set_text(text)?;
+        paste();
+        thread::sleep(settle);
+        Ok(())
+    }
+}
+
+impl<'a, B: ClipboardBackend> Drop for ClipboardTransaction<'a, B> {
+    fn drop(&mut self) {
+        if let Err(e) = self.backend.restore(&self.snapshot) {
+            eprintln!("failed to restore clipboard: {}", e);
+        }
+        ACTIVE_TRANSACTIONS.fetch_sub(1, Ordering::AcqRel);
+        TRANSACTION_GENERATION.fetch_add(1, Ordering::AcqRel);
+    }
+}
+
+// Changes from the same generation with no transaction active were made by
+// someone else.
+pub fn get_transaction_generation() -> usize {
+    TRANSACTION_GENERATION.load(Ordering::Acquire)
+}
+
+pub fn is_transaction_active() -> bool {
+    ACTIVE_TRANSACTIONS.load(Ordering::Acquire) > 0
+}
+
+pub fn get_copy_timeout() -> Duration {
+    let timeout_ms = crate::config::get_config()
+        .ok()
+        .and_then(|config| config.clipboard_timeout_ms)
+        .unwrap_or(DEFAULT_TIMEOUT_MS);
+    Duration::from_millis(timeout_ms)
+}
+
+pub struct SystemClipboard;
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use super::{ClipboardFormat, ClipboardSnapshot};
+    use std::thread;
+    use std::time::Duration;
+    use windows::Win32::Foundation::{HANDLE, HWND};
+    use windows::Win32::System::DataExchange::{
+        CloseClipboard, EmptyClipboard, EnumClipboardFormats, GetClipboardData,
+        GetClipboardSequenceNumber, OpenClipboard, SetClipboardData,
+    };
+    use windows::Win32::System::Memory::{
+        GlobalAlloc, GlobalLock, GlobalSize, GlobalUnlock, GMEM_MOVEABLE,
+    };
+
+    const CF_UNICODETEXT: u32 = 13;
+    // Formats whose handles are GDI objects rather than global memory. Windows
+    // synthesizes them from CF_DIB and CF_TEXT, so skipping them loses nothing.
+    const GDI_FORMATS: [u32; 6] = [2, 3, 9, 14, 0x82, 0x8E];
+
+    struct OpenedClipboard;
+
+    impl OpenedClipboard {
+        fn open() -> Result<Self, String> {
+            // Another app may hold the clipboard for a moment
+            for _ in 0..10 {
+                if unsafe { OpenClipboard(HWND(0)) }.as_bool() {
+                    return Ok(OpenedClipboard);
+                }
+                thread::sleep(Duration::from_millis(10));
+            }
+            Err("failed to open the clipboard".to_string())
+        }
+    }
+
+    impl Drop for OpenedClipboard {
+        fn drop(&mut self) {
+            unsafe { CloseClipboard() };
+        }
+    }
+
+    fn read_format(format: u32) -> Option<Vec<u8>> {
+        unsafe {
+            let handle = GetClipboardData(format).ok()?;
+            let size = GlobalSize(handle.0);
+            let ptr = GlobalLock(handle.0) as *const u8;
+            if ptr.is_null() {
+                return None;
+            }
+            let data = std::slice::from_raw_parts(ptr, size).to_vec();
+            GlobalUnlock(handle.0);
+            Some(data)
+        }
+    }
+
+    fn write_format(format: u32, data: &[u8]) -> Result<(), String> {
+        unsafe {
+            let memory = GlobalAlloc(GMEM_MOVEABLE, data.len().max(1));
+            if memory == 0 {
+                return Err("failed to allocate clipboard memory".to_string());
+            }
+            let ptr = GlobalLock(memory) as *mut u8;
+            if ptr.is_null() {
+                return Err("failed to lock clipboard memory".to_string());
+            }
+            std::ptr::copy_nonoverlapping(data.as_ptr(), ptr, data.len());
+            GlobalUnlock(memory);
+            SetClipboardData(format, HANDLE(memory))
+                .map(|_| ())
+                .map_err(|e| format!("failed to set clipboard format {}: {}", format, e))
+        }
+    }
+
+    pub fn change_count() -> Result<u64, String> {
+        Ok(unsafe { GetClipboardSequenceNumber() } as u64)
+    }
+
+    pub fn snapshot() -> Result<ClipboardSnapshot, String> {
+        let _clipboard = OpenedClipboard::open()?;
+        let mut formats = Vec::new();
+        let mut format = 0;
+        loop {
+            format = unsafe { EnumClipboardFormats(format) };
+            if format == 0 {
+                break;
+            }
+            if GDI_FORMATS.contains(&format) {
+                continue;
+            }
+            if let Some(data) = read_format(format) {
+                formats.push(ClipboardFormat {
+                    item: 0,
+                    format: format.to_string(),
+                    data,
+                });
+            }
+        }
+        Ok(ClipboardSnapshot { formats })
+    }
+
+    pub fn restore(snapshot: &ClipboardSnapshot) -> Result<(), String> {
+        let _clipboard = OpenedClipboard::open()?;
+        unsafe { EmptyClipboard() };
+        for format in &snapshot.formats {
+            let id = format.format.parse::<u32>().map_err(|e| e.to_string())?;
+            write_format(id, &format.data)?;
+        }
+        Ok(())
+    }
+
+    pub fn clear() -> Result<(), String> {
+        let _clipboard = OpenedClipboard::open()?;
+        unsafe { EmptyClipboard() };
+        Ok(())
+    }
+
+    pub fn get_text() -> Result<Option<String>, String> {
+        let _clipboard = OpenedClipboard::open()?;
+        Ok(read_format(CF_UNICODETEXT).map(|data| {
+            let wide = data
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .take_while(|&c| c != 0)
+                .collect::<Vec<_>>();
+            String::from_utf16_lossy(&wide)
+        }))
+    }
+
+    pub fn set_text(text: &str) -> Result<(), String> {
+        let _clipboard = OpenedClipboard::open()?;
+        unsafe { EmptyClipboard() };
+        let data = text
+            .encode_utf16()
+            .chain(std::iter::once(0))
+            .flat_map(|c| c.to_le_bytes())
+            .collect::<Vec<_>>();
+        write_format(CF_UNICODETEXT, &data)
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::{ClipboardFormat, ClipboardSnapshot};
+    use cocoa::base::{id, nil};
+    use cocoa::foundation::{NSArray, NSString};
+    use objc::{class, msg_send, sel, sel_impl};
+
+    unsafe fn general_pasteboard() -> id {
+        msg_send![class!(NSPasteboard), generalPasteboard]
+    }
+
+    unsafe fn to_string(string: id) -> String {
+        std::ffi::CStr::from_ptr(string.UTF8String())
+            .to_string_lossy()
+            .to_string()
+    }
+
+    pub fn change_count() -> Result<u64, String> {
+        let count: i64 = unsafe { msg_send![general_pasteboard(), changeCount] };
+        Ok(count as u64)
+    }
+
+    pub fn snapshot() -> Result<ClipboardSnapshot, String> {
+        let mut formats = Vec::new();
+        unsafe {
+            let items: id = msg_send![general_pasteboard(), pasteboardItems];
+            if items == nil {
+                return Ok(ClipboardSnapshot { formats });
+            }
+            for i in 0..items.count() {
+                let item = items.objectAtIndex(i);
+                let types: id = msg_send![item, types];
+                for j in 0..types.count() {
+                    let format = types.objectAtIndex(j);
+                    let data: id = msg_send![item, dataForType: format];
+                    if data == nil {
+                        continue;
+                    }
+                    let length: usize = msg_send![data, length];
+                    let bytes: *const u8 = msg_send![data, bytes];
+                    formats.push(ClipboardFormat {
+                        item: i as usize,
+                        format: to_string(format),
+                        data: if length == 0 {
+                            Vec::new()
+                        } else {
+                            std::slice::from_raw_parts(bytes, length).to_vec()
+                        },
+                    });
+                }
+            }
+        }
+        Ok(ClipboardSnapshot { formats })
+    }
+
+    pub fn restore(snapshot: &ClipboardSnapshot) -> Result<(), String> {
+        unsafe {
+            let pasteboard = general_pasteboard();
+            let _: i64 = msg_send![pasteboard, clearContents];
+            if snapshot.formats.is_empty() {
+                return Ok(());
+            }
+            let item_count = snapshot.formats.iter().map(|f| f.item).max().unwrap_or(0) + 1;
+            let items: id = msg_send![class!(NSMutableArray), array];
+            for i in 0..item_count {
+                let item: id = msg_send![class!(NSPasteboardItem), new];
+                for format in snapshot.formats.iter().filter(|f| f.item == i) {
+                    let data: id = msg_send![class!(NSData), dataWithBytes: format.data.as_ptr() length: format.data.len()];
+                    let format_type = NSString::alloc(nil).init_str(&format.format);
+                    let _: bool = msg_send![item, setData: data forType: format_type];
+                }
+                let _: () = msg_send![items, addObject: item];
+            }
+            let ok: bool = msg_send![pasteboard, writeObjects: items];
+            if ok {
+                Ok(())
+            } else {
+                Err("failed to restore the pasteboard".to_string())
+            }
+        }
+    }
+
+    pub fn clear() -> Result<(), String> {
+        let _: i64 = unsafe { msg_send![general_pasteboard(), clearContents] };
+        Ok(())
+    }
+
+    pub fn get_text() -> Result<Option<String>, String> {
+        unsafe {
+            let format = NSString::alloc(nil).init_str("public.utf8-plain-text");
+            let text: id = msg_send![general_pasteboard(), stringForType: format];
+            if text == nil {
+                Ok(None)
+            } else {
+                Ok(Some(to_string(text)))
+            }
+        }
+    }
+
+    pub fn set_text(text: &str) -> Result<(), String> {
+        unsafe {
+            let pasteboard = general_pasteboard();
+            let _: i64 = msg_send![pasteboard, clearContents];
+            let string = NSString::alloc(nil).init_str(text);
+            let format = NSString::alloc(nil).init_str("public.utf8-plain-text");
+            let ok: bool = msg_send![pasteboard, setString: string forType: format];
+            if ok {
+                Ok(())
+            } else {
+                Err("failed to set the pasteboard text".to_string())
+            }
+        }
+    }
+}
+
+// X11 and Wayland have no change counter and no way to offer several formats
+// from a command line tool, so the rich formats are read with xclip/wl-paste
+// and the ones arboard can write (HTML with its text, images, text) are put
+// back; file lists are put back on their own. On Wayland everything goes
+// through wl-clipboard, which uses the data control protocol and so works
+// without a focused window of our own.
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::{ClipboardFormat, ClipboardSnapshot};
+    use arboard::{Clipboard, ImageData};
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    use crate::utils::is_wayland;
+
+    const TEXT: &str = "text/plain;charset=utf-8";
+    const HTML: &str = "text/html";
+    const URI_LIST: &str = "text/uri-list";
+    const IMAGE: &str = "image/x-rgba";
+    const PNG: &str = "image/png";
+
+    fn read_target(target: &str) -> Option<Vec<u8>> {
+        let output = if is_wayland() {
+            Command::new("wl-paste")
+                .args(["--no-newline", "--type", target])
+                .output()
+        } else {
+            Command::new("xclip")
+                .args(["-selection", "clipboard", "-t", target, "-o"])
+                .output()
+        }
+        .ok()?;
+        if output.status.success() && !output.stdout.is_empty() {
+            Some(output.stdout)
+        } else {
+            None
+        }
+    }
+
+    fn write_target(target: &str, data: &[u8]) -> Result<(), String> {
+        let mut child = if is_wayland() {
+            Command::new("wl-copy")
+                .args(["--type", target])
+                .stdin(Stdio::piped())
+                .spawn()
+        } else {
+            Command::new("xclip")
+                .args(["-selection", "clipboard", "-t", target, "-i"])
+                .stdin(Stdio::piped())
+                .spawn()
+        }
+        .map_err(|e| format!("failed to write clipboard target {}: {}", target, e))?;
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(data)
+            .map_err(|e| e.to_string())?;
+        child.wait().map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    fn find<'a>(snapshot: &'a ClipboardSnapshot, format: &str) -> Option<&'a ClipboardFormat> {
+        snapshot
+            .formats
+            .iter()
+            .find(|f| f.format.starts_with(format))
+    }
+
+    // Neither X11 nor Wayland counts clipboard changes, so the text is hashed
+    // instead. It's polled often, so images aren't decoded and only one
+    // wl-paste is spawned; copying an image shows as the text going away.
+    pub fn change_count() -> Result<u64, String> {
+        let mut hasher = DefaultHasher::new();
+        if is_wayland() {
+            read_target(TEXT).hash(&mut hasher);
+        } else {
+            let mut clipboard = Clipboard::new().map_err(|e| e.to_string())?;
+            clipboard.get_text().ok().hash(&mut hasher);
+        }
+        Ok(hasher.finish())
+    }
+
+    pub fn snapshot() -> Result<ClipboardSnapshot, String> {
+        if is_wayland() {
+            let formats = [TEXT, HTML, URI_LIST, PNG]
+                .iter()
+                .filter_map(|target| {
+                    read_target(target).map(|data| ClipboardFormat {
+                        item: 0,
+                        format: target.to_string(),
+                        data,
+                    })
+                })
+                .collect();
+            return Ok(ClipboardSnapshot { formats });
+        }
+        let mut clipboard = Clipboard::new().map_err(|e| e.to_string())?;
+        let mut formats = Vec::new();
+        let mut push = |format: String, data: Vec<u8>| {
+            formats.push(ClipboardFormat {
+                item: 0,
+                format,
+                data,
+            })
+        };
+        if let Ok(text) = clipboard.get_text() {
+            push(TEXT.to_string(), text.into_bytes());
+        }
+        for target in [HTML, URI_LIST] {
+            if let Some(data) = read_target(target) {
+                push(target.to_string(), data);
+            }
+        }
+        if let Ok(image) = clipboard.get_image() {
+            push(
+                format!("{};width={};height={}", IMAGE, image.width, image.height),
+                image.bytes.into_owned(),
+            );
+        }
+        Ok(ClipboardSnapshot { formats })
+    }
+
+    // wl-copy offers a single type per selection. Text, which wl-copy offers
+    // under every text type and every app can paste, is put back when there
+    // is any; otherwise the richest of the other types.
+    fn restore_wayland(snapshot: &ClipboardSnapshot) -> Result<(), String> {
+        if let Some(text) = find(snapshot, TEXT) {
+            return write_text_wayland(&text.data);
+        }
+        for target in [URI_LIST, PNG, HTML] {
+            if let Some(format) = find(snapshot, target) {
+                return write_target(target, &format.data);
+            }
+        }
+        clear()
+    }
+
+    // Without --type wl-copy offers text/plain, UTF8_STRING, STRING and TEXT
+    fn write_text_wayland(data: &[u8]) -> Result<(), String> {
+        let mut child = Command::new("wl-copy")
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("failed to run wl-copy: {}", e))?;
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(data)
+            .map_err(|e| e.to_string())?;
+        child.wait().map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    pub fn restore(snapshot: &ClipboardSnapshot) -> Result<(), String> {
+        if is_wayland() {
+            return restore_wayland(snapshot);
+        }
+        let mut clipboard = Clipboard::new().map_err(|e| e.to_string())?;
+        let text = find(snapshot, TEXT).map(|f| String::from_utf8_lossy(&f.data).to_string());
+        if let Some(html) = find(snapshot, HTML) {
+            return clipboard
+                .set_html(String::from_utf8_lossy(&html.data).to_string(), text)
+                .map_err(|e| e.to_string());
+        }
+        if let Some(uri_list) = find(snapshot, URI_LIST) {
+            return write_target(URI_LIST, &uri_list.data);
+        }
+        if let Some(image) = find(snapshot, IMAGE) {
+            let size = |key: &str| {
+                image
+                    .format
+                    .split(';')
+                    .find_map(|part| part.strip_prefix(key))
+                    .and_then(|value| value.parse::<usize>().ok())
+                    .unwrap_or(0)
+            };
+            return clipboard
+                .set_image(ImageData {
+                    width: size("width="),
+                    height: size("height="),
+                    bytes: image.data.clone().into(),
+                })
+                .map_err(|e| e.to_string());
+        }
+        match text {
+            Some(text) => clipboard.set_text(text),
+            None => clipboard.clear(),
+        }
+        .map_err(|e| e.to_string())
+    }
+
+    pub fn clear() -> Result<(), String> {
+        if is_wayland() {
+            let status = Command::new("wl-copy")
+                .arg("--clear")
+                .status()
+                .map_err(|e| format!("failed to run wl-copy: {}", e))?;
+            return if status.success() {
+                Ok(())
+            } else {
+                Err("wl-copy --clear failed".to_string())
+            };
+        }
+        Clipboard::new()
+            .and_then(|mut clipboard| clipboard.clear())
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn get_text() -> Result<Option<String>, String> {
+        if is_wayland() {
+            return Ok(read_target(TEXT).map(|data| String::from_utf8_lossy(&data).to_string()));
+        }
+        let mut clipboard = Clipboard::new().map_err(|e| e.to_string())?;
+        Ok(clipboard.get_text().ok())
+    }
+
+    pub fn set_text(text: &str) -> Result<(), String> {
+        if is_wayland() {
+            return write_text_wayland(text.as_bytes());
+        }
+        Clipboard::new()
+            .and_then(|mut clipboard| clipboard.set_text(text))
+            .map_err(|e| e.to_string())
+    }
+}
+
+impl ClipboardBackend for SystemClipboard {
+    fn change_count(&mut self) -> Result<u64, String> {
+        platform::change_count()
+    }
+
+    fn snapshot(&mut self) -> Result<ClipboardSnapshot, String> {
+        platform::snapshot()
+    }
+
+    fn restore(&mut self, snapshot: &ClipboardSnapshot) -> Result<(), String> {
+        platform::restore(snapshot)
+    }
+
+    fn clear(&mut self) -> Result<(), String> {
+        platform::clear()
+    }
+
+    fn get_text(&mut self) -> Result<Option<String>, String> {
+        platform::get_text()
+    }
+
+    fn set_text(&mut self, text: &str) -> Result<(), String> {
+        platform::set_text(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[derive(Default)]
+    struct MemoryState {
+        formats: Vec<ClipboardFormat>,
+        change_count: u64,
+        fail_get_text: bool,
+    }
+
+    impl MemoryState {
+        fn set_text(&mut self, text: &str) {
+            self.formats = vec![text_format(text)];
+            self.change_count += 1;
+        }
+    }
+
+    // An in-memory clipboard. The state is shared, so that the copy and paste
+    // callbacks can play the target app while the transaction holds the
+    // backend.
+    #[derive(Clone, Default)]
+    struct MemoryClipboard(Rc<RefCell<MemoryState>>);
+
+    impl ClipboardBackend for MemoryClipboard {
+        fn change_count(&mut self) -> Result<u64, String> {
+            Ok(self.0.borrow().change_count)
+        }
+
+        fn snapshot(&mut self) -> Result<ClipboardSnapshot, String> {
+            Ok(ClipboardSnapshot {
+                formats: self.0.borrow().formats.clone(),
+            })
+        }
+
+        fn restore(&mut self, snapshot: &ClipboardSnapshot) -> Result<(), String> {
+            let mut state = self.0.borrow_mut();
+            state.formats = snapshot.formats.clone();
+            state.change_count += 1;
+            Ok(())
+        }
+
+        fn clear(&mut self) -> Result<(), String> {
+            let mut state = self.0.borrow_mut();
+            state.formats.clear();
+            state.change_count += 1;
+            Ok(())
+        }
+
+        fn get_text(&mut self) -> Result<Option<String>, String> {
+            let state = self.0.borrow();
+            if state.fail_get_text {
+                return Err("the clipboard is locked".to_string());
+            }
+            Ok(state
+                .formats
+                .iter()
+                .find(|format| format.format == "text/plain")
+                .map(|format| String::from_utf8_lossy(&format.data).to_string()))
+        }
+
+        fn set_text(&mut self, text: &str) -> Result<(), String> {
+            self.0.borrow_mut().set_text(text);
+            Ok(())
+        }
+    }
+
+    fn text_format(text: &str) -> ClipboardFormat {
+        ClipboardFormat {
+            item: 0,
+            format: "text/plain".to_string(),
+            data: text.as_bytes().to_vec(),
+        }
+    }
+
+    // A copied web page: text, HTML and an image of a second item
+    fn user_clipboard() -> MemoryClipboard {
+        let clipboard = MemoryClipboard::default();
+        clipboard.0.borrow_mut().formats = vec![
+            text_format("user text"),
+            ClipboardFormat {
+                item: 0,
+                format: "text/html".to_string(),
+                data: b"<b>user text</b>".to_vec(),
+            },
+            ClipboardFormat {
+                item: 1,
+                format: "image/png".to_string(),
+                data: vec![0x89, b'P', b'N', b'G'],
+            },
+        ];
+        clipboard
+    }
+
+    fn formats(clipboard: &MemoryClipboard) -> Vec<ClipboardFormat> {
+        clipboard.0.borrow().formats.clone()
+    }
+
+    #[test]
+    fn restores_after_copying() {
+        let mut clipboard = user_clipboard();
+        let before = formats(&clipboard);
+        let app = clipboard.clone();
+        let generation = get_transaction_generation();
+        {
+            let mut transaction = ClipboardTransaction::begin(&mut clipboard).unwrap();
+            assert!(is_transaction_active());
+            let copied = transaction
+                .copy_text(
+                    || app.0.borrow_mut().set_text("selected"),
+                    Duration::from_secs(1),
+                )
+                .unwrap();
+            assert_eq!(copied.as_deref(), Some("selected"));
+        }
+        assert_eq!(formats(&clipboard), before);
+        assert!(get_transaction_generation() >= generation + 2);
+    }
+
+    #[test]
+    fn restores_after_an_error() {
+        let mut clipboard = user_clipboard();
+        let before = formats(&clipboard);
+        let app = clipboard.clone();
+        {
+            let mut transaction = ClipboardTransaction::begin(&mut clipboard).unwrap();
+            let result = transaction.copy_text(
+                || {
+                    let mut state = app.0.borrow_mut();
+                    state.set_text("selected");
+                    state.fail_get_text = true;
+                },
+                Duration::from_secs(1),
+            );
+            assert!(result.is_err());
+        }
+        assert_eq!(formats(&clipboard), before);
+    }
+
+    #[test]
+    fn restores_after_a_timeout() {
+        let mut clipboard = user_clipboard();
+        let before = formats(&clipboard);
+        {
+            let mut transaction = ClipboardTransaction::begin(&mut clipboard).unwrap();
+            let started_at = Instant::now();
+            // Nothing is selected, so nothing gets copied
+            let copied = transaction
+                .copy_text(|| {}, Duration::from_millis(50))
+                .unwrap();
+            assert_eq!(copied, None);
+            assert!(started_at.elapsed() >= Duration::from_millis(50));
+        }
+        assert_eq!(formats(&clipboard), before);
+    }
+
+    #[test]
+    fn restores_after_pasting() {
+        let mut clipboard = user_clipboard();
+        let before = formats(&clipboard);
+        let app = clipboard.clone();
+        let pasted = Rc::new(RefCell::new(None));
+        {
+            let mut transaction = ClipboardTransaction::begin(&mut clipboard).unwrap();
+            let pasted = pasted.clone();
+            transaction
+                .paste_text(
+                    "translation",
+                    || *pasted.borrow_mut() = app.clone().get_text().unwrap(),
+                    Duration::from_millis(1),
+                )
+                .unwrap();
+        }
+        assert_eq!(pasted.borrow().as_deref(), Some("translation"));
+        assert_eq!(formats(&clipboard), before);
+    }
+}
@@ -0,0 +1,149 @@
+use debug_print::debug_println;
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::clipboard::{
+    get_transaction_generation, is_transaction_active, ClipboardBackend, SystemClipboard,
+};
+use crate::config::{get_config, set_config_value, Config};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(300);
+const DEFAULT_DEBOUNCE_MS: u64 = 500;
+const DEFAULT_MIN_LENGTH: usize = 2;
+const DEFAULT_MAX_LENGTH: usize = 5000;
+
+static WATCHER_STARTED: AtomicBool = AtomicBool::new(false);
+// The last text sent to the translator, so that copying it again is ignored
+static LAST_TEXT: Mutex<Option<String>> = Mutex::new(None);
+
+pub fn is_clipboard_watch_enabled() -> bool {
+    get_config()
+        .ok()
+        .and_then(|config| config.clipboard_watch)
+        .unwrap_or(false)
+}
+
+pub fn set_clipboard_watch_enabled(enabled: bool) -> Result<(), String> {
+    set_config_value("clipboardWatch", serde_json::Value::Bool(enabled))
+}
+
+// Whether the copied text should be translated: it's within the length limits
+// and not already in the target language.
+fn should_translate(config: &Config, text: &str) -> bool {
+    let length = text.chars().count();
+    let min_length = config
+        .clipboard_watch_min_length
+        .unwrap_or(DEFAULT_MIN_LENGTH);
+    let max_length = config
+        .clipboard_watch_max_length
+        .unwrap_or(DEFAULT_MAX_LENGTH);
+    if length < min_length || length > max_length {
+        debug_println!("clipboard watch: skipped {} chars", length);
+        return false;
+    }
+    let target = crate::lang_rules::route_target_language_with(config, text)
+        .or_else(|| config.default_target_language.clone());
+    if let (Some(target), Some(detected)) = (target, crate::lang::detect_lang_code(text, config)) {
+        if crate::lang::is_same_language(&target, &detected) {
+            debug_println!("clipboard watch: already in {}", detected);
+            return false;
+        }
+    }
+    true
+}
+
+fn translate(text: String) {
+    *LAST_TEXT.lock() = Some(text.clone());
+    // The window is shown first, so that it's there to receive the text
+    crate::windows::show_translator_window(false, true, false);
+    crate::utils::show();
+    crate::utils::send_text(text);
+}
+
+// Waits until the clipboard has stayed the same for the debounce time and
+// returns its change count then, or None if it was changed by us meanwhile.
+fn wait_until_settled(
+    clipboard: &mut SystemClipboard,
+    mut change_count: u64,
+    debounce: Duration,
+    generation: usize,
+) -> Option<u64> {
+    let mut settled_since = Instant::now();
+    while settled_since.elapsed() < debounce {
+        thread::sleep(POLL_INTERVAL.min(debounce));
+        if is_transaction_active() || get_transaction_generation() != generation {
+            return None;
+        }
+        let current = clipboard.change_count().ok()?;
+        if current != change_count {
+            change_count = current;
+            settled_since = Instant::now();
+        }
+    }
+    Some(change_count)
+}
+
+fn watch() {
+    let mut clipboard = SystemClipboard;
+    // None until the first poll with the watch mode on, so that what was on
+    // the clipboard before isn't translated
+    let mut last_change_count: Option<u64> = None;
+    let mut last_generation = get_transaction_generation();
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        let config = match get_config() {
+            Ok(config) => config,
+            Err(_) => continue,
+        };
+        if !config.clipboard_watch.unwrap_or(false) {
+            last_change_count = None;
+            continue;
+        }
+        let change_count = match clipboard.change_count() {
+            Ok(change_count) => change_count,
+            Err(_) => continue,
+        };
+        let generation = get_transaction_generation();
+        // Copying the selection and writing borrow the clipboard; whatever
+        // changed around them is ours, as are the copies made in our windows.
+        let is_ours = is_transaction_active()
+            || generation != last_generation
+            || crate::app_rules::is_own_app_foreground();
+        let changed = last_change_count.map_or(false, |count| count != change_count);
+        last_change_count = Some(change_count);
+        last_generation = generation;
+        if !changed || is_ours {
+            continue;
+        }
+        let debounce = Duration::from_millis(
+            config
+                .clipboard_watch_debounce_ms
+                .unwrap_or(DEFAULT_DEBOUNCE_MS),
+        );
+        match wait_until_settled(&mut clipboard, change_count, debounce, generation) {
+            Some(change_count) => last_change_count = Some(change_count),
+            None => continue,
+        }
+        let text = match clipboard.get_text() {
+            Ok(Some(text)) => text.trim().to_string(),
+            _ => continue,
+        };
+        if LAST_TEXT.lock().as_deref() == Some(text.as_str()) {
+            continue;
+        }
+        if should_translate(&config, &text) {
+            translate(text);
+        }
+    }
+}
+
+// Starts the clipboard watcher if it isn't running yet. Once started it keeps
+// running, and leaves the clipboard alone while the watch mode is off.
+pub fn start_clipboard_watch() {
+    if WATCHER_STARTED.swap(true, Ordering::AcqRel) {
+        return;
+    }
+    thread::spawn(watch);
+}
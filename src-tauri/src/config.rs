@@ -1,26 +1,147 @@
 use parking_lot::Mutex;
+use std::collections::HashMap;
 use tauri::Manager;
 use tauri::{path::BaseDirectory, AppHandle};
 
 use serde::{Deserialize, Serialize};
 
+use crate::app_rules::AppRule;
+use crate::gesture::GestureConfig;
+use crate::key_chord::{parse_key_chord, validate_shortcut};
+use crate::lang_rules::TargetLanguageRule;
+use crate::preprocess::PreprocessConfig;
 use crate::APP_HANDLE;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Config {
     pub hotkey: Option<String>,
+    pub display_window_hotkey: Option<String>,
     pub ocr_hotkey: Option<String>,
     pub writing_hotkey: Option<String>,
+    pub writing_hotkeys: Option<Vec<WritingHotkey>>,
     pub writing_newline_hotkey: Option<String>,
+    // Newline chords for specific apps, keyed by app name
+    pub writing_newline_hotkeys: Option<HashMap<String, String>>,
+    pub writing_undo_hotkey: Option<String>,
+    pub writing_cancel_hotkey: Option<String>,
+    // Arms the leader-key mode, in which the next key picks the action
+    pub leader_hotkey: Option<String>,
+    pub leader_timeout_ms: Option<u64>,
+    // How the writing result gets into the field: "auto" (set it directly,
+    // falling back to keystrokes), "accessibility", "clipboard" or "keystroke"
+    pub writing_input_method: Option<String>,
+    pub writing_speed: Option<String>,
+    pub writing_max_typing_ms: Option<u64>,
     pub restore_previous_position: Option<bool>,
     pub always_show_icons: Option<bool>,
+    // Clicking the thumb opens a popover with quick actions instead of the
+    // translator
+    pub thumb_popover: Option<bool>,
+    pub selection_gesture: Option<GestureConfig>,
+    // Apps in which the thumb never shows, and if not empty, the only apps in
+    // which it shows
+    pub thumb_denied_apps: Option<Vec<AppRule>>,
+    pub thumb_allowed_apps: Option<Vec<AppRule>>,
     pub allow_using_clipboard_when_selected_text_not_available: Option<bool>,
+    pub clipboard_timeout_ms: Option<u64>,
+    // Translates the text copied in other apps as soon as the clipboard
+    // changes
+    pub clipboard_watch: Option<bool>,
+    pub clipboard_watch_debounce_ms: Option<u64>,
+    pub clipboard_watch_min_length: Option<usize>,
+    pub clipboard_watch_max_length: Option<usize>,
+    pub default_target_language: Option<String>,
+    // The languages the user reads; local language detection picks only
+    // among them
+    pub detect_languages: Option<Vec<String>>,
+    // Pick the target language by the language of the text; the first
+    // matching rule wins
+    pub target_language_rules: Option<Vec<TargetLanguageRule>>,
+    pub writing_target_language: Option<String>,
+    // Converts Chinese translation results into the variant of the target
+    // language, e.g. Simplified into Traditional for "zh-TW"
+    pub chinese_conversion: Option<bool>,
+    // The variant a "zh-Hant" target is converted into: "zh-TW" or "zh-HK"
+    pub traditional_chinese_variant: Option<String>,
     pub automatic_check_for_updates: Option<bool>,
     pub hide_the_icon_in_the_dock: Option<bool>,
+    pub ocr_preprocess: Option<PreprocessConfig>,
+    // Tesseract language codes, e.g. "eng" or "chi_sim"
+    pub ocr_languages: Option<Vec<String>>,
+    pub ocr_history_max_entries: Option<usize>,
+    pub ocr_history_max_days: Option<u64>,
+    pub live_ocr_interval_ms: Option<u64>,
+    pub live_ocr_change_threshold: Option<f64>,
+    // Where the StarDict, dictd and CSV dictionaries are loaded from
+    pub dictionary_dir: Option<String>,
+}
+
+// An extra writing hotkey with its own action ("translate", "polish",
+// "fix-grammar" or "summarize") and target language. Missing fields fall back
+// to translating into `writingTargetLanguage`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WritingHotkey {
+    pub hotkey: String,
+    pub action: Option<String>,
+    pub target_language: Option<String>,
+}
+
+impl Config {
+    // Checks every key chord in the config, so that a typo shows up when the
+    // config is loaded instead of silently doing nothing when the chord is used.
+    pub fn validate(&self) -> Vec<String> {
+        // The global shortcuts and the writing chords share a grammar; the
+        // writing chords are typed, so their keys also have to be typeable
+        let mut errors: Vec<String> = crate::hotkeys::configured_hotkeys(self)
+            .into_iter()
+            .filter_map(|(name, hotkey, _)| {
+                validate_shortcut(&hotkey)
+                    .err()
+                    .map(|e| format!("{}: {}", name, e))
+            })
+            .collect();
+        let mut chords: Vec<(String, &String)> = Vec::new();
+        if let Some(chord) = &self.writing_newline_hotkey {
+            chords.push(("writingNewlineHotkey".to_string(), chord));
+        }
+        if let Some(writing_newline_hotkeys) = &self.writing_newline_hotkeys {
+            for (app_name, chord) in writing_newline_hotkeys {
+                chords.push((format!("writingNewlineHotkeys.{}", app_name), chord));
+            }
+        }
+        errors.extend(
+            chords
+                .into_iter()
+                .filter(|(_, chord)| !chord.trim().is_empty())
+                .filter_map(|(name, chord)| {
+                    parse_key_chord(chord)
+                        .err()
+                        .map(|e| format!("{}: {}", name, e))
+                }),
+        );
+        for (name, rules) in [
+            ("thumbDeniedApps", &self.thumb_denied_apps),
+            ("thumbAllowedApps", &self.thumb_allowed_apps),
+        ] {
+            for (i, rule) in rules.iter().flatten().enumerate() {
+                if let Err(e) = rule.validate() {
+                    errors.push(format!("{}[{}]: {}", name, i, e));
+                }
+            }
+        }
+        for (i, rule) in self.target_language_rules.iter().flatten().enumerate() {
+            if let Err(e) = rule.validate() {
+                errors.push(format!("targetLanguageRules[{}]: {}", i, e));
+            }
+        }
+        errors
+    }
 }
 
 static CONFIG_CACHE: Mutex<Option<Config>> = Mutex::new(None);
+static CONFIG_ERRORS: Mutex<Vec<String>> = Mutex::new(Vec::new());
 
 pub fn get_config() -> Result<Config, Box<dyn std::error::Error>> {
     let app_handle = APP_HANDLE.get().unwrap();
@@ -33,10 +154,26 @@ pub fn get_config_by_app(app: &AppHandle) -> Result<Config, Box<dyn std::error::
     }
     let config_content = get_config_content_by_app(app)?;
     let config: Config = serde_json::from_str(&config_content)?;
+    let errors = config.validate();
+    for error in &errors {
+        eprintln!("config error: {}", error);
+    }
+    if !errors.is_empty() {
+        app.emit("config-errors", errors.clone())
+            .unwrap_or_default();
+    }
+    *CONFIG_ERRORS.lock() = errors;
     CONFIG_CACHE.lock().replace(config.clone());
     Ok(config)
 }
 
+// The validation errors of the current config, loading it first if needed.
+#[tauri::command]
+pub fn get_config_errors() -> Result<Vec<String>, String> {
+    get_config().map_err(|e| e.to_string())?;
+    Ok(CONFIG_ERRORS.lock().clone())
+}
+
 #[tauri::command]
 pub fn clear_config_cache() {
     CONFIG_CACHE.lock().take();
@@ -51,7 +188,24 @@ pub fn get_config_content() -> Result<String, String> {
     }
 }
 
-pub fn get_config_content_by_app(app: &AppHandle) -> Result<String, String> {
+// Sets one top-level key of config.json, keeping the rest as the settings
+// window wrote it, and reloads the config.
+pub fn set_config_value(key: &str, value: serde_json::Value) -> Result<(), String> {
+    let app = APP_HANDLE
+        .get()
+        .ok_or_else(|| "Config directory not found".to_string())?;
+    let content = get_config_content_by_app(app)?;
+    let mut config: serde_json::Map<String, serde_json::Value> =
+        serde_json::from_str(&content).map_err(|e| e.to_string())?;
+    config.insert(key.to_string(), value);
+    let content = serde_json::to_string(&config).map_err(|e| e.to_string())?;
+    std::fs::write(get_config_path_by_app(app), content)
+        .map_err(|_| "Failed to write config file".to_string())?;
+    clear_config_cache();
+    Ok(())
+}
+
+fn get_config_path_by_app(app: &AppHandle) -> std::path::PathBuf {
     let app_config_dir = app
         .path()
         .resolve("xyz.yetone.apps.openai-translator", BaseDirectory::Config)
@@ -59,7 +213,11 @@ pub fn get_config_content_by_app(app: &AppHandle) -> Result<String, String> {
     if !app_config_dir.exists() {
         std::fs::create_dir_all(&app_config_dir).unwrap();
     }
-    let config_path = app_config_dir.join("config.json");
+    app_config_dir.join("config.json")
+}
+
+pub fn get_config_content_by_app(app: &AppHandle) -> Result<String, String> {
+    let config_path = get_config_path_by_app(app);
     if config_path.exists() {
         match std::fs::read_to_string(config_path) {
             Ok(content) => Ok(content),
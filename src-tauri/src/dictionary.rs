@@ -0,0 +1,1259 @@
+use debug_print::debug_println;
+use flate2::{read::GzDecoder, Decompress, FlushDecompress};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use tauri::path::BaseDirectory;
+use tauri::Manager;
+
+use crate::lang::{is_same_language, parse_lang};
+
+const MAX_WORD_CHARS: usize = 48;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DictionaryEntry {
+    pub dictionary: String,
+    pub headword: String,
+    pub phonetic: Option<String>,
+    pub senses: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WordLookup {
+    pub word: String,
+    // The dictionary form the entries were found under, when it differs from
+    // the looked up word (e.g. "run" for "running")
+    pub lemma: Option<String>,
+    pub entries: Vec<DictionaryEntry>,
+}
+
+// Reads ranges of a .dict file. Dictzip files (.dict.dz) are gzip files made
+// of independently compressed chunks, so a range is read by inflating only the
+// chunks it covers; other gzip files are inflated in full when loaded.
+enum DictData {
+    Plain(PathBuf),
+    Dictzip {
+        path: PathBuf,
+        chunk_len: u64,
+        // File offset and compressed size of each chunk
+        chunks: Vec<(u64, usize)>,
+    },
+    Memory(Vec<u8>),
+}
+
+enum Record {
+    // A definition stored in the .dict file of the dictionary
+    Data {
+        headword: String,
+        offset: u64,
+        size: u64,
+    },
+    Inline(DictionaryEntry),
+}
+
+enum Format {
+    StarDict { type_sequence: Option<String> },
+    Dict,
+    Csv,
+}
+
+struct Dictionary {
+    name: String,
+    format: Format,
+    data: Option<DictData>,
+    // Keyed by the lowercased headword
+    index: HashMap<String, Vec<Record>>,
+}
+
+// The languages a dictionary translates between, declared by the name of the
+// folder it is in, e.g. "en-zh" or "zh-Hant-en".
+#[derive(Debug, Clone, PartialEq)]
+struct LanguagePair {
+    source: String,
+    target: String,
+}
+
+impl LanguagePair {
+    fn parse(name: &str) -> Option<Self> {
+        name.match_indices('-')
+            .map(|(i, _)| (&name[..i], &name[i + 1..]))
+            .find(|(source, target)| parse_lang(source).is_some() && parse_lang(target).is_some())
+            .map(|(source, target)| LanguagePair {
+                source: source.to_string(),
+                target: target.to_string(),
+            })
+    }
+
+    fn matches(&self, source_lang: &str, target_lang: &str) -> bool {
+        is_same_language(&self.source, source_lang) && is_same_language(&self.target, target_lang)
+    }
+}
+
+type PairedDictionary = (LanguagePair, Dictionary);
+
+// The loaded dictionaries with the dir they were loaded from, so that changing
+// `dictionaryDir` loads the new dir on the next lookup
+static DICTIONARIES: Mutex<Option<(PathBuf, Vec<PairedDictionary>)>> = Mutex::new(None);
+
+// The chunk length, and the file offset and compressed size of each chunk
+type DictzipChunks = (u64, Vec<(u64, usize)>);
+
+fn read_u16_le(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes([*data.get(at)?, *data.get(at + 1)?]))
+}
+
+fn read_dictzip_header(path: &Path) -> Result<Option<DictzipChunks>, String> {
+    let mut file = File::open(path).map_err(|e| e.to_string())?;
+    let mut header = vec![0; 64 * 1024];
+    let len = file.read(&mut header).map_err(|e| e.to_string())?;
+    header.truncate(len);
+    parse_dictzip_header(&header).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+// Reads the chunk length and the file offset and compressed size of each chunk
+// from the RA field of a gzip header; None for a gzip file without one.
+fn parse_dictzip_header(header: &[u8]) -> Result<Option<DictzipChunks>, String> {
+    if header.len() < 10 || header[0] != 0x1f || header[1] != 0x8b {
+        return Err("not a gzip file".to_string());
+    }
+    let flags = header[3];
+    let mut pos = 10;
+    let mut random_access = None;
+    if flags & 0x04 != 0 {
+        let extra_len = read_u16_le(header, pos).ok_or("truncated gzip header")? as usize;
+        pos += 2;
+        let extra_end = pos + extra_len;
+        let mut field = pos;
+        while field + 4 <= extra_end {
+            let field_len = read_u16_le(header, field + 2).ok_or("truncated gzip header")? as usize;
+            if header[field] == b'R' && header[field + 1] == b'A' {
+                let data = field + 4;
+                let chunk_len = read_u16_le(header, data + 2).ok_or("truncated gzip header")?;
+                let chunk_count = read_u16_le(header, data + 4).ok_or("truncated gzip header")?;
+                let sizes = (0..chunk_count as usize)
+                    .map(|i| read_u16_le(header, data + 6 + i * 2).map(|size| size as usize))
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or("truncated dictzip chunk table")?;
+                random_access = Some((chunk_len as u64, sizes));
+            }
+            field += 4 + field_len;
+        }
+        pos = extra_end;
+    }
+    for flag in [0x08, 0x10] {
+        if flags & flag != 0 {
+            while *header.get(pos).ok_or("truncated gzip header")? != 0 {
+                pos += 1;
+            }
+            pos += 1;
+        }
+    }
+    if flags & 0x02 != 0 {
+        pos += 2;
+    }
+    Ok(random_access.map(|(chunk_len, sizes)| {
+        let mut offset = pos as u64;
+        let chunks = sizes
+            .into_iter()
+            .map(|size| {
+                let chunk = (offset, size);
+                offset += size as u64;
+                chunk
+            })
+            .collect();
+        (chunk_len, chunks)
+    }))
+}
+
+impl DictData {
+    fn open(path: PathBuf) -> Result<Self, String> {
+        if path
+            .extension()
+            .map_or(false, |extension| extension == "dz")
+        {
+            return match read_dictzip_header(&path)? {
+                Some((chunk_len, chunks)) => Ok(DictData::Dictzip {
+                    path,
+                    chunk_len,
+                    chunks,
+                }),
+                None => {
+                    let file = File::open(&path).map_err(|e| e.to_string())?;
+                    let mut data = Vec::new();
+                    GzDecoder::new(file)
+                        .read_to_end(&mut data)
+                        .map_err(|e| e.to_string())?;
+                    Ok(DictData::Memory(data))
+                }
+            };
+        }
+        Ok(DictData::Plain(path))
+    }
+
+    fn read(&self, offset: u64, size: u64) -> Result<Vec<u8>, String> {
+        match self {
+            DictData::Plain(path) => {
+                let mut file = File::open(path).map_err(|e| e.to_string())?;
+                file.seek(SeekFrom::Start(offset))
+                    .map_err(|e| e.to_string())?;
+                let mut data = vec![0; size as usize];
+                file.read_exact(&mut data).map_err(|e| e.to_string())?;
+                Ok(data)
+            }
+            DictData::Memory(data) => data
+                .get(offset as usize..(offset + size) as usize)
+                .map(|data| data.to_vec())
+                .ok_or_else(|| "definition is out of range".to_string()),
+            DictData::Dictzip {
+                path,
+                chunk_len,
+                chunks,
+            } => {
+                if size == 0 {
+                    return Ok(Vec::new());
+                }
+                let first = (offset / chunk_len) as usize;
+                let last = ((offset + size - 1) / chunk_len) as usize;
+                let mut file = File::open(path).map_err(|e| e.to_string())?;
+                let mut data = Vec::new();
+                for &(chunk_offset, chunk_size) in chunks
+                    .get(first..=last)
+                    .ok_or("definition is out of range")?
+                {
+                    let mut compressed = vec![0; chunk_size];
+                    file.seek(SeekFrom::Start(chunk_offset))
+                        .map_err(|e| e.to_string())?;
+                    file.read_exact(&mut compressed)
+                        .map_err(|e| e.to_string())?;
+                    let mut chunk = Vec::with_capacity(*chunk_len as usize);
+                    Decompress::new(false)
+                        .decompress_vec(&compressed, &mut chunk, FlushDecompress::Sync)
+                        .map_err(|e| e.to_string())?;
+                    data.extend(chunk);
+                }
+                let start = (offset - first as u64 * chunk_len) as usize;
+                data.get(start..start + size as usize)
+                    .map(|data| data.to_vec())
+                    .ok_or_else(|| "definition is out of range".to_string())
+            }
+        }
+    }
+}
+
+fn find_with_extensions(base: &Path, extensions: &[&str]) -> Option<PathBuf> {
+    extensions
+        .iter()
+        .map(|extension| base.with_extension(extension))
+        .find(|path| path.is_file())
+}
+
+fn read_maybe_gzipped(path: &Path) -> Result<Vec<u8>, String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let mut data = Vec::new();
+    if path
+        .extension()
+        .map_or(false, |extension| extension == "gz")
+    {
+        GzDecoder::new(file).read_to_end(&mut data)
+    } else {
+        BufReader::new(file).read_to_end(&mut data)
+    }
+    .map_err(|e| e.to_string())?;
+    Ok(data)
+}
+
+fn add_record(index: &mut HashMap<String, Vec<Record>>, headword: &str, record: Record) {
+    index
+        .entry(headword.to_lowercase())
+        .or_default()
+        .push(record);
+}
+
+// A StarDict .idx file is a list of NUL-terminated headwords, each followed by
+// the big-endian offset and size of its definition in the .dict file.
+fn parse_stardict_index(
+    idx: &[u8],
+    offset_bytes: usize,
+) -> Result<HashMap<String, Vec<Record>>, String> {
+    let mut index = HashMap::new();
+    let mut pos = 0;
+    while pos < idx.len() {
+        let end = idx[pos..]
+            .iter()
+            .position(|b| *b == 0)
+            .map(|i| pos + i)
+            .ok_or("truncated index")?;
+        let headword = String::from_utf8_lossy(&idx[pos..end]).to_string();
+        let fields = idx
+            .get(end + 1..end + 1 + offset_bytes + 4)
+            .ok_or("truncated index")?;
+        let offset = fields[..offset_bytes]
+            .iter()
+            .fold(0u64, |acc, b| acc << 8 | *b as u64);
+        let size = fields[offset_bytes..]
+            .iter()
+            .fold(0u64, |acc, b| acc << 8 | *b as u64);
+        add_record(
+            &mut index,
+            &headword,
+            Record::Data {
+                headword: headword.clone(),
+                offset,
+                size,
+            },
+        );
+        pos = end + 1 + offset_bytes + 4;
+    }
+    Ok(index)
+}
+
+fn load_stardict(ifo_path: &Path) -> Result<Dictionary, String> {
+    let ifo = std::fs::read_to_string(ifo_path).map_err(|e| e.to_string())?;
+    let info: HashMap<&str, &str> = ifo
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim(), value.trim()))
+        .collect();
+    let name = info
+        .get("bookname")
+        .map(|name| name.to_string())
+        .unwrap_or_else(|| ifo_path.display().to_string());
+    let offset_bytes = if info.get("idxoffsetbits") == Some(&"64") {
+        8
+    } else {
+        4
+    };
+    let idx_path = find_with_extensions(ifo_path, &["idx", "idx.gz"])
+        .ok_or_else(|| format!("{}: index file not found", name))?;
+    let dict_path = find_with_extensions(ifo_path, &["dict", "dict.dz"])
+        .ok_or_else(|| format!("{}: dict file not found", name))?;
+    let idx = read_maybe_gzipped(&idx_path)?;
+    let index = parse_stardict_index(&idx, offset_bytes).map_err(|e| format!("{}: {}", name, e))?;
+    Ok(Dictionary {
+        name,
+        format: Format::StarDict {
+            type_sequence: info.get("sametypesequence").map(|s| s.to_string()),
+        },
+        data: Some(DictData::open(dict_path)?),
+        index,
+    })
+}
+
+// Offsets and lengths in dictd .index files are numbers in base 64 with the
+// digits of the base64 alphabet, most significant first.
+fn decode_dictd_number(digits: &str) -> Option<u64> {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    digits.bytes().try_fold(0u64, |acc, digit| {
+        let value = ALPHABET.iter().position(|b| *b == digit)? as u64;
+        Some(acc * 64 + value)
+    })
+}
+
+// A line of a dictd .index file: headword, offset and size, separated by tabs.
+fn parse_dictd_index_line(line: &str) -> Option<(&str, u64, u64)> {
+    let mut fields = line.split('\t');
+    let headword = fields.next()?;
+    let offset = decode_dictd_number(fields.next()?)?;
+    let size = decode_dictd_number(fields.next()?)?;
+    Some((headword, offset, size))
+}
+
+fn load_dictd(index_path: &Path) -> Result<Dictionary, String> {
+    let dict_path = find_with_extensions(index_path, &["dict", "dict.dz"])
+        .ok_or_else(|| format!("{}: dict file not found", index_path.display()))?;
+    let file = File::open(index_path).map_err(|e| e.to_string())?;
+    let mut index = HashMap::new();
+    let mut name = index_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let data = DictData::open(dict_path)?;
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|e| e.to_string())?;
+        let (headword, offset, size) = match parse_dictd_index_line(&line) {
+            Some(fields) => fields,
+            None => continue,
+        };
+        if headword == "00-database-short" || headword == "00databaseshort" {
+            if let Ok(short) = data.read(offset, size) {
+                let short = String::from_utf8_lossy(&short);
+                if let Some(line) = short.lines().nth(1) {
+                    name = line.trim().to_string();
+                }
+            }
+        }
+        if headword.starts_with("00-database-") || headword.starts_with("00database") {
+            continue;
+        }
+        add_record(
+            &mut index,
+            headword,
+            Record::Data {
+                headword: headword.to_string(),
+                offset,
+                size,
+            },
+        );
+    }
+    Ok(Dictionary {
+        name,
+        format: Format::Dict,
+        data: Some(data),
+        index,
+    })
+}
+
+fn parse_csv_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+// CSV dictionaries have the columns word, phonetic and definition, with the
+// senses of a definition separated by semicolons. A header row is skipped.
+fn load_csv(path: &Path) -> Result<Dictionary, String> {
+    let content = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let name = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    Ok(Dictionary {
+        index: parse_csv(&name, &content),
+        name,
+        format: Format::Csv,
+        data: None,
+    })
+}
+
+fn parse_csv(name: &str, content: &str) -> HashMap<String, Vec<Record>> {
+    let mut index = HashMap::new();
+    for (i, line) in content.lines().enumerate() {
+        let fields = parse_csv_line(line);
+        let headword = fields[0].trim();
+        if headword.is_empty() || (i == 0 && headword.eq_ignore_ascii_case("word")) {
+            continue;
+        }
+        let phonetic = fields
+            .get(1)
+            .map(|phonetic| phonetic.trim().to_string())
+            .filter(|phonetic| !phonetic.is_empty());
+        let senses = fields
+            .get(2)
+            .map(|definition| {
+                definition
+                    .split(';')
+                    .map(|sense| sense.trim().to_string())
+                    .filter(|sense| !sense.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+        add_record(
+            &mut index,
+            headword,
+            Record::Inline(DictionaryEntry {
+                dictionary: name.to_string(),
+                headword: headword.to_string(),
+                phonetic,
+                senses,
+            }),
+        );
+    }
+    index
+}
+
+fn strip_tags(text: &str) -> String {
+    let text = text
+        .replace("<br>", "\n")
+        .replace("<br/>", "\n")
+        .replace("<br />", "\n");
+    let mut plain = String::with_capacity(text.len());
+    let mut in_tag = false;
+    for c in text.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            c if !in_tag => plain.push(c),
+            _ => {}
+        }
+    }
+    plain
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&")
+}
+
+fn split_senses(text: &str) -> Vec<String> {
+    text.lines()
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+// Splits StarDict entry data into its fields. Lowercase types are text that
+// ends with a NUL, uppercase types are binary with a 32-bit size in front, and
+// the last field of a same type sequence takes the rest of the data.
+fn parse_stardict_fields(data: &[u8], type_sequence: Option<&str>) -> Vec<(char, String)> {
+    let mut fields = Vec::new();
+    let mut pos = 0;
+    let types: Vec<char> = type_sequence
+        .map(|s| s.chars().collect())
+        .unwrap_or_default();
+    let mut i = 0;
+    while pos < data.len() {
+        let field_type = if types.is_empty() {
+            let field_type = data[pos] as char;
+            pos += 1;
+            field_type
+        } else if i < types.len() {
+            types[i]
+        } else {
+            break;
+        };
+        let is_last = !types.is_empty() && i == types.len() - 1;
+        i += 1;
+        let end = if is_last {
+            data.len()
+        } else if field_type.is_ascii_lowercase() {
+            data[pos..]
+                .iter()
+                .position(|b| *b == 0)
+                .map_or(data.len(), |end| pos + end)
+        } else {
+            let size = data.get(pos..pos + 4).map_or(0, |size| {
+                u32::from_be_bytes([size[0], size[1], size[2], size[3]])
+            }) as usize;
+            pos += 4;
+            (pos + size).min(data.len())
+        };
+        if field_type.is_ascii_lowercase() {
+            fields.push((
+                field_type,
+                String::from_utf8_lossy(&data[pos.min(end)..end]).to_string(),
+            ));
+        }
+        pos = end + usize::from(!is_last && field_type.is_ascii_lowercase());
+    }
+    fields
+}
+
+// The first /.../ or [...] on the headword line of a dictd entry.
+fn extract_phonetic(line: &str) -> Option<String> {
+    for (open, close) in [('/', '/'), ('[', ']')] {
+        if let Some(start) = line.find(open) {
+            if let Some(len) = line[start + 1..].find(close) {
+                let phonetic = line[start + 1..start + 1 + len].trim();
+                if !phonetic.is_empty() {
+                    return Some(phonetic.to_string());
+                }
+            }
+        }
+    }
+    None
+}
+
+impl Dictionary {
+    fn entries(&self, key: &str) -> Vec<DictionaryEntry> {
+        let records = match self.index.get(key) {
+            Some(records) => records,
+            None => return Vec::new(),
+        };
+        records
+            .iter()
+            .filter_map(|record| match record {
+                Record::Inline(entry) => Some(entry.clone()),
+                Record::Data {
+                    headword,
+                    offset,
+                    size,
+                } => {
+                    let data = match self.data.as_ref()?.read(*offset, *size) {
+                        Ok(data) => data,
+                        Err(e) => {
+                            eprintln!("{}: failed to read \"{}\": {}", self.name, headword, e);
+                            return None;
+                        }
+                    };
+                    Some(self.parse_entry(headword, &data))
+                }
+            })
+            .collect()
+    }
+
+    fn parse_entry(&self, headword: &str, data: &[u8]) -> DictionaryEntry {
+        let mut phonetic = None;
+        let mut senses = Vec::new();
+        match &self.format {
+            Format::StarDict { type_sequence } => {
+                for (field_type, text) in parse_stardict_fields(data, type_sequence.as_deref()) {
+                    match field_type {
+                        't' => phonetic = Some(text.trim().to_string()),
+                        'g' | 'h' | 'x' => senses.extend(split_senses(&strip_tags(&text))),
+                        'r' => {}
+                        _ => senses.extend(split_senses(&text)),
+                    }
+                }
+            }
+            Format::Dict | Format::Csv => {
+                let text = String::from_utf8_lossy(data);
+                let mut lines = split_senses(&text);
+                if lines.first().map_or(false, |line| {
+                    line.to_lowercase().starts_with(&headword.to_lowercase())
+                }) {
+                    phonetic = extract_phonetic(&lines[0]);
+                    lines.remove(0);
+                }
+                senses = lines;
+            }
+        }
+        DictionaryEntry {
+            dictionary: self.name.clone(),
+            headword: headword.to_string(),
+            phonetic,
+            senses,
+        }
+    }
+}
+
+pub fn get_dictionary_dir() -> Result<PathBuf, String> {
+    if let Some(dictionary_dir) = crate::config::get_config()
+        .ok()
+        .and_then(|config| config.dictionary_dir)
+        .filter(|dir| !dir.trim().is_empty())
+    {
+        return Ok(PathBuf::from(dictionary_dir));
+    }
+    let app_handle = crate::APP_HANDLE
+        .get()
+        .ok_or_else(|| "App handle not initialized".to_string())?;
+    let dictionary_dir = app_handle
+        .path()
+        .resolve(
+            "xyz.yetone.apps.openai-translator/dictionaries",
+            BaseDirectory::Config,
+        )
+        .map_err(|e| format!("failed to resolve dictionary dir: {}", e))?;
+    if !dictionary_dir.exists() {
+        std::fs::create_dir_all(&dictionary_dir)
+            .map_err(|e| format!("failed to create dictionary dir: {}", e))?;
+    }
+    Ok(dictionary_dir)
+}
+
+fn load_dictionary(path: &Path) -> Option<Result<Dictionary, String>> {
+    let file_name = path.file_name()?.to_string_lossy().to_lowercase();
+    if file_name.ends_with(".ifo") {
+        Some(load_stardict(path))
+    } else if file_name.ends_with(".index") {
+        Some(load_dictd(path))
+    } else if file_name.ends_with(".csv") {
+        Some(load_csv(path))
+    } else {
+        None
+    }
+}
+
+// The files in a dir and in its direct subdirs, which is how StarDict
+// dictionaries are usually unpacked.
+fn list_files(dir: &Path) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    for entry in std::fs::read_dir(dir).into_iter().flatten().flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            paths.extend(
+                std::fs::read_dir(&path)
+                    .into_iter()
+                    .flatten()
+                    .flatten()
+                    .map(|entry| entry.path()),
+            );
+        } else {
+            paths.push(path);
+        }
+    }
+    paths
+}
+
+// Loads the dictionaries in the language pair folders of the dictionary dir.
+fn load_dictionaries(dir: &Path) -> Vec<PairedDictionary> {
+    let mut paths = Vec::new();
+    for entry in std::fs::read_dir(dir).into_iter().flatten().flatten() {
+        let path = entry.path();
+        let pair = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .filter(|_| path.is_dir())
+            .and_then(LanguagePair::parse);
+        match pair {
+            Some(pair) => paths.extend(list_files(&path).into_iter().map(|path| (pair.clone(), path))),
+            None => eprintln!(
+                "{} is skipped: dictionaries go in folders named by their language pair, e.g. en-zh",
+                path.display()
+            ),
+        }
+    }
+    paths.sort_by(|(_, a), (_, b)| a.cmp(b));
+    paths
+        .into_iter()
+        .filter_map(|(pair, path)| Some((pair, load_dictionary(&path)?)))
+        .filter_map(|(pair, result)| match result {
+            Ok(dictionary) => {
+                debug_println!(
+                    "loaded {}-{} dictionary {} with {} words",
+                    pair.source,
+                    pair.target,
+                    dictionary.name,
+                    dictionary.index.len()
+                );
+                Some((pair, dictionary))
+            }
+            Err(e) => {
+                eprintln!("failed to load dictionary: {}", e);
+                None
+            }
+        })
+        .collect()
+}
+
+// Whether the text is a single word worth looking up instead of translating.
+pub fn is_single_word(text: &str) -> bool {
+    let text = text.trim();
+    let count = text.chars().count();
+    count > 0
+        && count <= MAX_WORD_CHARS
+        && text
+            .chars()
+            .all(|c| c.is_alphabetic() || c == '\'' || c == '-' || c == '’')
+}
+
+const IRREGULAR_FORMS: &[(&str, &str)] = &[
+    ("am", "be"),
+    ("are", "be"),
+    ("is", "be"),
+    ("was", "be"),
+    ("were", "be"),
+    ("been", "be"),
+    ("has", "have"),
+    ("had", "have"),
+    ("did", "do"),
+    ("done", "do"),
+    ("went", "go"),
+    ("gone", "go"),
+    ("ate", "eat"),
+    ("eaten", "eat"),
+    ("saw", "see"),
+    ("seen", "see"),
+    ("took", "take"),
+    ("taken", "take"),
+    ("gave", "give"),
+    ("given", "give"),
+    ("made", "make"),
+    ("came", "come"),
+    ("knew", "know"),
+    ("known", "know"),
+    ("thought", "think"),
+    ("brought", "bring"),
+    ("bought", "buy"),
+    ("taught", "teach"),
+    ("caught", "catch"),
+    ("found", "find"),
+    ("told", "tell"),
+    ("said", "say"),
+    ("wrote", "write"),
+    ("written", "write"),
+    ("better", "good"),
+    ("best", "good"),
+    ("worse", "bad"),
+    ("worst", "bad"),
+    ("children", "child"),
+    ("men", "man"),
+    ("women", "woman"),
+    ("people", "person"),
+    ("feet", "foot"),
+    ("teeth", "tooth"),
+    ("mice", "mouse"),
+    ("geese", "goose"),
+];
+
+// Dictionary forms an English word may be an inflection of, most likely
+// first.
+pub fn lemma_candidates(word: &str) -> Vec<String> {
+    let word = word.to_lowercase();
+    let mut candidates = Vec::new();
+    if let Some((_, lemma)) = IRREGULAR_FORMS.iter().find(|(form, _)| *form == word) {
+        candidates.push(lemma.to_string());
+    }
+    const SUFFIXES: &[(&str, &str)] = &[
+        ("ies", "y"),
+        ("ied", "y"),
+        ("ier", "y"),
+        ("iest", "y"),
+        ("ves", "f"),
+        ("ves", "fe"),
+        ("ing", ""),
+        ("ing", "e"),
+        ("ed", ""),
+        ("ed", "e"),
+        ("es", ""),
+        ("s", ""),
+        ("est", ""),
+        ("er", ""),
+        ("ly", ""),
+    ];
+    for (suffix, replacement) in SUFFIXES {
+        let stem = match word.strip_suffix(suffix) {
+            Some(stem) if stem.chars().count() >= 2 => stem,
+            _ => continue,
+        };
+        candidates.push(format!("{}{}", stem, replacement));
+        // "stopped" -> "stop", "running" -> "run"
+        let mut chars = stem.chars().rev();
+        if replacement.is_empty() && ["ing", "ed", "er", "est"].contains(suffix) {
+            if let (Some(a), Some(b)) = (chars.next(), chars.next()) {
+                if a == b && !"aeiouls".contains(a) {
+                    candidates.push(stem[..stem.len() - a.len_utf8()].to_string());
+                }
+            }
+        }
+    }
+    candidates.dedup();
+    candidates
+}
+
+fn lookup_in(dictionaries: &[&Dictionary], key: &str) -> Vec<DictionaryEntry> {
+    dictionaries
+        .iter()
+        .flat_map(|dictionary| dictionary.entries(key))
+        .collect()
+}
+
+// Looks the word up in the dictionaries for the language pair, then its
+// dictionary forms when the word itself is missing and it's English.
+fn lookup(
+    dictionaries: &[PairedDictionary],
+    word: &str,
+    source_lang: &str,
+    target_lang: &str,
+) -> Option<WordLookup> {
+    let dictionaries: Vec<&Dictionary> = dictionaries
+        .iter()
+        .filter(|(pair, _)| pair.matches(source_lang, target_lang))
+        .map(|(_, dictionary)| dictionary)
+        .collect();
+    if dictionaries.is_empty() {
+        return None;
+    }
+    let entries = lookup_in(&dictionaries, &word.to_lowercase());
+    if !entries.is_empty() {
+        return Some(WordLookup {
+            word: word.to_string(),
+            lemma: None,
+            entries,
+        });
+    }
+    if !is_same_language(source_lang, "en") {
+        return None;
+    }
+    lemma_candidates(word).into_iter().find_map(|lemma| {
+        let entries = lookup_in(&dictionaries, &lemma);
+        if entries.is_empty() {
+            return None;
+        }
+        Some(WordLookup {
+            word: word.to_string(),
+            lemma: Some(lemma),
+            entries,
+        })
+    })
+}
+
+fn lookup_in_dictionary_dir(
+    word: &str,
+    source_lang: &str,
+    target_lang: &str,
+) -> Option<WordLookup> {
+    let dir = match get_dictionary_dir() {
+        Ok(dir) => dir,
+        Err(e) => {
+            eprintln!("{}", e);
+            return None;
+        }
+    };
+    let mut loaded = DICTIONARIES.lock();
+    if loaded
+        .as_ref()
+        .map_or(true, |(loaded_dir, _)| *loaded_dir != dir)
+    {
+        *loaded = Some((dir.clone(), load_dictionaries(&dir)));
+    }
+    let (_, dictionaries) = loaded.as_ref()?;
+    lookup(dictionaries, word, source_lang, target_lang)
+}
+
+// Looks a single word up in the local dictionaries declared for the language
+// pair. Returns None for phrases and unknown words, which are left to the
+// translation engine. Loading and reading the dictionaries blocks, so it's
+// done off the async runtime.
+#[tauri::command]
+pub async fn lookup_word(
+    text: String,
+    source_lang: Option<String>,
+    target_lang: Option<String>,
+) -> Result<Option<WordLookup>, String> {
+    let (source_lang, target_lang) = match (source_lang, target_lang) {
+        (Some(source_lang), Some(target_lang)) => (source_lang, target_lang),
+        _ => return Ok(None),
+    };
+    if !is_single_word(&text) {
+        return Ok(None);
+    }
+    tauri::async_runtime::spawn_blocking(move || {
+        lookup_in_dictionary_dir(text.trim(), &source_lang, &target_lang)
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
+
+// Loads the dictionary dir again, so that added or removed files are picked
+// up, and returns how many dictionaries it has.
+#[tauri::command]
+pub async fn reload_dictionaries() -> Result<usize, String> {
+    tauri::async_runtime::spawn_blocking(|| {
+        let dir = get_dictionary_dir()?;
+        let dictionaries = load_dictionaries(&dir);
+        let count = dictionaries.len();
+        *DICTIONARIES.lock() = Some((dir, dictionaries));
+        Ok(count)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{Compress, Compression, FlushCompress};
+
+    fn index_entry(headword: &str, offset: u64, size: u32, offset_bytes: usize) -> Vec<u8> {
+        let mut entry = headword.as_bytes().to_vec();
+        entry.push(0);
+        entry.extend(&offset.to_be_bytes()[8 - offset_bytes..]);
+        entry.extend(&size.to_be_bytes());
+        entry
+    }
+
+    fn data_record(records: &[Record]) -> (&str, u64, u64) {
+        match &records[0] {
+            Record::Data {
+                headword,
+                offset,
+                size,
+            } => (headword, *offset, *size),
+            Record::Inline(_) => panic!("expected a data record"),
+        }
+    }
+
+    #[test]
+    fn stardict_index() {
+        for offset_bytes in [4, 8] {
+            let mut idx = index_entry("Apple", 0, 12, offset_bytes);
+            idx.extend(index_entry("apple", 12, 30, offset_bytes));
+            idx.extend(index_entry("苹果", 0x0102_0304, 5, offset_bytes));
+            let index = parse_stardict_index(&idx, offset_bytes).unwrap();
+            assert_eq!(index.len(), 2);
+            assert_eq!(index["apple"].len(), 2);
+            assert_eq!(data_record(&index["apple"]), ("Apple", 0, 12));
+            assert_eq!(data_record(&index["apple"][1..]), ("apple", 12, 30));
+            assert_eq!(data_record(&index["苹果"]), ("苹果", 0x0102_0304, 5));
+
+            // Cut in the middle of the offset and size
+            assert!(parse_stardict_index(&idx[..idx.len() - 2], offset_bytes).is_err());
+        }
+        assert!(parse_stardict_index(b"apple", 4).is_err());
+    }
+
+    #[test]
+    fn stardict_fields() {
+        let data = "/ˈæp.əl/\0a fruit\nthe tree".as_bytes();
+        assert_eq!(
+            parse_stardict_fields(data, Some("tm")),
+            vec![
+                ('t', "/ˈæp.əl/".to_string()),
+                ('m', "a fruit\nthe tree".to_string())
+            ]
+        );
+
+        // Without a same type sequence every field starts with its type
+        let mut data = b"mfirst\0W".to_vec();
+        data.extend(&3u32.to_be_bytes());
+        data.extend(b"xyz");
+        data.extend(b"g<b>second</b>\0");
+        assert_eq!(
+            parse_stardict_fields(&data, None),
+            vec![
+                ('m', "first".to_string()),
+                ('g', "<b>second</b>".to_string())
+            ]
+        );
+    }
+
+    // A dictzip file with the given chunks, each compressed on its own
+    fn dictzip(chunk_len: u16, text: &[u8], with_name: bool) -> Vec<u8> {
+        let chunks: Vec<Vec<u8>> = text
+            .chunks(chunk_len as usize)
+            .map(|chunk| {
+                let mut compressed = Vec::with_capacity(chunk.len() + 64);
+                Compress::new(Compression::default(), false)
+                    .compress_vec(chunk, &mut compressed, FlushCompress::Full)
+                    .unwrap();
+                compressed
+            })
+            .collect();
+        let mut extra = b"RA".to_vec();
+        extra.extend(&(6 + 2 * chunks.len() as u16).to_le_bytes());
+        extra.extend(&1u16.to_le_bytes());
+        extra.extend(&chunk_len.to_le_bytes());
+        extra.extend(&(chunks.len() as u16).to_le_bytes());
+        for chunk in &chunks {
+            extra.extend(&(chunk.len() as u16).to_le_bytes());
+        }
+        let flags = if with_name { 0x04 | 0x08 } else { 0x04 };
+        let mut file = vec![0x1f, 0x8b, 8, flags, 0, 0, 0, 0, 0, 3];
+        file.extend(&(extra.len() as u16).to_le_bytes());
+        file.extend(extra);
+        if with_name {
+            file.extend(b"words.dict\0");
+        }
+        for chunk in chunks {
+            file.extend(chunk);
+        }
+        file
+    }
+
+    #[test]
+    fn dictzip_chunks() {
+        let text: Vec<u8> = (0..100u8).map(|i| b'a' + i % 26).collect();
+        for with_name in [false, true] {
+            let file = dictzip(16, &text, with_name);
+            let (chunk_len, chunks) = parse_dictzip_header(&file).unwrap().unwrap();
+            assert_eq!(chunk_len, 16);
+            assert_eq!(chunks.len(), 7);
+            // The gzip header, the RA field with seven chunk sizes and the name
+            let header_len = if with_name { 47 } else { 36 };
+            assert_eq!(chunks[0].0, header_len);
+            assert_eq!(chunks[1].0, header_len + chunks[0].1 as u64);
+
+            let path = std::env::temp_dir().join(format!(
+                "dictionary-test-{}-{}.dict.dz",
+                std::process::id(),
+                with_name
+            ));
+            std::fs::write(&path, &file).unwrap();
+            let data = DictData::open(path.clone()).unwrap();
+            assert!(matches!(data, DictData::Dictzip { .. }));
+            // Within a chunk, across chunks and up to the end
+            for (offset, size) in [(0, 5), (14, 20), (30, 40), (96, 4), (50, 0)] {
+                assert_eq!(
+                    data.read(offset, size).unwrap(),
+                    &text[offset as usize..(offset + size) as usize],
+                    "{} {}",
+                    offset,
+                    size
+                );
+            }
+            assert!(data.read(96, 10).is_err());
+            std::fs::remove_file(path).unwrap();
+        }
+        assert!(parse_dictzip_header(b"plain text").is_err());
+        let gzip_without_ra = [0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 3];
+        assert_eq!(parse_dictzip_header(&gzip_without_ra), Ok(None));
+    }
+
+    #[test]
+    fn dictd_base64_offsets() {
+        let cases = [
+            ("A", Some(0)),
+            ("B", Some(1)),
+            ("a", Some(26)),
+            ("/", Some(63)),
+            ("BA", Some(64)),
+            ("//", Some(4095)),
+            ("BAA", Some(4096)),
+            ("", Some(0)),
+            ("A=", None),
+            ("1!", None),
+        ];
+        for (digits, expected) in cases {
+            assert_eq!(decode_dictd_number(digits), expected, "{}", digits);
+        }
+        assert_eq!(
+            parse_dictd_index_line("apple\tBA\tc"),
+            Some(("apple", 64, 28))
+        );
+        assert_eq!(
+            parse_dictd_index_line("ice cream\tZb\tBL"),
+            Some(("ice cream", 25 * 64 + 27, 64 + 11))
+        );
+        assert_eq!(parse_dictd_index_line("apple\tBA"), None);
+        assert_eq!(parse_dictd_index_line("apple\tB-A\tc"), None);
+    }
+
+    #[test]
+    fn csv_quoting() {
+        let cases = [
+            (
+                "apple,/ˈæp.əl/,a fruit",
+                vec!["apple", "/ˈæp.əl/", "a fruit"],
+            ),
+            (
+                r#"apple,,"a fruit, red or green; ""Malus"""#,
+                vec!["apple", "", r#"a fruit, red or green; "Malus""#],
+            ),
+            (r#""a,b",c"#, vec!["a,b", "c"]),
+            ("word,", vec!["word", ""]),
+            ("", vec![""]),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(parse_csv_line(line), expected, "{}", line);
+        }
+
+        let index = parse_csv(
+            "words",
+            "Word,Phonetic,Definition\nApple,/ˈæp.əl/,\"a fruit; the tree, too\"\n,,\nrun,,to move fast\n",
+        );
+        assert_eq!(index.len(), 2);
+        match &index["apple"][0] {
+            Record::Inline(entry) => {
+                assert_eq!(entry.dictionary, "words");
+                assert_eq!(entry.headword, "Apple");
+                assert_eq!(entry.phonetic.as_deref(), Some("/ˈæp.əl/"));
+                assert_eq!(entry.senses, vec!["a fruit", "the tree, too"]);
+            }
+            Record::Data { .. } => panic!("expected an inline record"),
+        }
+    }
+
+    #[test]
+    fn lemmas() {
+        let cases = [
+            ("running", "run"),
+            ("stopped", "stop"),
+            ("cities", "city"),
+            ("knives", "knife"),
+            ("leaves", "leaf"),
+            ("cats", "cat"),
+            ("boxes", "box"),
+            ("baked", "bake"),
+            ("quickly", "quick"),
+            ("went", "go"),
+            ("Children", "child"),
+        ];
+        for (word, lemma) in cases {
+            assert!(
+                lemma_candidates(word).contains(&lemma.to_string()),
+                "{} -> {:?}",
+                word,
+                lemma_candidates(word)
+            );
+        }
+        // Irregular forms come first, and stems need two characters
+        assert_eq!(lemma_candidates("was")[0], "be");
+        assert!(lemma_candidates("is").iter().all(|lemma| lemma == "be"));
+        assert!(lemma_candidates("apple").is_empty());
+    }
+
+    #[test]
+    fn single_words() {
+        for text in ["apple", " don't ", "well-known", "naïve", "苹果", "it’s"] {
+            assert!(is_single_word(text), "{}", text);
+        }
+        let too_long = "a".repeat(MAX_WORD_CHARS + 1);
+        for text in [
+            "",
+            "  ",
+            "apple pie",
+            "C3PO",
+            "42",
+            "e.g.",
+            too_long.as_str(),
+        ] {
+            assert!(!is_single_word(text), "{}", text);
+        }
+    }
+
+    fn csv_dictionary(source: &str, target: &str, content: &str) -> (LanguagePair, Dictionary) {
+        let pair = LanguagePair {
+            source: source.to_string(),
+            target: target.to_string(),
+        };
+        let dictionary = Dictionary {
+            name: format!("{}-{}", source, target),
+            format: Format::Csv,
+            data: None,
+            index: parse_csv("words", content),
+        };
+        (pair, dictionary)
+    }
+
+    #[test]
+    fn language_pairs() {
+        assert_eq!(
+            LanguagePair::parse("en-zh"),
+            Some(LanguagePair {
+                source: "en".to_string(),
+                target: "zh".to_string()
+            })
+        );
+        assert_eq!(
+            LanguagePair::parse("zh-Hant-en"),
+            Some(LanguagePair {
+                source: "zh-Hant".to_string(),
+                target: "en".to_string()
+            })
+        );
+        for name in ["en", "english", "stardict-langdao-ec", "en-"] {
+            assert_eq!(LanguagePair::parse(name), None, "{}", name);
+        }
+
+        let dictionaries = vec![
+            csv_dictionary("en", "zh", "run,,跑\n"),
+            csv_dictionary("en", "ja", "run,,走る\n"),
+            csv_dictionary("zh", "en", "跑,,to run\n"),
+        ];
+        let found = lookup(&dictionaries, "running", "en", "zh-Hans").unwrap();
+        assert_eq!(found.lemma.as_deref(), Some("run"));
+        assert_eq!(found.entries.len(), 1);
+        assert_eq!(found.entries[0].senses, vec!["跑"]);
+        assert_eq!(
+            lookup(&dictionaries, "run", "en", "ja").unwrap().entries[0].senses,
+            vec!["走る"]
+        );
+        assert_eq!(
+            lookup(&dictionaries, "跑", "zh", "en").unwrap().entries[0].senses,
+            vec!["to run"]
+        );
+        // No dictionary for the pair, or the word in the wrong direction
+        assert!(lookup(&dictionaries, "run", "en", "fr").is_none());
+        assert!(lookup(&dictionaries, "run", "zh", "en").is_none());
+        assert!(lookup(&dictionaries, "跑", "en", "zh").is_none());
+    }
+}
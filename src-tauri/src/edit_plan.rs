@@ -0,0 +1,383 @@
+use similar::{capture_diff_slices, Algorithm, DiffOp};
+use unicode_segmentation::UnicodeSegmentation;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CursorMove {
+    None,
+    Left(usize),
+    Right(usize),
+}
+
+// One piece of text the user typed into previously written content. To handle
+// it the cursor is moved to the end of the insertion, `delete_count` graphemes
+// are deleted with backspace and the replacement is typed in their place.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedEdit {
+    pub cursor_move: CursorMove,
+    pub delete_count: usize,
+    pub insertion: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Equal(usize),
+    Insert(Vec<String>),
+}
+
+fn graphemes(text: &str) -> Vec<&str> {
+    text.graphemes(true).collect()
+}
+
+// Diffs on grapheme clusters instead of chars, so that an emoji or a letter
+// with combining marks is never split between an equal and an inserted part.
+fn diff_segments(previous: &str, current: &str) -> Vec<Segment> {
+    let old = graphemes(previous);
+    let new = graphemes(current);
+    let mut segments = Vec::new();
+    for op in capture_diff_slices(Algorithm::Myers, &old, &new) {
+        let segment = match op {
+            DiffOp::Equal { len, .. } => Segment::Equal(len),
+            DiffOp::Insert {
+                new_index, new_len, ..
+            }
+            | DiffOp::Replace {
+                new_index, new_len, ..
+            } => Segment::Insert(
+                new[new_index..new_index + new_len]
+                    .iter()
+                    .map(|g| g.to_string())
+                    .collect(),
+            ),
+            DiffOp::Delete { .. } => continue,
+        };
+        match (segments.last_mut(), segment) {
+            (Some(Segment::Equal(a)), Segment::Equal(b)) => *a += b,
+            (Some(Segment::Insert(a)), Segment::Insert(b)) => a.extend(b),
+            (_, segment) => segments.push(segment),
+        }
+    }
+    segments
+}
+
+fn segment_len(segment: &Segment) -> usize {
+    match segment {
+        Segment::Equal(len) => *len,
+        Segment::Insert(graphemes) => graphemes.len(),
+    }
+}
+
+// The share of the graphemes of `previous` that are still present, unchanged
+// and in order, in `current`.
+pub fn retained_ratio(previous: &str, current: &str) -> f64 {
+    let total = graphemes(previous).len();
+    if total == 0 {
+        return 0.0;
+    }
+    let retained: usize = diff_segments(previous, current)
+        .iter()
+        .map(|segment| match segment {
+            Segment::Equal(len) => *len,
+            Segment::Insert(_) => 0,
+        })
+        .sum();
+    retained as f64 / total as f64
+}
+
+fn is_line_break(grapheme: &str) -> bool {
+    grapheme == "\n" || grapheme == "\r\n"
+}
+
+// Plans the edits that turn `current` into a text where every insertion made
+// since `previous` is replaced, in document order. The cursor is assumed to be
+// at the end of `current` before the first edit, and at the end of the typed
+// replacement before each following edit. Line breaks surrounding an insertion
+// and whitespace-only insertions are left untouched.
+pub fn plan_incremental_edits(previous: &str, current: &str) -> Vec<PlannedEdit> {
+    let segments = diff_segments(previous, current);
+    let total: usize = segments.iter().map(segment_len).sum();
+    let mut edits = Vec::new();
+    let mut position = 0;
+    let mut cursor: Option<usize> = None;
+    for segment in &segments {
+        let len = segment_len(segment);
+        let inserted = match segment {
+            Segment::Insert(graphemes) => graphemes,
+            Segment::Equal(_) => {
+                position += len;
+                continue;
+            }
+        };
+        let leading = inserted.iter().take_while(|g| is_line_break(g)).count();
+        let trailing = inserted[leading..]
+            .iter()
+            .rev()
+            .take_while(|g| is_line_break(g))
+            .count();
+        let body = &inserted[leading..len - trailing];
+        if body.concat().trim().is_empty() {
+            position += len;
+            continue;
+        }
+        let end = position + len - trailing;
+        let cursor_move = match cursor {
+            None if end < total => CursorMove::Left(total - end),
+            None => CursorMove::None,
+            Some(cursor) if end > cursor => CursorMove::Right(end - cursor),
+            Some(_) => CursorMove::None,
+        };
+        edits.push(PlannedEdit {
+            cursor_move,
+            delete_count: body.len(),
+            insertion: body.concat(),
+        });
+        cursor = Some(end);
+        position += len;
+    }
+    edits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A text field: graphemes and a cursor, starting at the end of the text
+    struct Buffer {
+        graphemes: Vec<String>,
+        cursor: usize,
+    }
+
+    impl Buffer {
+        fn new(text: &str) -> Self {
+            let graphemes: Vec<String> = graphemes(text).iter().map(|g| g.to_string()).collect();
+            let cursor = graphemes.len();
+            Self { graphemes, cursor }
+        }
+
+        // Carries out the edits, typing `replace(insertion)` for each one, and
+        // checks that the deleted graphemes are the insertion
+        fn apply(&mut self, edits: &[PlannedEdit], replace: impl Fn(&str) -> String) -> String {
+            for edit in edits {
+                match edit.cursor_move {
+                    CursorMove::None => {}
+                    CursorMove::Left(n) => self.cursor = self.cursor.checked_sub(n).unwrap(),
+                    CursorMove::Right(n) => self.cursor += n,
+                }
+                assert!(self.cursor <= self.graphemes.len());
+                let start = self.cursor.checked_sub(edit.delete_count).unwrap();
+                let deleted: String = self.graphemes.drain(start..self.cursor).collect();
+                assert_eq!(deleted, edit.insertion);
+                let typed: Vec<String> = graphemes(&replace(&edit.insertion))
+                    .iter()
+                    .map(|g| g.to_string())
+                    .collect();
+                self.cursor = start + typed.len();
+                self.graphemes.splice(start..start, typed);
+            }
+            self.graphemes.concat()
+        }
+    }
+
+    fn bracket(insertion: &str) -> String {
+        format!("[{}]", insertion)
+    }
+
+    // The text outside the brackets and the bracketed parts
+    fn split_brackets(text: &str) -> (String, Vec<String>) {
+        let mut outside = String::new();
+        let mut inside = Vec::new();
+        let mut current: Option<String> = None;
+        for c in text.chars() {
+            match (c, current.as_mut()) {
+                ('[', None) => current = Some(String::new()),
+                (']', Some(_)) => inside.extend(current.take()),
+                (c, Some(part)) => part.push(c),
+                (c, None) => outside.push(c),
+            }
+        }
+        (outside, inside)
+    }
+
+    fn is_subsequence(needle: &[&str], haystack: &[&str]) -> bool {
+        let mut haystack = haystack.iter();
+        needle.iter().all(|g| haystack.any(|h| h == g))
+    }
+
+    #[test]
+    fn diff_segments_keeps_graphemes_whole() {
+        // é with a combining accent, a family emoji and a flag
+        let previous = "cafe\u{301} 👨‍👩‍👧 🇯🇵";
+        let current = "cafe\u{301}e\u{301} 👨‍👩‍👧👨‍👩‍👦 🇯🇵🇫🇷";
+        assert_eq!(
+            diff_segments(previous, current),
+            vec![
+                Segment::Equal(4),
+                Segment::Insert(vec!["e\u{301}".to_string()]),
+                Segment::Equal(2),
+                Segment::Insert(vec!["👨‍👩‍👦".to_string()]),
+                Segment::Equal(2),
+                Segment::Insert(vec!["🇫🇷".to_string()]),
+            ]
+        );
+        assert_eq!(diff_segments("", ""), vec![]);
+        assert_eq!(diff_segments("abc", "abc"), vec![Segment::Equal(3)]);
+        assert_eq!(diff_segments("abc", ""), vec![]);
+    }
+
+    #[test]
+    fn diff_segments_merges_replacements_into_insertions() {
+        assert_eq!(
+            diff_segments("我喜欢猫", "我讨厌狗和猫"),
+            vec![
+                Segment::Equal(1),
+                Segment::Insert(
+                    vec!["讨", "厌", "狗", "和"]
+                        .into_iter()
+                        .map(String::from)
+                        .collect()
+                ),
+                Segment::Equal(1),
+            ]
+        );
+    }
+
+    #[test]
+    fn plan_cjk_insertions_in_document_order() {
+        let previous = "你好。今天天气很好。";
+        let current = "你好，世界。今天天气很好。再见";
+        let edits = plan_incremental_edits(previous, current);
+        assert_eq!(
+            edits,
+            vec![
+                PlannedEdit {
+                    cursor_move: CursorMove::Left(10),
+                    delete_count: 3,
+                    insertion: "，世界".to_string(),
+                },
+                PlannedEdit {
+                    cursor_move: CursorMove::Right(10),
+                    delete_count: 2,
+                    insertion: "再见".to_string(),
+                },
+            ]
+        );
+        let result = Buffer::new(current).apply(&edits, bracket);
+        assert_eq!(result, "你好[，世界]。今天天气很好。[再见]");
+    }
+
+    #[test]
+    fn plan_emoji_insertion_counts_graphemes() {
+        let edits = plan_incremental_edits("Hi there", "Hi 👋🏽 there");
+        assert_eq!(
+            edits,
+            vec![PlannedEdit {
+                cursor_move: CursorMove::Left(5),
+                delete_count: 2,
+                insertion: "👋🏽 ".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn plan_skips_line_breaks_and_whitespace() {
+        assert_eq!(plan_incremental_edits("a\nb", "a\n\n  \nb"), vec![]);
+        let edits = plan_incremental_edits("a\r\nb", "a\r\n\r\nnew line\r\n\r\nb");
+        assert_eq!(
+            edits,
+            vec![PlannedEdit {
+                cursor_move: CursorMove::Left(3),
+                delete_count: 8,
+                insertion: "new line".to_string(),
+            }]
+        );
+        assert_eq!(
+            Buffer::new("a\r\n\r\nnew line\r\n\r\nb").apply(&edits, bracket),
+            "a\r\n\r\n[new line]\r\n\r\nb"
+        );
+    }
+
+    // A small seeded generator, so that failures can be reproduced
+    struct Lcg(u64);
+
+    impl Lcg {
+        fn next(&mut self, bound: usize) -> usize {
+            self.0 = self
+                .0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            ((self.0 >> 33) % bound as u64) as usize
+        }
+    }
+
+    const ATOMS: [&str; 14] = [
+        "a",
+        "b",
+        "z",
+        " ",
+        "\n",
+        "\r\n",
+        ".",
+        "e\u{301}",
+        "中",
+        "文",
+        "。",
+        "👍🏾",
+        "👨‍👩‍👧",
+        "🇯🇵",
+    ];
+
+    fn random_atoms(rng: &mut Lcg, max_len: usize) -> Vec<&'static str> {
+        (0..rng.next(max_len + 1))
+            .map(|_| ATOMS[rng.next(ATOMS.len())])
+            .collect()
+    }
+
+    #[test]
+    fn plan_random_edits_replaces_every_insertion() {
+        for seed in 0..2000 {
+            let mut rng = Lcg(seed);
+            let previous_atoms = random_atoms(&mut rng, 20);
+            let mut current_atoms = previous_atoms.clone();
+            for _ in 0..rng.next(4) {
+                // Delete now and then, otherwise insert a run of atoms
+                if rng.next(4) == 0 && !current_atoms.is_empty() {
+                    current_atoms.remove(rng.next(current_atoms.len()));
+                } else {
+                    let at = rng.next(current_atoms.len() + 1);
+                    let inserted = random_atoms(&mut rng, 6);
+                    current_atoms.splice(at..at, inserted);
+                }
+            }
+            let previous = previous_atoms.concat();
+            let current = current_atoms.concat();
+            let edits = plan_incremental_edits(&previous, &current);
+            let context = format!("seed {}: {:?} -> {:?}", seed, previous, current);
+
+            // Typing every insertion again leaves the text as it was
+            assert_eq!(
+                Buffer::new(&current).apply(&edits, |s| s.to_string()),
+                current,
+                "{}",
+                context
+            );
+
+            // Every insertion is replaced, and what's left outside the
+            // replacements is the previous text, less what was deleted, and
+            // whitespace
+            let result = Buffer::new(&current).apply(&edits, bracket);
+            assert_eq!(result.replace(['[', ']'], ""), current, "{}", context);
+            let (outside, inside) = split_brackets(&result);
+            let insertions: Vec<&str> = edits.iter().map(|edit| edit.insertion.as_str()).collect();
+            assert_eq!(inside, insertions, "{}", context);
+            assert!(
+                inside.iter().all(|part| !part.trim().is_empty()),
+                "{}",
+                context
+            );
+            let kept: Vec<&str> = graphemes(&outside)
+                .into_iter()
+                .filter(|g| !g.trim().is_empty())
+                .collect();
+            assert!(is_subsequence(&kept, &graphemes(&previous)), "{}", context);
+        }
+    }
+}
@@ -0,0 +1,374 @@
+use serde::{Deserialize, Serialize};
+
+// Thresholds of the mouse gestures that select text, as set in the config.
+// Missing fields keep their default.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct GestureConfig {
+    // A drag selects text when the button is held longer than this...
+    pub drag_min_ms: Option<u64>,
+    // ...and the pointer moved further than this many pixels
+    pub drag_min_distance: Option<f64>,
+    // Clicks closer together than this, in time and in pixels, count as one
+    // double or triple click
+    pub multi_click_interval_ms: Option<u64>,
+    pub multi_click_max_distance: Option<f64>,
+    // Whether a click with Shift held, which extends the selection, counts
+    pub shift_click: Option<bool>,
+}
+
+impl Default for GestureConfig {
+    fn default() -> Self {
+        let thresholds = GestureThresholds::DEFAULT;
+        Self {
+            drag_min_ms: Some(thresholds.drag_min_ms),
+            drag_min_distance: Some(thresholds.drag_min_distance),
+            multi_click_interval_ms: Some(thresholds.multi_click_interval_ms),
+            multi_click_max_distance: Some(thresholds.multi_click_max_distance),
+            shift_click: Some(thresholds.shift_click),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GestureThresholds {
+    pub drag_min_ms: u64,
+    pub drag_min_distance: f64,
+    pub multi_click_interval_ms: u64,
+    pub multi_click_max_distance: f64,
+    pub shift_click: bool,
+}
+
+impl GestureThresholds {
+    pub const DEFAULT: GestureThresholds = GestureThresholds {
+        drag_min_ms: 300,
+        drag_min_distance: 20.0,
+        multi_click_interval_ms: 700,
+        multi_click_max_distance: 10.0,
+        shift_click: true,
+    };
+
+    pub fn from_config(config: &GestureConfig) -> Self {
+        let default = Self::DEFAULT;
+        Self {
+            drag_min_ms: config.drag_min_ms.unwrap_or(default.drag_min_ms),
+            drag_min_distance: config
+                .drag_min_distance
+                .unwrap_or(default.drag_min_distance),
+            multi_click_interval_ms: config
+                .multi_click_interval_ms
+                .unwrap_or(default.multi_click_interval_ms),
+            multi_click_max_distance: config
+                .multi_click_max_distance
+                .unwrap_or(default.multi_click_max_distance),
+            shift_click: config.shift_click.unwrap_or(default.shift_click),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ButtonAction {
+    Press,
+    Release,
+}
+
+// A left button event with the time in milliseconds, the pointer position and
+// whether Shift was held.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GestureEvent {
+    pub action: ButtonAction,
+    pub time_ms: u64,
+    pub x: i32,
+    pub y: i32,
+    pub shift: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Gesture {
+    Drag,
+    DoubleClick,
+    TripleClick,
+    ShiftClick,
+}
+
+// Recognizes the gestures that select text from the left button events alone,
+// so it can be driven by the mouse hook or by a list of events.
+#[derive(Debug, Clone)]
+pub struct GestureDetector {
+    thresholds: GestureThresholds,
+    press: Option<GestureEvent>,
+    last_click: Option<GestureEvent>,
+    click_count: u32,
+}
+
+fn distance(a: &GestureEvent, b: &GestureEvent) -> f64 {
+    let dx = (a.x - b.x) as f64;
+    let dy = (a.y - b.y) as f64;
+    (dx * dx + dy * dy).sqrt()
+}
+
+impl GestureDetector {
+    pub const fn new(thresholds: GestureThresholds) -> Self {
+        Self {
+            thresholds,
+            press: None,
+            last_click: None,
+            click_count: 0,
+        }
+    }
+
+    pub fn set_thresholds(&mut self, thresholds: GestureThresholds) {
+        self.thresholds = thresholds;
+    }
+
+    // Feeds the next event and returns the gesture it completes, if any.
+    // Gestures complete on release.
+    pub fn feed(&mut self, event: GestureEvent) -> Option<Gesture> {
+        match event.action {
+            ButtonAction::Press => {
+                self.press = Some(event);
+                None
+            }
+            ButtonAction::Release => {
+                let press = self.press.take();
+                self.release(press, event)
+            }
+        }
+    }
+
+    fn release(&mut self, press: Option<GestureEvent>, release: GestureEvent) -> Option<Gesture> {
+        let thresholds = self.thresholds;
+        if let Some(press) = &press {
+            let held_ms = release.time_ms.saturating_sub(press.time_ms);
+            if held_ms > thresholds.drag_min_ms
+                && distance(press, &release) > thresholds.drag_min_distance
+            {
+                self.last_click = None;
+                self.click_count = 0;
+                return Some(Gesture::Drag);
+            }
+        }
+        let continues = self.last_click.map_or(false, |last| {
+            release.time_ms.saturating_sub(last.time_ms) < thresholds.multi_click_interval_ms
+                && distance(&last, &release) < thresholds.multi_click_max_distance
+        });
+        self.click_count = if continues { self.click_count + 1 } else { 1 };
+        self.last_click = Some(release);
+        match self.click_count {
+            2 => Some(Gesture::DoubleClick),
+            3 => {
+                // A fourth click starts over instead of repeating the triple
+                self.last_click = None;
+                self.click_count = 0;
+                Some(Gesture::TripleClick)
+            }
+            _ => {
+                let shift = release.shift || press.map_or(false, |press| press.shift);
+                if thresholds.shift_click && shift {
+                    Some(Gesture::ShiftClick)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A click: press and release at `time_ms`, 50ms apart, at the same point
+    fn click(time_ms: u64, x: i32, shift: bool) -> Vec<GestureEvent> {
+        vec![
+            event(ButtonAction::Press, time_ms, x, 0, shift),
+            event(ButtonAction::Release, time_ms + 50, x, 0, shift),
+        ]
+    }
+
+    fn drag(time_ms: u64, held_ms: u64, dx: i32) -> Vec<GestureEvent> {
+        vec![
+            event(ButtonAction::Press, time_ms, 0, 0, false),
+            event(ButtonAction::Release, time_ms + held_ms, dx, 0, false),
+        ]
+    }
+
+    fn event(action: ButtonAction, time_ms: u64, x: i32, y: i32, shift: bool) -> GestureEvent {
+        GestureEvent {
+            action,
+            time_ms,
+            x,
+            y,
+            shift,
+        }
+    }
+
+    struct Case {
+        name: &'static str,
+        thresholds: GestureThresholds,
+        events: Vec<Vec<GestureEvent>>,
+        // What each release completes
+        expected: Vec<Option<Gesture>>,
+    }
+
+    #[test]
+    fn feed_recognizes_gestures() {
+        let default = GestureThresholds::DEFAULT;
+        let cases = vec![
+            Case {
+                name: "drag",
+                thresholds: default,
+                events: vec![drag(0, 500, 100)],
+                expected: vec![Some(Gesture::Drag)],
+            },
+            Case {
+                name: "short press is a click",
+                thresholds: default,
+                events: vec![drag(0, 200, 100)],
+                expected: vec![None],
+            },
+            Case {
+                name: "long press without moving is a click",
+                thresholds: default,
+                events: vec![drag(0, 500, 5)],
+                expected: vec![None],
+            },
+            Case {
+                name: "double click",
+                thresholds: default,
+                events: vec![click(0, 0, false), click(200, 2, false)],
+                expected: vec![None, Some(Gesture::DoubleClick)],
+            },
+            Case {
+                name: "slow clicks",
+                thresholds: default,
+                events: vec![click(0, 0, false), click(1000, 0, false)],
+                expected: vec![None, None],
+            },
+            Case {
+                name: "distant clicks",
+                thresholds: default,
+                events: vec![click(0, 0, false), click(200, 50, false)],
+                expected: vec![None, None],
+            },
+            Case {
+                name: "triple click",
+                thresholds: default,
+                events: vec![
+                    click(0, 0, false),
+                    click(200, 0, false),
+                    click(400, 0, false),
+                ],
+                expected: vec![None, Some(Gesture::DoubleClick), Some(Gesture::TripleClick)],
+            },
+            Case {
+                name: "fourth click starts over",
+                thresholds: default,
+                events: vec![
+                    click(0, 0, false),
+                    click(200, 0, false),
+                    click(400, 0, false),
+                    click(600, 0, false),
+                    click(800, 0, false),
+                ],
+                expected: vec![
+                    None,
+                    Some(Gesture::DoubleClick),
+                    Some(Gesture::TripleClick),
+                    None,
+                    Some(Gesture::DoubleClick),
+                ],
+            },
+            Case {
+                name: "drag resets the clicks",
+                thresholds: default,
+                events: vec![
+                    click(0, 0, false),
+                    drag(100, 400, 100),
+                    click(600, 100, false),
+                ],
+                expected: vec![None, Some(Gesture::Drag), None],
+            },
+            Case {
+                name: "shift click",
+                thresholds: default,
+                events: vec![click(0, 0, true)],
+                expected: vec![Some(Gesture::ShiftClick)],
+            },
+            Case {
+                name: "shift click turned off",
+                thresholds: GestureThresholds {
+                    shift_click: false,
+                    ..default
+                },
+                events: vec![click(0, 0, true)],
+                expected: vec![None],
+            },
+            Case {
+                name: "shift double click is a double click",
+                thresholds: default,
+                events: vec![click(0, 0, true), click(200, 0, true)],
+                expected: vec![Some(Gesture::ShiftClick), Some(Gesture::DoubleClick)],
+            },
+            Case {
+                name: "custom drag thresholds",
+                thresholds: GestureThresholds {
+                    drag_min_ms: 100,
+                    drag_min_distance: 5.0,
+                    ..default
+                },
+                events: vec![drag(0, 150, 10), drag(1000, 50, 10)],
+                expected: vec![Some(Gesture::Drag), None],
+            },
+            Case {
+                name: "custom multi click thresholds",
+                thresholds: GestureThresholds {
+                    multi_click_interval_ms: 2000,
+                    multi_click_max_distance: 100.0,
+                    ..default
+                },
+                events: vec![click(0, 0, false), click(1500, 60, false)],
+                expected: vec![None, Some(Gesture::DoubleClick)],
+            },
+        ];
+        for case in cases {
+            let mut detector = GestureDetector::new(case.thresholds);
+            let gestures: Vec<Option<Gesture>> = case
+                .events
+                .into_iter()
+                .flatten()
+                .filter_map(|event| {
+                    let gesture = detector.feed(event);
+                    match event.action {
+                        ButtonAction::Press => {
+                            assert_eq!(gesture, None, "{}", case.name);
+                            None
+                        }
+                        ButtonAction::Release => Some(gesture),
+                    }
+                })
+                .collect();
+            assert_eq!(gestures, case.expected, "{}", case.name);
+        }
+    }
+
+    #[test]
+    fn thresholds_from_config_keep_the_defaults() {
+        let config = GestureConfig {
+            drag_min_ms: Some(100),
+            drag_min_distance: None,
+            multi_click_interval_ms: None,
+            multi_click_max_distance: Some(4.0),
+            shift_click: Some(false),
+        };
+        assert_eq!(
+            GestureThresholds::from_config(&config),
+            GestureThresholds {
+                drag_min_ms: 100,
+                multi_click_max_distance: 4.0,
+                shift_click: false,
+                ..GestureThresholds::DEFAULT
+            }
+        );
+    }
+}
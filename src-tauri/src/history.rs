@@ -0,0 +1,262 @@
+use debug_print::debug_println;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tauri::path::BaseDirectory;
+use tauri::Manager;
+
+use crate::APP_HANDLE;
+
+const THUMBNAIL_SIZE: u32 = 240;
+const DEFAULT_MAX_ENTRIES: usize = 100;
+const DEFAULT_MAX_DAYS: u64 = 30;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct OcrHistoryEntry {
+    pub id: String,
+    pub created_at: u128,
+    pub source_app: Option<String>,
+    pub text: String,
+    pub translation: Option<String>,
+    pub image_path: PathBuf,
+    pub thumbnail_path: PathBuf,
+}
+
+// The app that was in the foreground when the capture started. It has to be
+// remembered up front because our own screenshot window takes focus.
+static SOURCE_APP: Mutex<Option<String>> = Mutex::new(None);
+// Tells apart the entries recorded within the same millisecond
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+pub fn remember_foreground_app() {
+    *SOURCE_APP.lock() = active_win_pos_rs::get_active_window()
+        .ok()
+        .map(|window| window.app_name);
+}
+
+pub fn take_source_app() -> Option<String> {
+    SOURCE_APP.lock().take()
+}
+
+fn get_history_dir() -> Result<PathBuf, String> {
+    let app_handle = APP_HANDLE
+        .get()
+        .ok_or_else(|| "App handle not initialized".to_string())?;
+    let history_dir = app_handle
+        .path()
+        .resolve("ocr_history", BaseDirectory::AppCache)
+        .map_err(|e| format!("failed to resolve ocr history dir: {}", e))?;
+    if !history_dir.exists() {
+        std::fs::create_dir_all(&history_dir)
+            .map_err(|e| format!("failed to create ocr history dir: {}", e))?;
+    }
+    Ok(history_dir)
+}
+
+fn now_millis() -> u128 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis()
+}
+
+fn next_id(created_at: u128) -> String {
+    format!("{}-{}", created_at, NEXT_ID.fetch_add(1, Ordering::Relaxed))
+}
+
+// Ids are a timestamp and a counter; anything else is rejected so that an id
+// can't escape the history dir.
+fn is_valid_id(id: &str) -> bool {
+    let mut parts = id.split('-');
+    let is_number = |part: Option<&str>| {
+        part.map_or(false, |part| {
+            !part.is_empty() && part.chars().all(|c| c.is_ascii_digit())
+        })
+    };
+    is_number(parts.next()) && is_number(parts.next()) && parts.next().is_none()
+}
+
+fn read_entry(entry_dir: &Path) -> Option<OcrHistoryEntry> {
+    let content = std::fs::read_to_string(entry_dir.join("entry.json")).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+fn write_entry(entry: &OcrHistoryEntry) -> Result<(), String> {
+    let entry_dir = get_history_dir()?.join(&entry.id);
+    let content = serde_json::to_string(entry).map_err(|e| e.to_string())?;
+    std::fs::write(entry_dir.join("entry.json"), content)
+        .map_err(|e| format!("failed to write ocr history entry: {}", e))
+}
+
+pub fn record(
+    image_file_path: &Path,
+    text: &str,
+    source_app: Option<String>,
+) -> Result<OcrHistoryEntry, String> {
+    let created_at = now_millis();
+    let id = next_id(created_at);
+    let entry_dir = get_history_dir()?.join(&id);
+    std::fs::create_dir(&entry_dir)
+        .map_err(|e| format!("failed to create ocr history entry: {}", e))?;
+
+    let img = image::open(image_file_path).map_err(|e| format!("failed to open image: {}", e))?;
+    let image_path = entry_dir.join("image.png");
+    img.save(&image_path)
+        .map_err(|e| format!("failed to save history image: {}", e))?;
+    let thumbnail_path = entry_dir.join("thumbnail.png");
+    img.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+        .save(&thumbnail_path)
+        .map_err(|e| format!("failed to save history thumbnail: {}", e))?;
+
+    let entry = OcrHistoryEntry {
+        id,
+        created_at,
+        source_app,
+        text: text.to_string(),
+        translation: None,
+        image_path,
+        thumbnail_path,
+    };
+    write_entry(&entry)?;
+    if let Err(e) = apply_retention() {
+        eprintln!("failed to apply ocr history retention: {}", e);
+    }
+    Ok(entry)
+}
+
+fn load_entries() -> Result<Vec<OcrHistoryEntry>, String> {
+    let history_dir = get_history_dir()?;
+    let mut entries = std::fs::read_dir(&history_dir)
+        .map_err(|e| format!("failed to read ocr history dir: {}", e))?
+        .filter_map(|dir_entry| dir_entry.ok())
+        .filter_map(|dir_entry| read_entry(&dir_entry.path()))
+        .collect::<Vec<_>>();
+    entries.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    Ok(entries)
+}
+
+// Keeps at most `ocrHistoryMaxEntries` captures that are younger than
+// `ocrHistoryMaxDays` days.
+fn apply_retention() -> Result<(), String> {
+    let (max_entries, max_days) = match crate::config::get_config() {
+        Ok(config) => (
+            config
+                .ocr_history_max_entries
+                .unwrap_or(DEFAULT_MAX_ENTRIES),
+            config.ocr_history_max_days.unwrap_or(DEFAULT_MAX_DAYS),
+        ),
+        Err(_) => (DEFAULT_MAX_ENTRIES, DEFAULT_MAX_DAYS),
+    };
+    let oldest_allowed = now_millis().saturating_sub(max_days as u128 * 24 * 60 * 60 * 1000);
+    let history_dir = get_history_dir()?;
+    for (i, entry) in load_entries()?.iter().enumerate() {
+        if i >= max_entries || entry.created_at < oldest_allowed {
+            debug_println!("remove ocr history entry: {}", entry.id);
+            std::fs::remove_dir_all(history_dir.join(&entry.id))
+                .map_err(|e| format!("failed to remove ocr history entry: {}", e))?;
+        }
+    }
+    Ok(())
+}
+
+fn get_entry(id: &str) -> Result<OcrHistoryEntry, String> {
+    if !is_valid_id(id) {
+        return Err(format!("Invalid ocr history id: {}", id));
+    }
+    read_entry(&get_history_dir()?.join(id))
+        .ok_or_else(|| format!("ocr history entry not found: {}", id))
+}
+
+// Tells the translator which entry the text it gets next belongs to, so that
+// the translation is stored with it.
+pub fn notify_recorded(entry: &OcrHistoryEntry) {
+    if let Some(handle) = APP_HANDLE.get() {
+        handle
+            .emit("ocr-history-recorded", entry.clone())
+            .unwrap_or_default();
+    }
+}
+
+#[tauri::command]
+pub fn list_ocr_history() -> Result<Vec<OcrHistoryEntry>, String> {
+    load_entries()
+}
+
+#[tauri::command]
+pub fn get_ocr_history_entry(id: String) -> Result<OcrHistoryEntry, String> {
+    get_entry(&id)
+}
+
+#[tauri::command]
+pub fn update_ocr_history_translation(id: String, translation: String) -> Result<(), String> {
+    let mut entry = get_entry(&id)?;
+    entry.translation = Some(translation);
+    write_entry(&entry)
+}
+
+#[tauri::command]
+pub fn delete_ocr_history_entry(id: String) -> Result<(), String> {
+    let entry = get_entry(&id)?;
+    std::fs::remove_dir_all(get_history_dir()?.join(entry.id))
+        .map_err(|e| format!("failed to remove ocr history entry: {}", e))
+}
+
+// Shows a past capture in the translator again, with its recognized text and,
+// if it was translated before, the stored translation.
+#[tauri::command]
+pub async fn reopen_ocr_history_entry(id: String) -> Result<OcrHistoryEntry, String> {
+    let entry = get_entry(&id)?;
+    if let Some(handle) = APP_HANDLE.get() {
+        handle
+            .emit("ocr-history-reopen", entry.clone())
+            .unwrap_or_default();
+    }
+    crate::utils::send_text(entry.text.clone());
+    let window = crate::windows::show_translator_window(false, true, true);
+    window.set_focus().unwrap_or_default();
+    Ok(entry)
+}
+
+// Sends the recognized text of a past capture through the normal translation
+// path, which produces a fresh translation.
+#[tauri::command]
+pub async fn retranslate_ocr_history_entry(id: String) -> Result<(), String> {
+    let entry = get_entry(&id)?;
+    notify_recorded(&entry);
+    crate::utils::send_text(entry.text);
+    crate::windows::show_translator_window(false, true, true);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_are_unique_within_a_millisecond() {
+        let first = next_id(1_700_000_000_000);
+        let second = next_id(1_700_000_000_000);
+        assert_ne!(first, second);
+        assert!(is_valid_id(&first));
+        assert!(is_valid_id(&second));
+    }
+
+    #[test]
+    fn invalid_ids_are_rejected() {
+        assert!(is_valid_id("1700000000000-0"));
+        for id in [
+            "",
+            "1700000000000",
+            "1700000000000-",
+            "-1",
+            "1-2-3",
+            "../1-2",
+            "1-2/..",
+            "1-x",
+        ] {
+            assert!(!is_valid_id(id), "{:?}", id);
+        }
+    }
+}
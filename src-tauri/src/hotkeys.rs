@@ -0,0 +1,237 @@
+use debug_print::debug_println;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::str::FromStr;
+use tauri::{AppHandle, Manager, Runtime};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut};
+
+use crate::config::Config;
+use crate::windows::TRANSLATOR_WIN_NAME;
+use crate::APP_HANDLE;
+
+// What a global shortcut does. The handlers are called directly, so the
+// shortcuts keep working while the webview is loading or reloading.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HotkeyAction {
+    ShowTranslatorWithSelectedText,
+    // Shows the translator with the selected text and the nth action
+    ShowTranslatorWithAction(usize),
+    ShowTranslator,
+    Ocr,
+    Writing {
+        action: Option<String>,
+        target_language: Option<String>,
+    },
+    UndoWriting,
+    CancelWriting,
+    Leader,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HotkeyError {
+    // The config key of the hotkey, e.g. "ocrHotkey" or "writingHotkeys[1]"
+    pub name: String,
+    pub hotkey: String,
+    pub error: String,
+}
+
+struct RegisteredHotkey {
+    shortcut: Shortcut,
+    action: HotkeyAction,
+}
+
+static REGISTERED_HOTKEYS: Mutex<Vec<RegisteredHotkey>> = Mutex::new(Vec::new());
+
+pub fn configured_hotkeys(config: &Config) -> Vec<(String, String, HotkeyAction)> {
+    let mut hotkeys = vec![
+        (
+            "hotkey",
+            &config.hotkey,
+            HotkeyAction::ShowTranslatorWithSelectedText,
+        ),
+        (
+            "displayWindowHotkey",
+            &config.display_window_hotkey,
+            HotkeyAction::ShowTranslator,
+        ),
+        ("ocrHotkey", &config.ocr_hotkey, HotkeyAction::Ocr),
+        (
+            "writingHotkey",
+            &config.writing_hotkey,
+            HotkeyAction::Writing {
+                action: None,
+                target_language: None,
+            },
+        ),
+        (
+            "writingUndoHotkey",
+            &config.writing_undo_hotkey,
+            HotkeyAction::UndoWriting,
+        ),
+        (
+            "writingCancelHotkey",
+            &config.writing_cancel_hotkey,
+            HotkeyAction::CancelWriting,
+        ),
+        ("leaderHotkey", &config.leader_hotkey, HotkeyAction::Leader),
+    ]
+    .into_iter()
+    .filter_map(|(name, hotkey, action)| {
+        hotkey
+            .as_ref()
+            .map(|hotkey| (name.to_string(), hotkey.clone(), action))
+    })
+    .collect::<Vec<_>>();
+    for (i, writing_hotkey) in config.writing_hotkeys.iter().flatten().enumerate() {
+        hotkeys.push((
+            format!("writingHotkeys[{}]", i),
+            writing_hotkey.hotkey.clone(),
+            HotkeyAction::Writing {
+                action: writing_hotkey.action.clone(),
+                target_language: writing_hotkey.target_language.clone(),
+            },
+        ));
+    }
+    hotkeys
+        .into_iter()
+        .filter(|(_, hotkey, _)| !hotkey.trim().is_empty())
+        .collect()
+}
+
+// Registers every hotkey in the config, replacing the ones registered before.
+// Hotkeys that can't be parsed, that are used twice in the config or that
+// another app already owns are skipped and reported as "hotkey-errors".
+pub fn register_hotkeys<R: Runtime>(app: &AppHandle<R>) -> Vec<HotkeyError> {
+    let config = match crate::config::get_config_by_app(app) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("failed to load the hotkeys: {}", e);
+            return Vec::new();
+        }
+    };
+    let global_shortcut = app.global_shortcut();
+    let mut registered_hotkeys = REGISTERED_HOTKEYS.lock();
+    for registered in registered_hotkeys.drain(..) {
+        if let Err(e) = global_shortcut.unregister(registered.shortcut) {
+            eprintln!("failed to unregister a hotkey: {}", e);
+        }
+    }
+    let mut errors = Vec::new();
+    let mut names: Vec<String> = Vec::new();
+    for (name, hotkey, action) in configured_hotkeys(&config) {
+        let mut error = |error: String| {
+            errors.push(HotkeyError {
+                name: name.clone(),
+                hotkey: hotkey.clone(),
+                error,
+            })
+        };
+        let shortcut = match Shortcut::from_str(&hotkey) {
+            Ok(shortcut) => shortcut,
+            Err(e) => {
+                error(format!("invalid hotkey: {}", e));
+                continue;
+            }
+        };
+        if let Some(i) = registered_hotkeys
+            .iter()
+            .position(|registered| registered.shortcut.id() == shortcut.id())
+        {
+            // The plain writing hotkey may be repeated in writingHotkeys
+            if registered_hotkeys[i].action != action {
+                error(format!("already used by {}", names[i]));
+            }
+            continue;
+        }
+        match global_shortcut.register(shortcut) {
+            Ok(_) => {
+                debug_println!("registered {} ({})", name, hotkey);
+                registered_hotkeys.push(RegisteredHotkey { shortcut, action });
+                names.push(name.clone());
+            }
+            Err(e) => error(format!(
+                "failed to register, it may be used by another app: {}",
+                e
+            )),
+        }
+    }
+    for error in &errors {
+        eprintln!("{} \"{}\": {}", error.name, error.hotkey, error.error);
+    }
+    if !errors.is_empty() {
+        app.emit("hotkey-errors", errors.clone())
+            .unwrap_or_default();
+    }
+    errors
+}
+
+// Called by the global shortcut plugin for every registered shortcut, the
+// configured hotkeys and the keys of an armed leader mode.
+pub fn handle_shortcut<R: Runtime>(_app: &AppHandle<R>, shortcut: &Shortcut) {
+    let action = REGISTERED_HOTKEYS
+        .lock()
+        .iter()
+        .find(|registered| registered.shortcut.id() == shortcut.id())
+        .map(|registered| registered.action.clone());
+    match action {
+        Some(action) => {
+            debug_println!("hotkey pressed: {:?}", action);
+            dispatch(action);
+        }
+        None => crate::leader::handle_shortcut(shortcut),
+    }
+}
+
+pub fn dispatch(action: HotkeyAction) {
+    match action {
+        HotkeyAction::ShowTranslatorWithSelectedText => {
+            tauri::async_runtime::spawn(
+                crate::windows::show_translator_window_with_selected_text_command(),
+            );
+        }
+        HotkeyAction::ShowTranslatorWithAction(index) => {
+            tauri::async_runtime::spawn(async move {
+                crate::windows::show_translator_window_with_selected_text_command().await;
+                if let Some(window) = APP_HANDLE
+                    .get()
+                    .and_then(|handle| handle.get_window(TRANSLATOR_WIN_NAME))
+                {
+                    window
+                        .emit("leader-action", json!({ "index": index }))
+                        .unwrap_or_default();
+                }
+            });
+        }
+        HotkeyAction::ShowTranslator => {
+            tauri::async_runtime::spawn(crate::windows::show_translator_window_command());
+        }
+        HotkeyAction::Ocr => {
+            std::thread::spawn(crate::ocr::ocr);
+        }
+        HotkeyAction::Writing {
+            action,
+            target_language,
+        } => {
+            std::thread::spawn(move || crate::writing::writing_command(action, target_language));
+        }
+        HotkeyAction::UndoWriting => {
+            std::thread::spawn(|| {
+                if let Err(e) = crate::writing::undo_writing() {
+                    eprintln!("failed to undo writing: {}", e);
+                }
+            });
+        }
+        HotkeyAction::CancelWriting => crate::writing::cancel_writing(),
+        HotkeyAction::Leader => crate::leader::arm(),
+    }
+}
+
+// Re-reads the config and registers its hotkeys, e.g. after the settings are
+// saved.
+#[tauri::command]
+pub fn register_hotkeys_command<R: Runtime>(app: AppHandle<R>) -> Vec<HotkeyError> {
+    crate::config::clear_config_cache();
+    register_hotkeys(&app)
+}
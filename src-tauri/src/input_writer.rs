@@ -0,0 +1,473 @@
+use debug_print::debug_println;
+use enigo::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+#[cfg(not(target_os = "windows"))]
+use tauri::path::BaseDirectory;
+#[cfg(not(target_os = "windows"))]
+use tauri::Manager;
+
+use crate::clipboard::{ClipboardTransaction, SystemClipboard};
+use crate::key_chord::{parse_key_chord, KeyChord};
+use crate::utils::{key_chord, key_sequence};
+#[cfg(not(target_os = "windows"))]
+use crate::APP_HANDLE;
+
+// Puts text into the focused input of the frontmost application.
+pub trait InputWriter {
+    fn name(&self) -> &'static str;
+    // Whether the writer types a streamed translation chunk by chunk. The
+    // others set the text at once and are given the whole translation when
+    // the stream ends.
+    fn streams(&self) -> bool {
+        false
+    }
+    fn write(&self, enigo: &mut Enigo, text: &str) -> Result<(), String>;
+}
+
+// Sets the selected text of the focused field through the platform
+// accessibility API, so the text appears at once and autocomplete or IME
+// can't interfere. Only available on Linux (AT-SPI) and macOS (AX).
+#[cfg(not(target_os = "windows"))]
+pub struct AccessibilityWriter;
+
+// How fast simulated keystrokes are typed: all at once, one word at a time or
+// one character at a time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypingSpeed {
+    Instant,
+    PerWord,
+    PerChar,
+}
+
+impl TypingSpeed {
+    pub fn from_config() -> Self {
+        let writing_speed = crate::config::get_config()
+            .ok()
+            .and_then(|config| config.writing_speed);
+        match writing_speed.as_deref() {
+            Some("instant") => TypingSpeed::Instant,
+            Some("per-word") => TypingSpeed::PerWord,
+            _ => TypingSpeed::PerChar,
+        }
+    }
+}
+
+const CHAR_DELAY: Duration = Duration::from_millis(20);
+const WORD_DELAY: Duration = Duration::from_millis(60);
+
+// Set by the cancel hotkey; typing stops at the next character or word.
+static TYPING_CANCELLED: AtomicBool = AtomicBool::new(false);
+
+pub fn cancel_typing() {
+    TYPING_CANCELLED.store(true, Ordering::Release);
+}
+
+pub fn reset_typing_cancel() {
+    TYPING_CANCELLED.store(false, Ordering::Release);
+}
+
+pub fn is_typing_cancelled() -> bool {
+    TYPING_CANCELLED.load(Ordering::Acquire)
+}
+
+// Types the text with synthesized key events. Works everywhere, but is slow
+// for long texts, so once `deadline` has passed the rest is pasted instead.
+pub struct KeystrokeWriter {
+    pub speed: TypingSpeed,
+    pub deadline: Option<Instant>,
+}
+
+// Pastes the text through the clipboard and restores the previous clipboard
+// content afterwards.
+pub struct ClipboardPasteWriter;
+
+#[cfg(target_os = "linux")]
+fn set_selected_text(text: &str) -> Result<(), String> {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    let script = APP_HANDLE
+        .get()
+        .ok_or_else(|| "App handle not initialized".to_string())?
+        .path()
+        .resolve("resources/atspi-write-text.py", BaseDirectory::Resource)
+        .map_err(|e| format!("failed to resolve atspi-write-text.py: {}", e))?;
+
+    let mut child = Command::new("python3")
+        .arg(script)
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("failed to run atspi-write-text.py: {}", e))?;
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(text.as_bytes())
+        .map_err(|e| e.to_string())?;
+    let output = child.wait_with_output().map_err(|e| e.to_string())?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn set_selected_text(text: &str) -> Result<(), String> {
+    let apple_script = APP_HANDLE
+        .get()
+        .ok_or_else(|| "App handle not initialized".to_string())?
+        .path()
+        .resolve(
+            "resources/set-selected-text-by-ax.applescript",
+            BaseDirectory::Resource,
+        )
+        .map_err(|e| {
+            format!(
+                "failed to resolve set-selected-text-by-ax.applescript: {}",
+                e
+            )
+        })?;
+
+    let output = std::process::Command::new("osascript")
+        .arg(apple_script)
+        .arg(text)
+        .output()
+        .map_err(|e| format!("failed to run applescript: {}", e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+impl InputWriter for AccessibilityWriter {
+    fn name(&self) -> &'static str {
+        "accessibility"
+    }
+
+    fn write(&self, enigo: &mut Enigo, text: &str) -> Result<(), String> {
+        write_lines(enigo, text, |_, line| set_selected_text(line))
+    }
+}
+
+// The chord that starts a new line in the focused app: the per-app chord from
+// `writingNewlineHotkeys` (e.g. Shift+Enter for chat apps) if there is one,
+// otherwise `writingNewlineHotkey`. None means typing "\n" as is.
+fn get_newline_chord() -> Option<KeyChord> {
+    let config = crate::config::get_config().ok()?;
+    let app_name = active_win_pos_rs::get_active_window()
+        .ok()
+        .map(|window| window.app_name.to_lowercase());
+    let per_app_chord = match (app_name, &config.writing_newline_hotkeys) {
+        (Some(app_name), Some(writing_newline_hotkeys)) => writing_newline_hotkeys
+            .iter()
+            .find(|(name, _)| name.to_lowercase() == app_name)
+            .map(|(_, chord)| chord.clone()),
+        _ => None,
+    };
+    let chord = per_app_chord.or(config.writing_newline_hotkey)?;
+    if chord.trim().is_empty() {
+        return None;
+    }
+    match parse_key_chord(&chord) {
+        Ok(chord) => Some(chord),
+        Err(e) => {
+            eprintln!("{}", e);
+            None
+        }
+    }
+}
+
+// Splits after each run of whitespace, so that every piece is a word followed
+// by the spaces or line breaks that come after it.
+fn split_words(text: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    let mut start = 0;
+    let mut in_whitespace = false;
+    for (i, c) in text.char_indices() {
+        if c.is_whitespace() {
+            in_whitespace = true;
+        } else if in_whitespace {
+            words.push(&text[start..i]);
+            start = i;
+            in_whitespace = false;
+        }
+    }
+    if start < text.len() {
+        words.push(&text[start..]);
+    }
+    words
+}
+
+// Writes the text with `write_line` in one go, or line by line with the
+// newline chord in between when the focused app has one. Stops between lines
+// when cancelled; if a line fails after others were written, the rest is typed
+// instead of being written again by the next writer.
+fn write_lines<F>(enigo: &mut Enigo, text: &str, write_line: F) -> Result<(), String>
+where
+    F: Fn(&mut Enigo, &str) -> Result<(), String>,
+{
+    if is_typing_cancelled() {
+        return Ok(());
+    }
+    let newline_chord = match get_newline_chord() {
+        Some(newline_chord) => newline_chord,
+        None => return write_line(enigo, text),
+    };
+    let mut offset = 0;
+    for (i, line) in text.split('\n').enumerate() {
+        if i > 0 {
+            if is_typing_cancelled() {
+                return Ok(());
+            }
+            key_chord(enigo, &newline_chord);
+            offset += 1;
+        }
+        if !line.is_empty() {
+            if let Err(e) = write_line(enigo, line) {
+                if offset == 0 {
+                    return Err(e);
+                }
+                debug_println!("writing a line failed, typing the rest: {}", e);
+                let keystroke = KeystrokeWriter {
+                    speed: TypingSpeed::Instant,
+                    deadline: None,
+                };
+                return keystroke.write(enigo, &text[offset..]);
+            }
+        }
+        offset += line.len();
+    }
+    Ok(())
+}
+
+// The key events the keystroke writer sends, so that its typing can be
+// checked without a display.
+trait KeySink {
+    fn type_text(&mut self, text: &str);
+    fn press_chord(&mut self, chord: &KeyChord);
+}
+
+impl KeySink for Enigo {
+    fn type_text(&mut self, text: &str) {
+        key_sequence(self, text);
+    }
+
+    fn press_chord(&mut self, chord: &KeyChord) {
+        key_chord(self, chord);
+    }
+}
+
+// Types a piece of text, pressing the newline chord for its line breaks.
+fn type_piece<K: KeySink>(keys: &mut K, piece: &str, newline_chord: &Option<KeyChord>) {
+    let newline_chord = match newline_chord {
+        Some(newline_chord) => newline_chord,
+        None => {
+            keys.type_text(piece);
+            return;
+        }
+    };
+    for (i, line) in piece.split('\n').enumerate() {
+        if i > 0 {
+            keys.press_chord(newline_chord);
+        }
+        if !line.is_empty() {
+            keys.type_text(line);
+        }
+    }
+}
+
+impl KeystrokeWriter {
+    // Types the text at the configured speed. Returns where the text that is
+    // left starts when the deadline passes before it's all typed.
+    fn type_text<K: KeySink>(
+        &self,
+        keys: &mut K,
+        text: &str,
+        newline_chord: &Option<KeyChord>,
+    ) -> Option<usize> {
+        let (pieces, delay) = match self.speed {
+            TypingSpeed::Instant => (vec![text], Duration::ZERO),
+            TypingSpeed::PerWord => (split_words(text), WORD_DELAY),
+            TypingSpeed::PerChar => (
+                text.char_indices()
+                    .map(|(i, c)| &text[i..i + c.len_utf8()])
+                    .collect(),
+                CHAR_DELAY,
+            ),
+        };
+        let mut offset = 0;
+        for piece in pieces {
+            if is_typing_cancelled() {
+                return None;
+            }
+            if self
+                .deadline
+                .map_or(false, |deadline| Instant::now() >= deadline)
+            {
+                return Some(offset);
+            }
+            type_piece(keys, piece, newline_chord);
+            offset += piece.len();
+            thread::sleep(delay);
+        }
+        None
+    }
+}
+
+impl InputWriter for KeystrokeWriter {
+    fn name(&self) -> &'static str {
+        "keystroke"
+    }
+
+    fn streams(&self) -> bool {
+        true
+    }
+
+    fn write(&self, enigo: &mut Enigo, text: &str) -> Result<(), String> {
+        let newline_chord = get_newline_chord();
+        let offset = match self.type_text(enigo, text, &newline_chord) {
+            Some(offset) => offset,
+            None => return Ok(()),
+        };
+        let rest = &text[offset..];
+        debug_println!("typing time is up, pasting {} bytes", rest.len());
+        ClipboardPasteWriter.write(enigo, rest).or_else(|e| {
+            debug_println!("clipboard writer failed: {}", e);
+            type_piece(enigo, rest, &newline_chord);
+            Ok(())
+        })
+    }
+}
+
+impl InputWriter for ClipboardPasteWriter {
+    fn name(&self) -> &'static str {
+        "clipboard"
+    }
+
+    fn write(&self, enigo: &mut Enigo, text: &str) -> Result<(), String> {
+        write_lines(enigo, text, |enigo, line| {
+            let mut clipboard = SystemClipboard;
+            let mut transaction = ClipboardTransaction::begin(&mut clipboard)?;
+            transaction.paste_text(
+                line,
+                || crate::utils::paste(enigo),
+                Duration::from_millis(100),
+            )
+        })
+    }
+}
+
+// Setting the text directly is the default, as it is instant and can't be
+// disturbed by autocomplete or IME; simulated keystrokes are the fallback for
+// fields that don't support it.
+fn get_writers(speed: TypingSpeed, deadline: Option<Instant>) -> Vec<Box<dyn InputWriter>> {
+    let writing_input_method = crate::config::get_config()
+        .ok()
+        .and_then(|config| config.writing_input_method)
+        .unwrap_or_else(|| "auto".to_string());
+    let keystroke: Box<dyn InputWriter> = Box::new(KeystrokeWriter { speed, deadline });
+    match writing_input_method.as_str() {
+        "keystroke" => vec![keystroke],
+        "clipboard" => vec![Box::new(ClipboardPasteWriter), keystroke],
+        #[cfg(not(target_os = "windows"))]
+        "accessibility" => vec![Box::new(AccessibilityWriter), keystroke],
+        #[cfg(not(target_os = "windows"))]
+        _ => vec![
+            Box::new(AccessibilityWriter),
+            Box::new(ClipboardPasteWriter),
+            keystroke,
+        ],
+        // Windows has no accessibility writer, so pasting is the direct way
+        #[cfg(target_os = "windows")]
+        _ => vec![Box::new(ClipboardPasteWriter), keystroke],
+    }
+}
+
+// Whether the configured writer types streamed chunks as they arrive, rather
+// than taking the whole text at the end.
+pub fn streams_chunks() -> bool {
+    get_writers(TypingSpeed::Instant, None)
+        .first()
+        .map_or(true, |writer| writer.streams())
+}
+
+// Writes with the configured writer, falling back to the next one (ending with
+// simulated keystrokes) when a writer fails.
+pub fn write_text(enigo: &mut Enigo, text: &str, speed: TypingSpeed, deadline: Option<Instant>) {
+    for writer in get_writers(speed, deadline) {
+        match writer.write(enigo, text) {
+            Ok(_) => return,
+            Err(e) => {
+                debug_println!("{} writer failed: {}", writer.name(), e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NEWLINE: &str = "<newline>";
+
+    #[derive(Default)]
+    struct RecordedKeys(Vec<String>);
+
+    impl KeySink for RecordedKeys {
+        fn type_text(&mut self, text: &str) {
+            self.0.push(text.to_string());
+        }
+
+        fn press_chord(&mut self, _chord: &KeyChord) {
+            self.0.push(NEWLINE.to_string());
+        }
+    }
+
+    fn type_with(
+        speed: TypingSpeed,
+        deadline: Option<Instant>,
+        text: &str,
+    ) -> (Vec<String>, Option<usize>) {
+        let newline_chord = Some(parse_key_chord("shift+enter").unwrap());
+        let mut keys = RecordedKeys::default();
+        let writer = KeystrokeWriter { speed, deadline };
+        let rest = writer.type_text(&mut keys, text, &newline_chord);
+        (keys.0, rest)
+    }
+
+    #[test]
+    fn every_speed_presses_the_newline_chord() {
+        let text = "one two\nthree";
+        let (keys, rest) = type_with(TypingSpeed::Instant, None, text);
+        assert_eq!(keys, vec!["one two", NEWLINE, "three"]);
+        assert_eq!(rest, None);
+
+        let (keys, _) = type_with(TypingSpeed::PerWord, None, text);
+        assert_eq!(keys, vec!["one ", "two", NEWLINE, "three"]);
+
+        let (keys, _) = type_with(TypingSpeed::PerChar, None, "a\nb");
+        assert_eq!(keys, vec!["a", NEWLINE, "b"]);
+    }
+
+    #[test]
+    fn the_rest_is_left_once_the_deadline_passes() {
+        let (keys, rest) = type_with(TypingSpeed::PerWord, Some(Instant::now()), "one\ntwo");
+        assert!(keys.is_empty());
+        assert_eq!(rest, Some(0));
+
+        // What is left is typed with the newline chord too when pasting fails
+        let mut keys = RecordedKeys::default();
+        type_piece(
+            &mut keys,
+            "one\ntwo",
+            &Some(parse_key_chord("shift+enter").unwrap()),
+        );
+        assert_eq!(keys.0, vec!["one", NEWLINE, "two"]);
+    }
+}
@@ -0,0 +1,335 @@
+use enigo::*;
+
+// A key combination such as "shift+enter" or "CommandOrControl+Shift+F1":
+// modifiers that are held down while the main key is clicked. Used for the
+// chords typed while writing, e.g. `writingNewlineHotkey`. They are written
+// the way the global shortcut plugin reads hotkeys, so that both kinds of
+// chords are checked by the same parser.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyChord {
+    pub modifiers: Vec<Key>,
+    pub key: Option<Key>,
+}
+
+fn parse_modifier(name: &str) -> Option<Key> {
+    match name {
+        "ctrl" | "control" => Some(Key::Control),
+        "alt" | "option" => Some(Key::Alt),
+        "shift" => Some(Key::Shift),
+        "cmd" | "command" | "super" => Some(Key::Meta),
+        "commandorcontrol" | "cmdorctrl" | "commandorctrl" | "cmdorcontrol" => {
+            if cfg!(target_os = "macos") {
+                Some(Key::Meta)
+            } else {
+                Some(Key::Control)
+            }
+        }
+        _ => None,
+    }
+}
+
+fn parse_function_key(name: &str) -> Option<Key> {
+    let number = name.strip_prefix('f')?.parse::<u8>().ok()?;
+    let key = match number {
+        1 => Key::F1,
+        2 => Key::F2,
+        3 => Key::F3,
+        4 => Key::F4,
+        5 => Key::F5,
+        6 => Key::F6,
+        7 => Key::F7,
+        8 => Key::F8,
+        9 => Key::F9,
+        10 => Key::F10,
+        11 => Key::F11,
+        12 => Key::F12,
+        _ => return None,
+    };
+    Some(key)
+}
+
+fn single_char(name: &str) -> Option<char> {
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
+
+// The keys that can be typed, by their names in the global shortcut plugin.
+fn parse_key(name: &str) -> Option<Key> {
+    let key = match name {
+        "enter" => Key::Return,
+        "tab" => Key::Tab,
+        "space" => Key::Space,
+        "escape" | "esc" => Key::Escape,
+        "backspace" => Key::Backspace,
+        "delete" => Key::Delete,
+        "capslock" => Key::CapsLock,
+        "home" => Key::Home,
+        "end" => Key::End,
+        "pageup" => Key::PageUp,
+        "pagedown" => Key::PageDown,
+        "arrowup" | "up" => Key::UpArrow,
+        "arrowdown" | "down" => Key::DownArrow,
+        "arrowleft" | "left" => Key::LeftArrow,
+        "arrowright" | "right" => Key::RightArrow,
+        "backquote" | "`" => Key::Layout('`'),
+        "minus" | "-" => Key::Layout('-'),
+        "equal" | "=" => Key::Layout('='),
+        "bracketleft" | "[" => Key::Layout('['),
+        "bracketright" | "]" => Key::Layout(']'),
+        "backslash" | "\\" => Key::Layout('\\'),
+        "semicolon" | ";" => Key::Layout(';'),
+        "quote" | "'" => Key::Layout('\''),
+        "comma" | "," => Key::Layout(','),
+        "period" | "." => Key::Layout('.'),
+        "slash" | "/" => Key::Layout('/'),
+        _ => {
+            let letter = single_char(name.strip_prefix("key").unwrap_or(name))
+                .filter(char::is_ascii_lowercase);
+            let digit = single_char(name.strip_prefix("digit").unwrap_or(name))
+                .filter(char::is_ascii_digit);
+            return letter
+                .or(digit)
+                .map(Key::Layout)
+                .or_else(|| parse_function_key(name));
+        }
+    };
+    Some(key)
+}
+
+// The other keys the global shortcut plugin knows, which work as hotkeys but
+// can't be typed.
+fn is_untyped_key(name: &str) -> bool {
+    let is_numbered = |prefix: &str, range: std::ops::RangeInclusive<u8>| {
+        name.strip_prefix(prefix)
+            .and_then(|number| number.parse::<u8>().ok())
+            .map_or(false, |number| range.contains(&number))
+    };
+    is_numbered("f", 13..=24)
+        || is_numbered("numpad", 0..=9)
+        || is_numbered("num", 0..=9)
+        || matches!(
+            name,
+            "insert"
+                | "printscreen"
+                | "scrolllock"
+                | "numlock"
+                | "numpadadd"
+                | "numadd"
+                | "numpadplus"
+                | "numplus"
+                | "numpaddecimal"
+                | "numdecimal"
+                | "numpaddivide"
+                | "numdivide"
+                | "numpadenter"
+                | "numenter"
+                | "numpadequal"
+                | "numequal"
+                | "numpadmultiply"
+                | "nummultiply"
+                | "numpadsubtract"
+                | "numsubtract"
+                | "audiovolumedown"
+                | "volumedown"
+                | "audiovolumeup"
+                | "volumeup"
+                | "audiovolumemute"
+                | "volumemute"
+                | "mediaplay"
+                | "mediapause"
+                | "mediaplaypause"
+                | "mediastop"
+                | "mediatracknext"
+                | "mediatrackprev"
+        )
+}
+
+// Parses a chord the way the global shortcut plugin does: "+"-separated
+// modifiers followed by exactly one key, in any case. The key is None when the
+// plugin knows it but it can't be typed.
+fn parse_chord(chord: &str) -> Result<(Vec<Key>, Option<Key>), String> {
+    let parts: Vec<&str> = chord.split('+').map(|part| part.trim()).collect();
+    let (key_part, modifier_parts) = parts.split_last().unwrap();
+    let mut modifiers = Vec::new();
+    for part in modifier_parts.iter().chain(Some(key_part)) {
+        if part.is_empty() {
+            return Err(format!("Invalid key chord \"{}\": empty key", chord));
+        }
+    }
+    for part in modifier_parts {
+        let modifier = parse_modifier(&part.to_lowercase()).ok_or_else(|| {
+            format!(
+                "Invalid key chord \"{}\": \"{}\" is not a modifier; the key goes last",
+                chord, part
+            )
+        })?;
+        if !modifiers.contains(&modifier) {
+            modifiers.push(modifier);
+        }
+    }
+    let name = key_part.to_lowercase();
+    match parse_key(&name) {
+        Some(key) => Ok((modifiers, Some(key))),
+        None if is_untyped_key(&name) => Ok((modifiers, None)),
+        None if parse_modifier(&name).is_some() => Err(format!(
+            "Invalid key chord \"{}\": it has no key besides modifiers",
+            chord
+        )),
+        None => Err(format!(
+            "Invalid key chord \"{}\": unknown key \"{}\"",
+            chord, key_part
+        )),
+    }
+}
+
+// Checks a global shortcut before it's registered.
+pub fn validate_shortcut(chord: &str) -> Result<(), String> {
+    parse_chord(chord).map(|_| ())
+}
+
+pub fn parse_key_chord(chord: &str) -> Result<KeyChord, String> {
+    let (modifiers, key) = parse_chord(chord)?;
+    let key =
+        key.ok_or_else(|| format!("Invalid key chord \"{}\": its key can't be typed", chord))?;
+    Ok(KeyChord {
+        modifiers,
+        key: Some(key),
+    })
+}
+
+pub fn press_key_chord(enigo: &mut Enigo, chord: &KeyChord) {
+    for modifier in &chord.modifiers {
+        enigo.key_down(*modifier);
+    }
+    if let Some(key) = chord.key {
+        enigo.key_click(key);
+    }
+    for modifier in chord.modifiers.iter().rev() {
+        enigo.key_up(*modifier);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chord(modifiers: Vec<Key>, key: Key) -> Result<KeyChord, String> {
+        Ok(KeyChord {
+            modifiers,
+            key: Some(key),
+        })
+    }
+
+    #[test]
+    fn parses_the_shortcut_grammar() {
+        let cases = [
+            ("shift+enter", vec![Key::Shift], Key::Return),
+            ("Cmd+A", vec![Key::Meta], Key::Layout('a')),
+            ("Command+KeyA", vec![Key::Meta], Key::Layout('a')),
+            ("super+z", vec![Key::Meta], Key::Layout('z')),
+            ("Option+Tab", vec![Key::Alt], Key::Tab),
+            (
+                "Control+Alt+Delete",
+                vec![Key::Control, Key::Alt],
+                Key::Delete,
+            ),
+            (
+                "ctrl + shift + space",
+                vec![Key::Control, Key::Shift],
+                Key::Space,
+            ),
+            ("shift+shift+Digit1", vec![Key::Shift], Key::Layout('1')),
+            ("Escape", vec![], Key::Escape),
+            ("esc", vec![], Key::Escape),
+            ("ArrowUp", vec![], Key::UpArrow),
+            ("Down", vec![], Key::DownArrow),
+            ("alt+left", vec![Key::Alt], Key::LeftArrow),
+            ("ArrowRight", vec![], Key::RightArrow),
+            ("ctrl+Backquote", vec![Key::Control], Key::Layout('`')),
+            ("ctrl+`", vec![Key::Control], Key::Layout('`')),
+            ("BracketLeft", vec![], Key::Layout('[')),
+            ("Quote", vec![], Key::Layout('\'')),
+            ("alt+Comma", vec![Key::Alt], Key::Layout(',')),
+            ("Shift+Slash", vec![Key::Shift], Key::Layout('/')),
+            ("Minus", vec![], Key::Layout('-')),
+            ("-", vec![], Key::Layout('-')),
+        ];
+        for (text, modifiers, key) in cases {
+            assert_eq!(parse_key_chord(text), chord(modifiers, key), "{}", text);
+            assert_eq!(validate_shortcut(text), Ok(()), "{}", text);
+        }
+
+        let command_or_control = if cfg!(target_os = "macos") {
+            Key::Meta
+        } else {
+            Key::Control
+        };
+        assert_eq!(
+            parse_key_chord("CommandOrControl+Shift+Enter"),
+            chord(vec![command_or_control, Key::Shift], Key::Return)
+        );
+    }
+
+    #[test]
+    fn function_keys() {
+        let keys = [
+            Key::F1,
+            Key::F2,
+            Key::F3,
+            Key::F4,
+            Key::F5,
+            Key::F6,
+            Key::F7,
+            Key::F8,
+            Key::F9,
+            Key::F10,
+            Key::F11,
+            Key::F12,
+        ];
+        for (i, key) in keys.iter().enumerate() {
+            let text = format!("F{}", i + 1);
+            assert_eq!(parse_key_chord(&text), chord(vec![], *key), "{}", text);
+        }
+    }
+
+    #[test]
+    fn hotkeys_that_cannot_be_typed() {
+        for text in [
+            "F13",
+            "F24",
+            "PrintScreen",
+            "ctrl+Numpad1",
+            "VolumeUp",
+            "Insert",
+        ] {
+            assert_eq!(validate_shortcut(text), Ok(()), "{}", text);
+            assert!(parse_key_chord(text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn rejects_what_the_shortcut_plugin_rejects() {
+        for text in [
+            "",
+            "shift+",
+            "+a",
+            "shift",
+            "ctrl+shift",
+            "a+shift",
+            "ctrl+a+b",
+            "hyper+a",
+            "win+a",
+            "meta+a",
+            "return",
+            "F25",
+            "keyaa",
+            "Digitx",
+        ] {
+            assert!(validate_shortcut(text).is_err(), "{:?}", text);
+            assert!(parse_key_chord(text).is_err(), "{:?}", text);
+        }
+    }
+}
@@ -1,4 +1,46 @@
-use whatlang::detect;
+use serde::{Deserialize, Serialize};
+use whatlang::{detect, detect_script, Detector, Info, Lang, Script};
+
+use crate::chinese::ChineseVariant;
+use crate::config::Config;
+
+const DEFAULT_TOP_N: usize = 3;
+// Text with fewer letters than this is too short for a reliable guess
+const MIN_RELIABLE_LETTERS: usize = 12;
+// Segments with fewer letters are merged into their neighbours
+const MIN_SEGMENT_LETTERS: usize = 12;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LangCandidate {
+    // The frontend's code, e.g. "zh-Hans", or the ISO 639-3 code for the
+    // languages the frontend doesn't know
+    pub lang: String,
+    pub iso639_3: String,
+    pub confidence: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LangSegment {
+    pub text: String,
+    pub lang: Option<String>,
+    pub script: Option<String>,
+    pub confidence: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LangDetection {
+    pub lang: Option<String>,
+    pub script: Option<String>,
+    pub is_reliable: bool,
+    // Ordered by confidence, the first one is `lang`
+    pub candidates: Vec<LangCandidate>,
+    // The text split where it switches languages; the segments add up to the
+    // whole text
+    pub segments: Vec<LangSegment>,
+}
 
 #[tauri::command]
 pub fn detect_lang(text: String) -> String {
@@ -7,3 +49,406 @@ pub fn detect_lang(text: String) -> String {
         None => "".to_string(),
     }
 }
+
+// The language code the frontend uses for a detected language, e.g. "en" or
+// "zh-Hans". Languages the frontend doesn't know give None.
+pub fn lang_code(lang: Lang) -> Option<&'static str> {
+    let code = match lang {
+        Lang::Eng => "en",
+        Lang::Cmn => "zh-Hans",
+        Lang::Jpn => "ja",
+        Lang::Kor => "ko",
+        Lang::Fra => "fr",
+        Lang::Deu => "de",
+        Lang::Spa => "es",
+        Lang::Ita => "it",
+        Lang::Por => "pt",
+        Lang::Rus => "ru",
+        Lang::Ukr => "uk",
+        Lang::Pol => "pl",
+        Lang::Nld => "nl",
+        Lang::Swe => "sv",
+        Lang::Dan => "da",
+        Lang::Nob => "no",
+        Lang::Fin => "fi",
+        Lang::Ces => "cs",
+        Lang::Slk => "sk",
+        Lang::Hun => "hu",
+        Lang::Ron => "ro",
+        Lang::Bul => "bg",
+        Lang::Ell => "el",
+        Lang::Tur => "tr",
+        Lang::Ara => "ar",
+        Lang::Heb => "he",
+        Lang::Pes => "fa",
+        Lang::Hin => "hi",
+        Lang::Ben => "bn",
+        Lang::Tha => "th",
+        Lang::Vie => "vi",
+        Lang::Ind => "id",
+        Lang::Bel => "be",
+        _ => return None,
+    };
+    Some(code)
+}
+
+// The frontend's code for the language of the text, telling traditional
+// Chinese apart, or the ISO 639-3 code for the languages it doesn't know.
+fn lang_code_for(lang: Lang, text: &str) -> String {
+    if lang == Lang::Cmn {
+        match crate::chinese::detect_variant(text) {
+            Some(ChineseVariant::Simplified) | None => {}
+            Some(_) => return "zh-Hant".to_string(),
+        }
+    }
+    lang_code(lang)
+        .map(|code| code.to_string())
+        .unwrap_or_else(|| lang.code().to_string())
+}
+
+// The whatlang language for a frontend or ISO 639-3 code. Only the primary
+// subtag counts, so "zh-Hant" and "en-GB" work too.
+pub fn parse_lang(code: &str) -> Option<Lang> {
+    if let Some(lang) = Lang::from_code(code.to_lowercase()) {
+        return Some(lang);
+    }
+    Lang::all()
+        .iter()
+        .copied()
+        .find(|&lang| lang_code(lang).map_or(false, |known| is_same_language(known, code)))
+}
+
+// Whether two language codes name the same language, ignoring the script and
+// region, e.g. "zh-Hans" and "zh-Hant".
+pub fn is_same_language(a: &str, b: &str) -> bool {
+    let primary = |code: &str| code.split('-').next().unwrap_or("").to_lowercase();
+    primary(a) == primary(b)
+}
+
+// The languages detection is limited to: the given codes, or else the
+// `detectLanguages` of the config. An empty list means all languages.
+fn get_allowlist(codes: Option<Vec<String>>) -> Vec<Lang> {
+    let codes = codes
+        .or_else(|| {
+            crate::config::get_config()
+                .ok()
+                .and_then(|config| config.detect_languages)
+        })
+        .unwrap_or_default();
+    let mut allowlist = Vec::new();
+    for code in codes {
+        match parse_lang(&code) {
+            Some(lang) if !allowlist.contains(&lang) => allowlist.push(lang),
+            Some(_) => {}
+            None => eprintln!("unknown language in the detection allowlist: {}", code),
+        }
+    }
+    allowlist
+}
+
+fn detect_with(text: &str, allowlist: &[Lang]) -> Option<Info> {
+    if allowlist.is_empty() {
+        detect(text)
+    } else {
+        Detector::with_allowlist(allowlist.to_vec()).detect(text)
+    }
+}
+
+fn count_letters(text: &str) -> usize {
+    text.chars().filter(|c| c.is_alphabetic()).count()
+}
+
+// The `top_n` most likely languages. whatlang only reports its best guess, so
+// the runners-up come from detecting again without the languages found so
+// far; each share of the confidence is taken from what the ones before left.
+fn rank_langs(text: &str, allowlist: &[Lang], top_n: usize) -> Vec<LangCandidate> {
+    let mut remaining: Vec<Lang> = if allowlist.is_empty() {
+        Lang::all().to_vec()
+    } else {
+        allowlist.to_vec()
+    };
+    let mut candidates = Vec::new();
+    let mut left = 1.0;
+    while candidates.len() < top_n && !remaining.is_empty() {
+        // whatlang tells Han text apart by script alone and may answer with a
+        // language that isn't in the allowlist
+        let info = match Detector::with_allowlist(remaining.clone()).detect(text) {
+            Some(info) if remaining.contains(&info.lang()) => info,
+            _ => break,
+        };
+        let confidence = left * info.confidence();
+        left -= confidence;
+        remaining.retain(|&lang| lang != info.lang());
+        candidates.push(LangCandidate {
+            lang: lang_code_for(info.lang(), text),
+            iso639_3: info.lang().code().to_string(),
+            confidence,
+        });
+    }
+    candidates
+}
+
+fn detect_segment(text: &str, allowlist: &[Lang]) -> LangSegment {
+    let info = detect_with(text, allowlist);
+    LangSegment {
+        text: text.to_string(),
+        lang: info.as_ref().map(|info| lang_code_for(info.lang(), text)),
+        script: detect_script(text).map(|script| script.name().to_string()),
+        confidence: info.map_or(0.0, |info| info.confidence()),
+    }
+}
+
+// Sentence ends that need a space after them to end a sentence, unlike the
+// CJK ones, so that "3.14" stays in one piece
+fn is_spaced_sentence_end(c: char) -> bool {
+    matches!(c, '.' | '!' | '?' | ';')
+}
+
+fn is_sentence_end(c: char) -> bool {
+    matches!(c, '\n' | '。' | '！' | '？' | '；' | '…')
+}
+
+// Japanese mixes kanji and kana within a sentence
+fn script_group(script: Script) -> Script {
+    match script {
+        Script::Hiragana | Script::Katakana => Script::Mandarin,
+        script => script,
+    }
+}
+
+// Splits the text after sentence ends and where the script changes. Spaces and
+// punctuation stay with the piece before them, so the pieces add up to the
+// text.
+fn split_pieces(text: &str) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    let mut script = None;
+    let mut after_spaced_end = false;
+    let mut ended = false;
+    for (i, c) in text.char_indices() {
+        let char_script = if c.is_alphabetic() {
+            detect_script(c.encode_utf8(&mut [0; 4])).map(script_group)
+        } else {
+            None
+        };
+        let script_changed = match (script, char_script) {
+            (Some(a), Some(b)) => a != b,
+            _ => false,
+        };
+        if i > start && c.is_alphanumeric() && (ended || script_changed) {
+            pieces.push(&text[start..i]);
+            start = i;
+            script = None;
+            ended = false;
+        }
+        if c.is_alphanumeric() {
+            after_spaced_end = false;
+        } else if is_sentence_end(c) || (after_spaced_end && c.is_whitespace()) {
+            ended = true;
+        }
+        after_spaced_end = is_spaced_sentence_end(c) || (after_spaced_end && !c.is_whitespace());
+        if char_script.is_some() {
+            script = char_script;
+        }
+    }
+    if start < text.len() {
+        pieces.push(&text[start..]);
+    }
+    pieces
+}
+
+// Detects the language of every part of a text that switches languages. Short
+// pieces, e.g. a product name in another script, join the piece before them,
+// and neighbours in the same language are merged.
+fn segment(text: &str, allowlist: &[Lang]) -> Vec<LangSegment> {
+    let mut merged: Vec<String> = Vec::new();
+    for piece in split_pieces(text) {
+        match merged.last_mut() {
+            Some(last)
+                if count_letters(piece) < MIN_SEGMENT_LETTERS
+                    || count_letters(last) < MIN_SEGMENT_LETTERS =>
+            {
+                last.push_str(piece)
+            }
+            _ => merged.push(piece.to_string()),
+        }
+    }
+    let mut segments: Vec<LangSegment> = Vec::new();
+    for piece in merged {
+        let segment = detect_segment(&piece, allowlist);
+        match segments.last_mut() {
+            Some(last) if last.lang == segment.lang => {
+                let (a, b) = (count_letters(&last.text), count_letters(&segment.text));
+                if a + b > 0 {
+                    last.confidence = (last.confidence * a as f64 + segment.confidence * b as f64)
+                        / (a + b) as f64;
+                }
+                last.text.push_str(&segment.text);
+                if last.script != segment.script {
+                    last.script = None;
+                }
+            }
+            _ => segments.push(segment),
+        }
+    }
+    segments
+}
+
+pub fn detect_lang_details(
+    text: &str,
+    top_n: Option<usize>,
+    allowlist: Option<Vec<String>>,
+) -> LangDetection {
+    let allowlist = get_allowlist(allowlist);
+    let candidates = rank_langs(text, &allowlist, top_n.unwrap_or(DEFAULT_TOP_N).max(1));
+    let is_reliable = count_letters(text) >= MIN_RELIABLE_LETTERS
+        && detect_with(text, &allowlist).map_or(false, |info| info.is_reliable());
+    LangDetection {
+        lang: candidates.first().map(|candidate| candidate.lang.clone()),
+        script: detect_script(text).map(|script| script.name().to_string()),
+        is_reliable,
+        candidates,
+        segments: segment(text, &allowlist),
+    }
+}
+
+// Like `detect_lang`, with the candidates, script, reliability and segments,
+// and limited to `allowlist` or the configured languages.
+#[tauri::command]
+pub fn detect_lang_details_command(
+    text: String,
+    top_n: Option<usize>,
+    allowlist: Option<Vec<String>>,
+) -> LangDetection {
+    detect_lang_details(&text, top_n, allowlist)
+}
+
+// The frontend's code for the language of the text, limited to the
+// `detectLanguages` of the config.
+pub fn detect_lang_code(text: &str, config: &Config) -> Option<String> {
+    let allowlist = get_allowlist(Some(config.detect_languages.clone().unwrap_or_default()));
+    detect_with(text, &allowlist).map(|info| lang_code_for(info.lang(), text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pieces() {
+        let cases: [(&str, &[&str]); 5] = [
+            (
+                "Pi is 3.14 and e is 2.72. Both are irrational.",
+                &["Pi is 3.14 and e is 2.72. ", "Both are irrational."],
+            ),
+            (
+                "See example.com, v1.2.3 and 1.5x faster",
+                &["See example.com, v1.2.3 and 1.5x faster"],
+            ),
+            ("我喜欢Rust语言", &["我喜欢", "Rust", "语言"]),
+            (
+                "第一句。第二句！第三句",
+                &["第一句。", "第二句！", "第三句"],
+            ),
+            ("これは日本語の文です", &["これは日本語の文です"]),
+        ];
+        for (text, pieces) in cases {
+            assert_eq!(split_pieces(text), pieces, "{}", text);
+        }
+    }
+
+    #[test]
+    fn mixed_english_and_chinese() {
+        let english = "The quick brown fox jumps over the lazy dog while the children are playing in the garden. ";
+        let chinese = "今天天气很好，我们一起去公园散步吧，顺便买一些新鲜的水果回家。";
+        let text = format!("{}{}", english, chinese);
+        let segments = segment(&text, &[]);
+        let langs: Vec<Option<&str>> = segments
+            .iter()
+            .map(|segment| segment.lang.as_deref())
+            .collect();
+        assert_eq!(langs, vec![Some("en"), Some("zh-Hans")]);
+        assert_eq!(segments[0].text, english);
+        assert_eq!(segments[1].text, chinese);
+        assert_eq!(segments[0].script.as_deref(), Some("Latin"));
+        assert_eq!(segments[1].script.as_deref(), Some("Mandarin"));
+    }
+
+    #[test]
+    fn short_pieces_are_merged() {
+        // A Chinese name in an English sentence joins it instead of being a
+        // segment of its own
+        let text = "I love 北京 very much, it is a great city to visit in the summer.";
+        let segments = segment(text, &[]);
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].text, text);
+        assert_eq!(segments[0].lang.as_deref(), Some("en"));
+        assert_eq!(segments[0].script.as_deref(), Some("Latin"));
+
+        // Neighbouring sentences in the same language are one segment
+        let text = "This is the first sentence of the text. And this is the second sentence of it.";
+        let segments = segment(text, &[]);
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].text, text);
+        assert!(segments[0].confidence > 0.0 && segments[0].confidence <= 1.0);
+    }
+
+    #[test]
+    fn candidate_confidences() {
+        let texts = [
+            "The quick brown fox jumps over the lazy dog.",
+            "Le renard brun rapide saute par-dessus le chien paresseux.",
+            "今天天气很好，我们一起去公园散步吧。",
+            "ok",
+        ];
+        for text in texts {
+            for top_n in [1, 3, 5] {
+                let candidates = rank_langs(text, &[], top_n);
+                assert!(
+                    !candidates.is_empty() && candidates.len() <= top_n,
+                    "{}",
+                    text
+                );
+                let total: f64 = candidates
+                    .iter()
+                    .map(|candidate| candidate.confidence)
+                    .sum();
+                assert!(total <= 1.0 + 1e-9, "{}: {}", text, total);
+                assert!(candidates
+                    .iter()
+                    .all(|candidate| candidate.confidence >= 0.0));
+                let mut langs: Vec<&str> = candidates
+                    .iter()
+                    .map(|candidate| candidate.iso639_3.as_str())
+                    .collect();
+                langs.sort_unstable();
+                langs.dedup();
+                assert_eq!(langs.len(), candidates.len(), "{}", text);
+            }
+        }
+        // Never more candidates than allowed languages
+        let candidates = rank_langs(texts[0], &[Lang::Eng, Lang::Fra], 3);
+        assert_eq!(candidates.len(), 2);
+        assert_eq!(candidates[0].lang, "en");
+    }
+
+    #[test]
+    fn language_codes() {
+        let cases = [
+            ("en", Some(Lang::Eng)),
+            ("en-GB", Some(Lang::Eng)),
+            ("eng", Some(Lang::Eng)),
+            ("zh-Hans", Some(Lang::Cmn)),
+            ("zh-Hant", Some(Lang::Cmn)),
+            ("cmn", Some(Lang::Cmn)),
+            ("xx", None),
+            ("", None),
+        ];
+        for (code, lang) in cases {
+            assert_eq!(parse_lang(code), lang, "{}", code);
+        }
+        assert!(is_same_language("zh-Hans", "zh-Hant"));
+        assert!(is_same_language("EN", "en-US"));
+        assert!(!is_same_language("en", "eng"));
+    }
+}
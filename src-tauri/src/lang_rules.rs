@@ -0,0 +1,206 @@
+use serde::{Deserialize, Serialize};
+
+use crate::config::{get_config, Config};
+use crate::lang::{detect_lang_code, is_same_language, parse_lang};
+
+// Picks the target language by the language of the text, e.g. English to
+// Chinese. A rule without `source` (or with "*") matches any language, so it
+// goes last as the "otherwise" rule.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TargetLanguageRule {
+    pub source: Option<String>,
+    pub target: String,
+}
+
+impl TargetLanguageRule {
+    pub fn validate(&self) -> Result<(), String> {
+        for code in self.source.iter().chain(Some(&self.target)) {
+            if code != "*" && parse_lang(code).is_none() {
+                return Err(format!("unknown language \"{}\"", code));
+            }
+        }
+        Ok(())
+    }
+
+    fn matches(&self, source: Option<&str>) -> bool {
+        match (self.source.as_deref(), source) {
+            (None | Some("*"), _) => true,
+            (Some(rule_source), Some(source)) => is_same_language(rule_source, source),
+            (Some(_), None) => false,
+        }
+    }
+}
+
+// The target language of the first rule matching the language of the text,
+// skipping rules that would translate the text into its own language. None
+// when no rule matches, and the caller's default applies.
+pub fn route_target_language_with(config: &Config, text: &str) -> Option<String> {
+    let rules = config.target_language_rules.as_ref()?;
+    if rules.is_empty() {
+        return None;
+    }
+    let source = detect_lang_code(text, config);
+    rules
+        .iter()
+        .filter(|rule| rule.matches(source.as_deref()))
+        .find(|rule| {
+            source
+                .as_deref()
+                .map_or(true, |source| !is_same_language(source, &rule.target))
+        })
+        .map(|rule| rule.target.clone())
+}
+
+pub fn route_target_language(text: &str) -> Option<String> {
+    get_config()
+        .ok()
+        .and_then(|config| route_target_language_with(&config, text))
+}
+
+#[tauri::command]
+pub fn get_routed_target_language(text: String) -> Option<String> {
+    route_target_language(&text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const ENGLISH: &str = "The quick brown fox jumps over the lazy dog in the garden.";
+    const CHINESE: &str = "今天天气很好，我们一起去公园散步吧。";
+    const TRADITIONAL_CHINESE: &str = "今天天氣很好，我們一起去公園散步吧。";
+    const FRENCH: &str = "Le renard brun rapide saute par-dessus le chien paresseux.";
+    const JAPANESE: &str = "今日はとても良い天気なので、公園を散歩しましょう。";
+
+    fn config(rules: serde_json::Value) -> Config {
+        serde_json::from_value(json!({ "targetLanguageRules": rules })).unwrap()
+    }
+
+    #[test]
+    fn routes_by_source_language() {
+        let tables = [
+            (
+                // English to Chinese, Chinese to English, otherwise English
+                json!([
+                    { "source": "en", "target": "zh-Hans" },
+                    { "source": "zh", "target": "en" },
+                    { "source": "*", "target": "en" },
+                ]),
+                [
+                    (ENGLISH, Some("zh-Hans")),
+                    (CHINESE, Some("en")),
+                    (TRADITIONAL_CHINESE, Some("en")),
+                    (FRENCH, Some("en")),
+                    (JAPANESE, Some("en")),
+                    ("12345", Some("en")),
+                ],
+            ),
+            (
+                // A rule without a source is a "*" rule too
+                json!([
+                    { "source": "fr", "target": "de" },
+                    { "target": "ja" },
+                ]),
+                [
+                    (ENGLISH, Some("ja")),
+                    (CHINESE, Some("ja")),
+                    (TRADITIONAL_CHINESE, Some("ja")),
+                    (FRENCH, Some("de")),
+                    (JAPANESE, None),
+                    ("12345", Some("ja")),
+                ],
+            ),
+            (
+                // Targets in the language of the text are skipped, whatever
+                // their script or region
+                json!([
+                    { "source": "*", "target": "en-GB" },
+                    { "source": "*", "target": "zh-Hant" },
+                    { "source": "*", "target": "fr" },
+                ]),
+                [
+                    (ENGLISH, Some("zh-Hant")),
+                    (CHINESE, Some("en-GB")),
+                    (TRADITIONAL_CHINESE, Some("en-GB")),
+                    (FRENCH, Some("en-GB")),
+                    (JAPANESE, Some("en-GB")),
+                    ("12345", Some("en-GB")),
+                ],
+            ),
+            (
+                // No "*" rule: languages without a rule keep the default
+                json!([{ "source": "en", "target": "en-US" }]),
+                [
+                    (ENGLISH, None),
+                    (CHINESE, None),
+                    (TRADITIONAL_CHINESE, None),
+                    (FRENCH, None),
+                    (JAPANESE, None),
+                    ("12345", None),
+                ],
+            ),
+        ];
+        for (rules, cases) in tables {
+            let config = config(rules.clone());
+            for (text, target) in cases {
+                assert_eq!(
+                    route_target_language_with(&config, text).as_deref(),
+                    target,
+                    "{} {}",
+                    rules,
+                    text
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn no_rules_keep_the_default() {
+        let empty: Config = serde_json::from_value(json!({})).unwrap();
+        assert_eq!(route_target_language_with(&empty, ENGLISH), None);
+        assert_eq!(
+            route_target_language_with(&config(json!([])), ENGLISH),
+            None
+        );
+    }
+
+    #[test]
+    fn detection_is_limited_to_the_configured_languages() {
+        let config: Config = serde_json::from_value(json!({
+            "detectLanguages": ["de", "ja"],
+            "targetLanguageRules": [
+                { "source": "de", "target": "en" },
+                { "source": "*", "target": "de" },
+            ],
+        }))
+        .unwrap();
+        // German is the closest of the allowed languages
+        assert_eq!(
+            route_target_language_with(&config, "Das ist ein kleiner Hund im Garten.").as_deref(),
+            Some("en")
+        );
+        assert_eq!(
+            route_target_language_with(&config, FRENCH).as_deref(),
+            Some("en")
+        );
+        assert_eq!(
+            route_target_language_with(&config, CHINESE).as_deref(),
+            Some("de")
+        );
+    }
+
+    #[test]
+    fn rules_are_validated() {
+        let rule = |source: Option<&str>, target: &str| TargetLanguageRule {
+            source: source.map(|source| source.to_string()),
+            target: target.to_string(),
+        };
+        assert!(rule(Some("en"), "zh-Hans").validate().is_ok());
+        assert!(rule(Some("*"), "zh-Hant").validate().is_ok());
+        assert!(rule(None, "jpn").validate().is_ok());
+        assert!(rule(Some("english"), "en").validate().is_err());
+        assert!(rule(Some("en"), "xx").validate().is_err());
+    }
+}
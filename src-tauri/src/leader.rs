@@ -0,0 +1,179 @@
+use debug_print::debug_println;
+use parking_lot::Mutex;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::{thread, time::Duration};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut};
+use tauri_plugin_notification::NotificationExt;
+
+use crate::hotkeys::{dispatch, HotkeyAction};
+use crate::APP_HANDLE;
+
+const DEFAULT_LEADER_TIMEOUT_MS: u64 = 1500;
+
+// The keys that pick an action in leader mode. They are only registered as
+// global shortcuts while the mode is armed, so they reach the focused app the
+// rest of the time. An rdev listener isn't used for this: rdev::listen only
+// observes keys, so the picked key would also be typed into the focused app,
+// and rdev::grab needs the unstable_grab feature and, on Linux, read access to
+// /dev/input. A global shortcut swallows the key wherever hotkeys work.
+const LEADER_KEYS: [&str; 13] = [
+    "T", "O", "W", "P", "1", "2", "3", "4", "5", "6", "7", "8", "9",
+];
+
+struct LeaderShortcut {
+    shortcut: Shortcut,
+    action: HotkeyAction,
+}
+
+static LEADER_SHORTCUTS: Mutex<Vec<LeaderShortcut>> = Mutex::new(Vec::new());
+// The leader keys that couldn't be registered the last time the mode was
+// armed, usually because another app has them as global shortcuts
+static UNAVAILABLE_KEYS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+// Held while the leader keys are registered or unregistered, so that arming
+// and disarming from different threads don't interleave
+static SWITCHING: Mutex<()> = Mutex::new(());
+// Bumped on every arm and disarm; a timeout only disarms the leader mode it
+// was started for.
+static LEADER_GENERATION: AtomicUsize = AtomicUsize::new(0);
+
+fn key_action(key: &str) -> Option<HotkeyAction> {
+    let action = match key {
+        "T" => HotkeyAction::ShowTranslatorWithSelectedText,
+        "O" => HotkeyAction::Ocr,
+        "W" => HotkeyAction::Writing {
+            action: None,
+            target_language: None,
+        },
+        "P" => HotkeyAction::Writing {
+            action: Some("polish".to_string()),
+            target_language: None,
+        },
+        _ => {
+            let index = key.parse::<usize>().ok().filter(|&n| n >= 1)? - 1;
+            HotkeyAction::ShowTranslatorWithAction(index)
+        }
+    };
+    Some(action)
+}
+
+// Starts the leader-key mode: the next of the leader keys picks the action.
+// The mode ends by itself after `leaderTimeoutMs`. Registering a shortcut
+// waits for the main thread, which may be running the shortcut handler that
+// called this, so it's done on a thread of its own.
+pub fn arm() {
+    thread::spawn(arm_leader_keys);
+}
+
+fn arm_leader_keys() {
+    let _switching = SWITCHING.lock();
+    disarm_leader_keys();
+    let app_handle = match APP_HANDLE.get() {
+        Some(app_handle) => app_handle,
+        None => return,
+    };
+    let timeout_ms = crate::config::get_config()
+        .ok()
+        .and_then(|config| config.leader_timeout_ms)
+        .unwrap_or(DEFAULT_LEADER_TIMEOUT_MS);
+    let global_shortcut = app_handle.global_shortcut();
+    let mut shortcuts = Vec::new();
+    let mut unavailable_keys = Vec::new();
+    for key in LEADER_KEYS {
+        let (shortcut, action) = match (Shortcut::from_str(key), key_action(key)) {
+            (Ok(shortcut), Some(action)) => (shortcut, action),
+            _ => continue,
+        };
+        match global_shortcut.register(shortcut) {
+            Ok(_) => shortcuts.push(LeaderShortcut { shortcut, action }),
+            Err(e) => {
+                eprintln!("failed to register the leader key {}: {}", key, e);
+                unavailable_keys.push(key.to_string());
+            }
+        }
+    }
+    if shortcuts.is_empty() {
+        eprintln!("leader mode is not armed: none of its keys could be registered");
+        app_handle
+            .notification()
+            .builder()
+            .title("OpenAI Translator")
+            .body("The leader keys are used as shortcuts by another app")
+            .show()
+            .unwrap_or_default();
+        return;
+    }
+    *UNAVAILABLE_KEYS.lock() = unavailable_keys;
+    *LEADER_SHORTCUTS.lock() = shortcuts;
+    let generation = LEADER_GENERATION.fetch_add(1, Ordering::AcqRel) + 1;
+    crate::windows::show_leader_hint_window();
+    thread::spawn(move || {
+        thread::sleep(Duration::from_millis(timeout_ms));
+        if LEADER_GENERATION.load(Ordering::Acquire) == generation {
+            debug_println!("leader mode timed out");
+            disarm();
+        }
+    });
+}
+
+fn disarm() {
+    let _switching = SWITCHING.lock();
+    disarm_leader_keys();
+}
+
+fn disarm_leader_keys() {
+    LEADER_GENERATION.fetch_add(1, Ordering::AcqRel);
+    let shortcuts: Vec<LeaderShortcut> = LEADER_SHORTCUTS.lock().drain(..).collect();
+    unregister(shortcuts);
+}
+
+// Unregisters the leader keys. They are taken out of the lock first, as
+// unregistering waits for the main thread, which may be handling a shortcut.
+fn unregister(shortcuts: Vec<LeaderShortcut>) {
+    if shortcuts.is_empty() {
+        return;
+    }
+    if let Some(app_handle) = APP_HANDLE.get() {
+        let global_shortcut = app_handle.global_shortcut();
+        for leader_shortcut in shortcuts {
+            if let Err(e) = global_shortcut.unregister(leader_shortcut.shortcut) {
+                eprintln!("failed to unregister a leader key: {}", e);
+            }
+        }
+    }
+    crate::windows::close_leader_hint_window();
+}
+
+// Called for the shortcuts that aren't configured hotkeys; runs the action of
+// an armed leader key. The leader keys are taken out right away, so a second
+// press doesn't run the action again, and unregistered off the shortcut
+// handler.
+pub fn handle_shortcut(shortcut: &Shortcut) {
+    let mut leader_shortcuts = LEADER_SHORTCUTS.lock();
+    let action = leader_shortcuts
+        .iter()
+        .find(|leader_shortcut| leader_shortcut.shortcut.id() == shortcut.id())
+        .map(|leader_shortcut| leader_shortcut.action.clone());
+    let action = match action {
+        Some(action) => action,
+        None => return,
+    };
+    let shortcuts: Vec<LeaderShortcut> = leader_shortcuts.drain(..).collect();
+    drop(leader_shortcuts);
+    // Stop the timeout from disarming a leader mode armed after this one
+    LEADER_GENERATION.fetch_add(1, Ordering::AcqRel);
+    thread::spawn(move || {
+        {
+            let _switching = SWITCHING.lock();
+            unregister(shortcuts);
+        }
+        debug_println!("leader action: {:?}", action);
+        dispatch(action);
+    });
+}
+
+// The leader keys the hint window shows as unavailable.
+#[tauri::command]
+pub fn get_unavailable_leader_keys() -> Vec<String> {
+    UNAVAILABLE_KEYS.lock().clone()
+}
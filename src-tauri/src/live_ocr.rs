@@ -0,0 +1,170 @@
+use debug_print::debug_println;
+use image::{imageops::FilterType, GrayImage};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::{thread, time::Duration};
+use tauri::Manager;
+
+use crate::ocr::{capture_region, find_screen, get_image_dir, recognize_with_preprocess};
+use crate::APP_HANDLE;
+
+const DEFAULT_INTERVAL_MS: u64 = 1000;
+const MIN_INTERVAL_MS: u64 = 200;
+const DEFAULT_CHANGE_THRESHOLD: f64 = 0.01;
+const DIFF_SAMPLE_WIDTH: u32 = 96;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LiveOcrRegion {
+    // Origin of the screen the region belongs to, as passed to `screenshot`
+    pub x: i32,
+    pub y: i32,
+    // Rectangle in logical pixels relative to the screen origin
+    pub left: f64,
+    pub top: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LiveOcrText {
+    pub text: String,
+    pub target_language: Option<String>,
+}
+
+// Bumped on every start and stop; a capture loop exits as soon as the value no
+// longer matches the one it was started with.
+static LIVE_OCR_GENERATION: AtomicUsize = AtomicUsize::new(0);
+
+// Mean absolute difference of two frames in the 0..1 range, computed on small
+// grayscale copies so that it is cheap and ignores subpixel noise.
+pub fn frame_difference(previous: &GrayImage, current: &GrayImage) -> f64 {
+    if previous.dimensions() != current.dimensions() {
+        return 1.0;
+    }
+    let (width, height) = previous.dimensions();
+    if width == 0 || height == 0 {
+        return 0.0;
+    }
+    let sample_width = width.min(DIFF_SAMPLE_WIDTH);
+    let sample_height = (height * sample_width / width).max(1);
+    let a = image::imageops::resize(previous, sample_width, sample_height, FilterType::Triangle);
+    let b = image::imageops::resize(current, sample_width, sample_height, FilterType::Triangle);
+    let sum: u64 = a
+        .pixels()
+        .zip(b.pixels())
+        .map(|(p, q)| (p.0[0] as i64 - q.0[0] as i64).unsigned_abs())
+        .sum();
+    sum as f64 / (sample_width as f64 * sample_height as f64 * 255.0)
+}
+
+pub fn normalize_text(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn run_live_ocr(region: LiveOcrRegion, interval: Duration, threshold: f64, generation: usize) {
+    let screen = match find_screen(region.x, region.y) {
+        Ok(screen) => screen,
+        Err(e) => {
+            eprintln!("live ocr: {}", e);
+            return;
+        }
+    };
+    let frame_file_path = match get_image_dir() {
+        Ok(dir) => dir.join("live.png"),
+        Err(e) => {
+            eprintln!("live ocr: {}", e);
+            return;
+        }
+    };
+    let mut previous_frame: Option<GrayImage> = None;
+    let mut previous_text = String::new();
+    while LIVE_OCR_GENERATION.load(Ordering::Acquire) == generation {
+        match capture_region(
+            &screen,
+            region.left,
+            region.top,
+            region.width,
+            region.height,
+        ) {
+            Ok(frame) => {
+                let gray = frame.to_luma8();
+                let changed = match &previous_frame {
+                    Some(previous) => frame_difference(previous, &gray) >= threshold,
+                    None => true,
+                };
+                if changed {
+                    previous_frame = Some(gray);
+                    let text = frame
+                        .save(&frame_file_path)
+                        .map_err(|e| e.to_string())
+                        .and_then(|_| recognize_with_preprocess(&frame_file_path))
+                        .map(|text| normalize_text(&text));
+                    match text {
+                        Ok(text) if !text.is_empty() && text != previous_text => {
+                            debug_println!("live ocr text: {:?}", text);
+                            previous_text = text.clone();
+                            if let Some(handle) = APP_HANDLE.get() {
+                                let target_language =
+                                    crate::lang_rules::route_target_language(&text);
+                                handle
+                                    .emit(
+                                        "live-ocr-text",
+                                        LiveOcrText {
+                                            text,
+                                            target_language,
+                                        },
+                                    )
+                                    .unwrap_or_default();
+                            }
+                        }
+                        Ok(_) => {}
+                        Err(e) => eprintln!("live ocr: {}", e),
+                    }
+                }
+            }
+            Err(e) => eprintln!("live ocr: {}", e),
+        }
+        thread::sleep(interval);
+    }
+    debug_println!("live ocr loop {} stopped", generation);
+}
+
+#[tauri::command]
+pub async fn start_live_ocr(region: LiveOcrRegion, interval_ms: Option<u64>) -> Result<(), String> {
+    if region.width <= 0.0 || region.height <= 0.0 {
+        return Err("Region is empty".to_string());
+    }
+    let config = crate::config::get_config().map_err(|e| e.to_string())?;
+    let interval_ms = interval_ms
+        .or(config.live_ocr_interval_ms)
+        .unwrap_or(DEFAULT_INTERVAL_MS)
+        .max(MIN_INTERVAL_MS);
+    let threshold = config
+        .live_ocr_change_threshold
+        .unwrap_or(DEFAULT_CHANGE_THRESHOLD);
+    let generation = LIVE_OCR_GENERATION.fetch_add(1, Ordering::AcqRel) + 1;
+
+    crate::windows::show_live_ocr_window(&region);
+
+    thread::spawn(move || {
+        run_live_ocr(
+            region,
+            Duration::from_millis(interval_ms),
+            threshold,
+            generation,
+        );
+    });
+    Ok(())
+}
+
+pub fn stop_live_ocr_loop() {
+    LIVE_OCR_GENERATION.fetch_add(1, Ordering::AcqRel);
+}
+
+#[tauri::command]
+pub fn stop_live_ocr() {
+    stop_live_ocr_loop();
+    crate::windows::close_live_ocr_window();
+}
@@ -3,10 +3,26 @@
     windows_subsystem = "windows"
 )]
 
+mod app_rules;
+mod chinese;
+mod clipboard;
+mod clipboard_watch;
 mod config;
+mod dictionary;
+mod edit_plan;
 mod fetch;
+mod gesture;
+mod history;
+mod hotkeys;
+mod input_writer;
+mod key_chord;
 mod lang;
+mod lang_rules;
+mod leader;
+mod live_ocr;
 mod ocr;
+mod preprocess;
+mod selection_watch;
 mod tray;
 mod utils;
 mod windows;
@@ -17,22 +33,36 @@ use parking_lot::Mutex;
 use serde_json::json;
 use std::env;
 use std::sync::atomic::{AtomicBool, Ordering};
-use sysinfo::{CpuExt, System, SystemExt};
 use tauri_plugin_aptabase::EventTracker;
 use tauri_plugin_autostart::MacosLauncher;
 use tauri_plugin_updater::UpdaterExt;
 use windows::get_translator_window;
 
-use crate::config::{clear_config_cache, get_config_content};
+use crate::chinese::{convert_chinese, detect_chinese_variant};
+use crate::config::{clear_config_cache, get_config_content, get_config_errors};
+use crate::dictionary::{lookup_word, reload_dictionaries};
 use crate::fetch::fetch_stream;
-use crate::lang::detect_lang;
-use crate::ocr::{cut_image, finish_ocr, ocr_command, screenshot};
+use crate::gesture::{ButtonAction, Gesture, GestureDetector, GestureEvent, GestureThresholds};
+use crate::history::{
+    delete_ocr_history_entry, get_ocr_history_entry, list_ocr_history, reopen_ocr_history_entry,
+    retranslate_ocr_history_entry, update_ocr_history_translation,
+};
+use crate::hotkeys::register_hotkeys_command;
+use crate::lang::{detect_lang, detect_lang_details_command};
+use crate::lang_rules::get_routed_target_language;
+use crate::leader::get_unavailable_leader_keys;
+use crate::live_ocr::{start_live_ocr, stop_live_ocr};
+use crate::ocr::{cut_image, finish_ocr, ocr_clipboard_image, ocr_command, ocr_file, screenshot};
+use crate::utils::get_input_backend_info;
 use crate::windows::{
-    get_translator_window_always_on_top, show_action_manager_window,
-    show_translator_window_command, show_translator_window_with_selected_text_command,
-    show_updater_window, TRANSLATOR_WIN_NAME,
+    close_thumb_command, get_translator_window_always_on_top, open_thumb_text_in_translator,
+    resize_thumb_popover, show_action_manager_window, show_translator_window_command,
+    show_translator_window_with_selected_text_command, show_updater_window, thumb_clicked,
+    LIVE_OCR_WIN_NAME, TRANSLATOR_WIN_NAME,
+};
+use crate::writing::{
+    cancel_writing, finish_writing, undo_writing, write_to_input, writing_command,
 };
-use crate::writing::{finish_writing, write_to_input, writing_command};
 
 use mouce::Mouse;
 use once_cell::sync::OnceCell;
@@ -43,11 +73,10 @@ use tiny_http::{Response as HttpResponse, Server};
 
 pub static APP_HANDLE: OnceCell<AppHandle> = OnceCell::new();
 pub static ALWAYS_ON_TOP: AtomicBool = AtomicBool::new(false);
-pub static CPU_VENDOR: Mutex<String> = Mutex::new(String::new());
 pub static SELECTED_TEXT: Mutex<String> = Mutex::new(String::new());
-pub static PREVIOUS_PRESS_TIME: Mutex<u128> = Mutex::new(0);
-pub static PREVIOUS_RELEASE_TIME: Mutex<u128> = Mutex::new(0);
-pub static PREVIOUS_RELEASE_POSITION: Mutex<(i32, i32)> = Mutex::new((0, 0));
+pub static GESTURE_DETECTOR: Mutex<GestureDetector> =
+    Mutex::new(GestureDetector::new(GestureThresholds::DEFAULT));
+pub static SHIFT_PRESSED: AtomicBool = AtomicBool::new(false);
 pub static RELEASE_THREAD_ID: Mutex<u32> = Mutex::new(0);
 
 #[derive(Clone, serde::Serialize)]
@@ -87,8 +116,25 @@ fn query_accessibility_permissions() -> bool {
 #[inline]
 fn launch_ipc_server(server: &Server) {
     for mut req in server.incoming_requests() {
-        let mut selected_text = String::new();
-        req.as_reader().read_to_string(&mut selected_text).unwrap();
+        let mut body = Vec::new();
+        req.as_reader().read_to_end(&mut body).unwrap();
+        let is_image = req.headers().iter().any(|header| {
+            header.field.equiv("Content-Type")
+                && (header.value.as_str().starts_with("image/")
+                    || header.value.as_str() == "application/pdf")
+        }) || ocr::has_upload_signature(&body);
+        if is_image {
+            let result = ocr::save_image_bytes(&body)
+                .and_then(|path| ocr::prepare_ocr_input(&path, None))
+                .and_then(|path| ocr::ocr_and_send(&path, None));
+            let response = match result {
+                Ok(_) => HttpResponse::from_string("ok"),
+                Err(e) => HttpResponse::from_string(e).with_status_code(400),
+            };
+            req.respond(response).unwrap();
+            continue;
+        }
+        let selected_text = String::from_utf8_lossy(&body).to_string();
         utils::send_text(selected_text);
         let window = windows::show_translator_window(false, true, false);
         window.set_focus().unwrap();
@@ -98,6 +144,48 @@ fn launch_ipc_server(server: &Server) {
     }
 }
 
+// Feeds a left button event to the gesture detector with the thresholds from
+// the config and returns the text selection gesture it completes.
+fn feed_gesture(action: ButtonAction, x: i32, y: i32) -> Option<Gesture> {
+    let thresholds = config::get_config()
+        .ok()
+        .and_then(|config| config.selection_gesture)
+        .map(|gesture_config| GestureThresholds::from_config(&gesture_config))
+        .unwrap_or(GestureThresholds::DEFAULT);
+    let time_ms = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+    let mut detector = GESTURE_DETECTOR.lock();
+    detector.set_thresholds(thresholds);
+    detector.feed(GestureEvent {
+        action,
+        time_ms,
+        x,
+        y,
+        shift: SHIFT_PRESSED.load(Ordering::Acquire),
+    })
+}
+
+// mouce only reports mouse events, so the state of Shift for shift+click is
+// tracked with a keyboard listener of its own.
+fn bind_shift_listener() {
+    std::thread::spawn(|| {
+        let result = rdev::listen(|event| match event.event_type {
+            rdev::EventType::KeyPress(rdev::Key::ShiftLeft | rdev::Key::ShiftRight) => {
+                SHIFT_PRESSED.store(true, Ordering::Release);
+            }
+            rdev::EventType::KeyRelease(rdev::Key::ShiftLeft | rdev::Key::ShiftRight) => {
+                SHIFT_PRESSED.store(false, Ordering::Release);
+            }
+            _ => {}
+        });
+        if let Err(e) = result {
+            eprintln!("failed to listen to the keyboard: {:?}", e);
+        }
+    });
+}
+
 fn bind_mouse_hook() {
     let mut mouse_manager = Mouse::new();
 
@@ -105,10 +193,9 @@ fn bind_mouse_hook() {
         return;
     }
 
+    bind_shift_listener();
+
     let hook_result = mouse_manager.hook(Box::new(|event| {
-        if cfg!(target_os = "linux") {
-            return;
-        }
         match event {
             mouce::common::MouseEvent::Press(mouce::common::MouseButton::Left) => {
                 let config = config::get_config().unwrap();
@@ -116,11 +203,8 @@ fn bind_mouse_hook() {
                 if !always_show_icons {
                     return;
                 }
-                let current_press_time = std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap()
-                    .as_millis();
-                *PREVIOUS_PRESS_TIME.lock() = current_press_time;
+                let (x, y): (i32, i32) = windows::get_mouse_location().unwrap();
+                feed_gesture(ButtonAction::Press, x, y);
             }
             mouce::common::MouseEvent::Release(mouce::common::MouseButton::Left) => {
                 let config = config::get_config().unwrap();
@@ -129,37 +213,8 @@ fn bind_mouse_hook() {
                     windows::delete_thumb();
                     return;
                 }
-                let current_release_time = std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap()
-                    .as_millis();
-                let mut is_text_selected_event = false;
                 let (x, y): (i32, i32) = windows::get_mouse_location().unwrap();
-                let (prev_release_x, prev_release_y) = { *PREVIOUS_RELEASE_POSITION.lock() };
-                {
-                    *PREVIOUS_RELEASE_POSITION.lock() = (x, y);
-                }
-                let mouse_distance =
-                    (((x - prev_release_x).pow(2) + (y - prev_release_y).pow(2)) as f64).sqrt();
-                let mut previous_press_time = 0;
-                let mut previous_release_time = 0;
-                {
-                    let previous_press_time_lock = PREVIOUS_PRESS_TIME.lock();
-                    let mut previous_release_time_lock = PREVIOUS_RELEASE_TIME.lock();
-                    previous_release_time = *previous_release_time_lock;
-                    *previous_release_time_lock = current_release_time;
-                    previous_press_time = *previous_press_time_lock;
-                }
-                let is_pressed = previous_release_time < previous_press_time;
-                let pressed_time = current_release_time - previous_press_time;
-                let is_double_click =
-                    current_release_time - previous_release_time < 700 && mouse_distance < 10.0;
-                if is_pressed && pressed_time > 300 && mouse_distance > 20.0 {
-                    is_text_selected_event = true;
-                }
-                if previous_release_time != 0 && is_double_click {
-                    is_text_selected_event = true;
-                }
+                let is_text_selected_event = feed_gesture(ButtonAction::Release, x, y).is_some();
                 let is_click_on_thumb = match APP_HANDLE.get() {
                     Some(handle) => match handle.get_window(windows::THUMB_WIN_NAME) {
                         Some(window) => match window.outer_position() {
@@ -173,7 +228,9 @@ fn bind_mouse_hook() {
                                             width: mut w,
                                             height: mut h,
                                         } = size.to_logical::<i32>(scale_factor);
-                                        if cfg!(target_os = "windows") {
+                                        if cfg!(target_os = "windows")
+                                            && !windows::is_thumb_expanded()
+                                        {
                                             w = (20.0 as f64 * scale_factor) as i32;
                                             h = (20.0 as f64 * scale_factor) as i32;
                                         }
@@ -186,7 +243,9 @@ fn bind_mouse_hook() {
                                             width: mut w,
                                             height: mut h,
                                         } = size;
-                                        if cfg!(target_os = "windows") {
+                                        if cfg!(target_os = "windows")
+                                            && !windows::is_thumb_expanded()
+                                        {
                                             w = (20.0 as f64 * scale_factor) as u32;
                                             h = (20.0 as f64 * scale_factor) as u32;
                                         }
@@ -209,14 +268,14 @@ fn bind_mouse_hook() {
                 };
                 // println!("is_text_selected_event: {}", is_text_selected_event);
                 // println!("is_click_on_thumb: {}", is_click_on_thumb);
+                if is_click_on_thumb && windows::is_thumb_expanded() {
+                    // Clicks inside the popover are handled by the popover
+                    return;
+                }
                 if !is_text_selected_event && !is_click_on_thumb {
                     windows::close_thumb();
                     // println!("not text selected event");
                     // println!("is_click_on_thumb: {}", is_click_on_thumb);
-                    // println!("mouse_distance: {}", mouse_distance);
-                    // println!("pressed_time: {}", pressed_time);
-                    // println!("released_time: {}", current_release_time - previous_release_time);
-                    // println!("is_double_click: {}", is_double_click);
                     return;
                 }
 
@@ -226,36 +285,10 @@ fn bind_mouse_hook() {
                         return;
                     }
                     std::thread::spawn(move || {
-                        let _lock = RELEASE_THREAD_ID.lock();
-                        let selected_text = utils::get_selected_text().unwrap_or_default();
-                        if !selected_text.is_empty() {
-                            {
-                                *SELECTED_TEXT.lock() = selected_text;
-                            }
-                            windows::show_thumb(x, y);
-                        } else {
-                            // println!("selected text is empty");
-                            windows::close_thumb();
-                        }
+                        windows::show_thumb_for_selection(x, y);
                     });
                 } else {
-                    windows::close_thumb();
-                    let selected_text = (*SELECTED_TEXT.lock()).to_string();
-                    if !selected_text.is_empty() {
-                        let window = windows::show_translator_window(false, true, false);
-                        utils::send_text(selected_text);
-                        if cfg!(target_os = "windows") {
-                            window.set_always_on_top(true).unwrap();
-                            let always_on_top = ALWAYS_ON_TOP.load(Ordering::Acquire);
-                            if !always_on_top {
-                                std::thread::spawn(move || {
-                                    window.set_always_on_top(false).unwrap();
-                                });
-                            }
-                        } else {
-                            window.set_focus().unwrap();
-                        }
-                    }
+                    windows::click_thumb();
                 }
             }
             _ => {}
@@ -275,13 +308,6 @@ fn bind_mouse_hook() {
 fn main() {
     let silently = env::args().any(|arg| arg == "--silently");
 
-    let mut sys = System::new();
-    sys.refresh_cpu(); // Refreshing CPU information.
-    if let Some(cpu) = sys.cpus().first() {
-        let vendor_id = cpu.vendor_id().to_string();
-        *CPU_VENDOR.lock() = vendor_id;
-    }
-
     let mut app = tauri::Builder::default()
         .plugin(
             tauri_plugin_aptabase::Builder::new("A-US-9856842764")
@@ -322,7 +348,12 @@ fn main() {
             let app_handle = app.handle();
             APP_HANDLE.get_or_init(|| app.handle().clone());
             tray::create_tray(&app_handle)?;
-            app_handle.plugin(tauri_plugin_global_shortcut::Builder::new().build())?;
+            app_handle.plugin(
+                tauri_plugin_global_shortcut::Builder::new()
+                    .with_handler(hotkeys::handle_shortcut)
+                    .build(),
+            )?;
+            hotkeys::register_hotkeys(&app_handle);
             app_handle.plugin(tauri_plugin_updater::Builder::new().build())?;
             if silently {
                 let window = get_translator_window(false, false, false);
@@ -397,6 +428,14 @@ fn main() {
             get_update_result,
             get_config_content,
             clear_config_cache,
+            get_config_errors,
+            register_hotkeys_command,
+            get_unavailable_leader_keys,
+            get_input_backend_info,
+            resize_thumb_popover,
+            close_thumb_command,
+            open_thumb_text_in_translator,
+            thumb_clicked,
             show_translator_window_command,
             show_translator_window_with_selected_text_command,
             show_action_manager_window,
@@ -406,10 +445,28 @@ fn main() {
             writing_command,
             write_to_input,
             finish_writing,
+            undo_writing,
+            cancel_writing,
             detect_lang,
+            detect_lang_details_command,
+            get_routed_target_language,
+            convert_chinese,
+            detect_chinese_variant,
+            lookup_word,
+            reload_dictionaries,
             cut_image,
             finish_ocr,
             screenshot,
+            ocr_file,
+            ocr_clipboard_image,
+            list_ocr_history,
+            get_ocr_history_entry,
+            update_ocr_history_translation,
+            delete_ocr_history_entry,
+            reopen_ocr_history_entry,
+            retranslate_ocr_history_entry,
+            start_live_ocr,
+            stop_live_ocr,
         ])
         .build(tauri::generate_context!())
         .expect("error while building tauri application");
@@ -432,6 +489,8 @@ fn main() {
         tauri::RunEvent::Ready => {
             app.track_event("app_started", None);
             bind_mouse_hook();
+            clipboard_watch::start_clipboard_watch();
+            selection_watch::start_selection_watch();
             let handle = app.clone();
             tauri::async_runtime::spawn(async move {
                 let mut builder = handle.updater_builder();
@@ -474,6 +533,10 @@ fn main() {
             event: tauri::WindowEvent::CloseRequested { api, .. },
             ..
         } => {
+            if label == LIVE_OCR_WIN_NAME {
+                live_ocr::stop_live_ocr_loop();
+                return;
+            }
             if label != TRANSLATOR_WIN_NAME {
                 return;
             }
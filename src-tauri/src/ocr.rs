@@ -1,64 +1,248 @@
+use crate::preprocess::preprocess_file;
 use debug_print::debug_println;
-#[cfg(target_os = "windows")]
-use std::path::Path;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 use tauri::path::BaseDirectory;
 use tauri::Manager;
 
-#[tauri::command(async)]
-pub fn cut_image(left: u32, top: u32, width: u32, height: u32) {
-    use image::GenericImage;
-    let app_handle = crate::APP_HANDLE.get().unwrap();
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ScreenshotMeta {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub scale_factor: f32,
+    pub physical_width: u32,
+    pub physical_height: u32,
+}
+
+impl ScreenshotMeta {
+    // The ratio between the captured image and the logical coordinates the
+    // frontend selects in. The display size is in physical pixels on some
+    // platforms (e.g. Windows, where the app is DPI aware) and in logical ones
+    // on others, so only the monitor's scale factor is reliable; the image size
+    // is the fallback when the scale factor is missing.
+    pub fn pixel_ratio(&self) -> f64 {
+        if self.scale_factor > 0.0 {
+            self.scale_factor as f64
+        } else if self.width > 0 && self.physical_width > 0 {
+            self.physical_width as f64 / self.width as f64
+        } else {
+            1.0
+        }
+    }
+}
+
+pub fn get_image_dir() -> Result<PathBuf, String> {
+    let app_handle = crate::APP_HANDLE
+        .get()
+        .ok_or_else(|| "App handle not initialized".to_string())?;
     let image_dir = app_handle
         .path()
         .resolve("ocr_images", BaseDirectory::AppCache)
-        .unwrap();
+        .map_err(|e| format!("failed to resolve ocr image dir: {}", e))?;
+    if !image_dir.exists() {
+        std::fs::create_dir_all(&image_dir)
+            .map_err(|e| format!("failed to create ocr image dir: {}", e))?;
+    }
+    Ok(image_dir)
+}
+
+fn read_screenshot_meta(image_dir: &Path) -> Option<ScreenshotMeta> {
+    let content = std::fs::read_to_string(image_dir.join("fullscreen.json")).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+// Maps a rectangle in logical screen coordinates to the physical pixels of an
+// image with the given size, clamping it to the image bounds.
+pub fn logical_to_physical_rect(
+    left: f64,
+    top: f64,
+    width: f64,
+    height: f64,
+    ratio: f64,
+    image_width: u32,
+    image_height: u32,
+) -> Option<(u32, u32, u32, u32)> {
+    let x1 = ((left * ratio).floor().max(0.0) as u32).min(image_width);
+    let y1 = ((top * ratio).floor().max(0.0) as u32).min(image_height);
+    let x2 = (((left + width) * ratio).ceil().max(0.0) as u32).min(image_width);
+    let y2 = (((top + height) * ratio).ceil().max(0.0) as u32).min(image_height);
+    if x2 <= x1 || y2 <= y1 {
+        return None;
+    }
+    Some((x1, y1, x2 - x1, y2 - y1))
+}
+
+#[tauri::command(async)]
+pub fn cut_image(left: f64, top: f64, width: f64, height: f64) -> Result<(), String> {
+    use image::GenericImageView;
+
+    let image_dir = get_image_dir()?;
     let image_file_path = image_dir.join("fullscreen.png");
     if !image_file_path.exists() {
-        return;
+        return Err("No screenshot found".to_string());
     }
-    let mut img = match image::open(&image_file_path) {
-        Ok(v) => v,
-        Err(e) => {
-            eprintln!("error: {}", e);
-            return;
-        }
-    };
-    let img2 = img.sub_image(left, top, width, height);
+    let img =
+        image::open(&image_file_path).map_err(|e| format!("failed to open screenshot: {}", e))?;
+    let ratio = read_screenshot_meta(&image_dir)
+        .map(|meta| meta.pixel_ratio())
+        .unwrap_or(1.0);
+    let (x, y, w, h) =
+        logical_to_physical_rect(left, top, width, height, ratio, img.width(), img.height())
+            .ok_or_else(|| "Selection is empty".to_string())?;
+    debug_println!("cut image: ratio {} rect {:?}", ratio, (x, y, w, h));
     let new_image_file_path = image_dir.join("cut.png");
-    match img2.to_image().save(&new_image_file_path) {
-        Ok(_) => {}
-        Err(e) => {
-            eprintln!("{:?}", e.to_string());
-            return;
-        }
-    }
+    img.view(x, y, w, h)
+        .to_image()
+        .save(&new_image_file_path)
+        .map_err(|e| format!("failed to save cut image: {}", e))
+}
+
+pub fn find_screen(x: i32, y: i32) -> Result<screenshots::Screen, String> {
+    use screenshots::Screen;
+
+    let screens = Screen::all().map_err(|e| format!("failed to list screens: {}", e))?;
+    let contains = |info: &screenshots::DisplayInfo, x: f64, y: f64| {
+        x >= info.x as f64
+            && x < info.x as f64 + info.width as f64
+            && y >= info.y as f64
+            && y < info.y as f64 + info.height as f64
+    };
+    // Prefer an exact origin match, then a screen containing the point, and
+    // finally a screen containing the point once scaled back to logical pixels.
+    let index = screens
+        .iter()
+        .position(|s| s.display_info.x == x && s.display_info.y == y)
+        .or_else(|| {
+            screens
+                .iter()
+                .position(|s| contains(&s.display_info, x as f64, y as f64))
+        })
+        .or_else(|| {
+            screens.iter().position(|s| {
+                let scale = s.display_info.scale_factor.max(1.0) as f64;
+                contains(&s.display_info, x as f64 / scale, y as f64 / scale)
+            })
+        })
+        .ok_or_else(|| format!("No screen found at ({}, {})", x, y))?;
+    Ok(screens.into_iter().nth(index).unwrap())
 }
 
 #[tauri::command]
-pub fn screenshot(x: i32, y: i32) {
-    use screenshots::{Compression, Screen};
-    use std::fs;
-
-    let screens = Screen::all().unwrap();
-    for screen in screens {
-        let info = screen.display_info;
-        if info.x == x && info.y == y {
-            let app_handle = crate::APP_HANDLE.get().unwrap();
-            let image_dir = app_handle
-                .path()
-                .resolve("ocr_images", BaseDirectory::AppCache)
-                .unwrap();
-            if !image_dir.exists() {
-                std::fs::create_dir_all(&image_dir).unwrap();
-            }
-            let image_file_path = image_dir.join("fullscreen.png");
-            let image = screen.capture().unwrap();
-            let buffer = image.to_png(Compression::Fast).unwrap();
-            debug_println!("image_file_path: {:?}", image_file_path);
-            fs::write(image_file_path, buffer).unwrap();
-            break;
-        }
-    }
+pub fn screenshot(x: i32, y: i32) -> Result<ScreenshotMeta, String> {
+    use screenshots::Compression;
+
+    let screen = find_screen(x, y)?;
+    let info = screen.display_info;
+    let image_dir = get_image_dir()?;
+    let image_file_path = image_dir.join("fullscreen.png");
+    let image = screen
+        .capture()
+        .map_err(|e| format!("failed to capture screen: {}", e))?;
+    let meta = ScreenshotMeta {
+        x: info.x,
+        y: info.y,
+        width: info.width,
+        height: info.height,
+        scale_factor: info.scale_factor,
+        physical_width: image.width(),
+        physical_height: image.height(),
+    };
+    let buffer = image
+        .to_png(Compression::Fast)
+        .map_err(|e| format!("failed to encode screenshot: {}", e))?;
+    debug_println!("image_file_path: {:?}", image_file_path);
+    std::fs::write(&image_file_path, buffer)
+        .map_err(|e| format!("failed to write screenshot: {}", e))?;
+    let meta_content = serde_json::to_string(&meta).map_err(|e| e.to_string())?;
+    std::fs::write(image_dir.join("fullscreen.json"), meta_content)
+        .map_err(|e| format!("failed to write screenshot metadata: {}", e))?;
+    Ok(meta)
+}
+
+// Captures the given screen and crops it to a rectangle in logical coordinates
+// relative to the screen origin.
+pub fn capture_region(
+    screen: &screenshots::Screen,
+    left: f64,
+    top: f64,
+    width: f64,
+    height: f64,
+) -> Result<image::DynamicImage, String> {
+    use image::GenericImageView;
+    use screenshots::Compression;
+
+    let info = screen.display_info;
+    let buffer = screen
+        .capture()
+        .and_then(|image| image.to_png(Compression::Fast))
+        .map_err(|e| format!("failed to capture screen: {}", e))?;
+    let img = image::load_from_memory(&buffer)
+        .map_err(|e| format!("failed to decode screenshot: {}", e))?;
+    let meta = ScreenshotMeta {
+        x: info.x,
+        y: info.y,
+        width: info.width,
+        height: info.height,
+        scale_factor: info.scale_factor,
+        physical_width: img.width(),
+        physical_height: img.height(),
+    };
+    let (x, y, w, h) = logical_to_physical_rect(
+        left,
+        top,
+        width,
+        height,
+        meta.pixel_ratio(),
+        img.width(),
+        img.height(),
+    )
+    .ok_or_else(|| "Region is empty".to_string())?;
+    Ok(image::DynamicImage::ImageRgba8(
+        img.view(x, y, w, h).to_image(),
+    ))
+}
+
+const DEFAULT_OCR_LANGUAGES: [&str; 6] = ["eng", "chi_sim", "chi_tra", "jpn", "rus", "kor"];
+
+// The languages to recognize, as tesseract codes
+pub fn get_ocr_languages() -> Vec<String> {
+    crate::config::get_config()
+        .ok()
+        .and_then(|config| config.ocr_languages)
+        .filter(|languages| !languages.is_empty())
+        .unwrap_or_else(|| {
+            DEFAULT_OCR_LANGUAGES
+                .iter()
+                .map(|language| language.to_string())
+                .collect()
+        })
+}
+
+// The BCP-47 code Vision uses for a tesseract language
+#[cfg(target_os = "macos")]
+fn vision_language(language: &str) -> Option<&'static str> {
+    let language = match language {
+        "eng" => "en-US",
+        "chi_sim" => "zh-Hans",
+        "chi_tra" => "zh-Hant",
+        "jpn" => "ja-JP",
+        "kor" => "ko-KR",
+        "rus" => "ru-RU",
+        "ukr" => "uk-UA",
+        "fra" => "fr-FR",
+        "deu" => "de-DE",
+        "spa" => "es-ES",
+        "ita" => "it-IT",
+        "por" => "pt-BR",
+        "vie" => "vi-VT",
+        "tha" => "th-TH",
+        "ara" => "ar-SA",
+        _ => return None,
+    };
+    Some(language)
 }
 
 #[cfg(target_os = "linux")]
@@ -74,9 +258,8 @@ pub fn do_ocr() -> Result<(), Box<dyn std::error::Error>> {
 }
 
 #[cfg(target_os = "windows")]
-pub fn do_ocr_with_cut_file_path(image_file_path: &Path) {
+pub fn recognize_image_file(image_file_path: &Path) -> Result<String, String> {
     use windows::core::HSTRING;
-    use windows::Globalization::Language;
     use windows::Graphics::Imaging::BitmapDecoder;
     use windows::Media::Ocr::OcrEngine;
     use windows::Storage::{FileAccessMode, StorageFile};
@@ -85,78 +268,293 @@ pub fn do_ocr_with_cut_file_path(image_file_path: &Path) {
     debug_println!("ocr image file path: {:?}", path);
 
     let file = StorageFile::GetFileFromPathAsync(&HSTRING::from(path))
-        .unwrap()
-        .get()
-        .unwrap();
+        .and_then(|op| op.get())
+        .map_err(|e| e.to_string())?;
 
-    let bitmap = BitmapDecoder::CreateWithIdAsync(
-        BitmapDecoder::PngDecoderId().unwrap(),
-        &file.OpenAsync(FileAccessMode::Read).unwrap().get().unwrap(),
-    )
-    .unwrap()
-    .get()
-    .unwrap();
+    let stream = file
+        .OpenAsync(FileAccessMode::Read)
+        .and_then(|op| op.get())
+        .map_err(|e| e.to_string())?;
 
-    let bitmap = bitmap.GetSoftwareBitmapAsync().unwrap().get().unwrap();
+    let bitmap = BitmapDecoder::PngDecoderId()
+        .and_then(|decoder_id| BitmapDecoder::CreateWithIdAsync(decoder_id, &stream))
+        .and_then(|op| op.get())
+        .and_then(|decoder| decoder.GetSoftwareBitmapAsync())
+        .and_then(|op| op.get())
+        .map_err(|e| e.to_string())?;
 
-    let engine = OcrEngine::TryCreateFromUserProfileLanguages();
+    let engine = OcrEngine::TryCreateFromUserProfileLanguages().map_err(|e| {
+        debug_println!("ocr error: {:?}", e);
+        if e.to_string().contains("0x00000000") {
+            "Language package not installed!\n\nSee: https://learn.microsoft.com/zh-cn/windows/powertoys/text-extractor#supported-languages".to_string()
+        } else {
+            e.to_string()
+        }
+    })?;
 
-    match engine {
-        Ok(engine) => {
-            let result = engine.RecognizeAsync(&bitmap).unwrap().get().unwrap();
+    let result = engine
+        .RecognizeAsync(&bitmap)
+        .and_then(|op| op.get())
+        .map_err(|e| e.to_string())?;
 
-            let mut content = String::new();
-            for line in result.Lines().unwrap() {
-                content.push_str(&line.Text().unwrap().to_string_lossy().trim());
-                content.push('\n');
-            }
+    let mut content = String::new();
+    for line in result.Lines().map_err(|e| e.to_string())? {
+        content.push_str(
+            line.Text()
+                .map_err(|e| e.to_string())?
+                .to_string_lossy()
+                .trim(),
+        );
+        content.push('\n');
+    }
+
+    debug_println!("ocr content: {:?}", content);
+    Ok(content)
+}
 
-            debug_println!("ocr content: {:?}", content);
-            crate::utils::send_text(content);
-            crate::windows::show_translator_window(false, true, true);
+#[cfg(target_os = "macos")]
+#[link(name = "Vision", kind = "framework")]
+extern "C" {}
+
+#[cfg(target_os = "macos")]
+pub fn recognize_image_file(image_file_path: &Path) -> Result<String, String> {
+    use cocoa::base::{id, nil, YES};
+    use cocoa::foundation::{NSArray, NSDictionary, NSString};
+    use objc::{class, msg_send, sel, sel_impl};
+
+    let path = image_file_path.to_string_lossy();
+    debug_println!("ocr image file path: {:?}", path);
+
+    unsafe {
+        let ns_path = NSString::alloc(nil).init_str(&path);
+        let url: id = msg_send![class!(NSURL), fileURLWithPath: ns_path];
+        let handler: id = msg_send![class!(VNImageRequestHandler), alloc];
+        let handler: id =
+            msg_send![handler, initWithURL: url options: NSDictionary::dictionary(nil)];
+        let request: id = msg_send![class!(VNRecognizeTextRequest), new];
+        // VNRequestTextRecognitionLevelAccurate
+        let _: () = msg_send![request, setRecognitionLevel: 0i64];
+        let _: () = msg_send![request, setUsesLanguageCorrection: YES];
+        // Vision fails on languages it doesn't support, which depend on the
+        // macOS version, so only the supported ones are asked for
+        let supported_selector = sel!(supportedRecognitionLanguagesAndReturnError:);
+        let can_list: bool = msg_send![request, respondsToSelector: supported_selector];
+        if can_list {
+            let mut list_error: id = nil;
+            let supported: id =
+                msg_send![request, supportedRecognitionLanguagesAndReturnError: &mut list_error];
+            let mut languages: Vec<id> = Vec::new();
+            for language in get_ocr_languages()
+                .iter()
+                .filter_map(|language| vision_language(language))
+            {
+                let ns_language = NSString::alloc(nil).init_str(language);
+                let is_supported: bool =
+                    supported != nil && msg_send![supported, containsObject: ns_language];
+                if is_supported {
+                    languages.push(ns_language);
+                }
+            }
+            if !languages.is_empty() {
+                let languages = NSArray::arrayWithObjects(nil, &languages);
+                let _: () = msg_send![request, setRecognitionLanguages: languages];
+            }
+        }
+        let requests = NSArray::arrayWithObject(nil, request);
+        let mut error: id = nil;
+        let ok: bool = msg_send![handler, performRequests: requests error: &mut error];
+        if !ok {
+            return Err("Vision text recognition failed".to_string());
         }
-        Err(e) => {
-            debug_println!("ocr error: {:?}", e);
-            if e.to_string().contains("0x00000000") {
-                eprintln!("{}", "Language package not installed!\n\nSee: https://learn.microsoft.com/zh-cn/windows/powertoys/text-extractor#supported-languages".to_string());
-            } else {
-                eprintln!("{}", e.to_string());
+        let results: id = msg_send![request, results];
+        let mut content = String::new();
+        if results != nil {
+            for i in 0..results.count() {
+                let observation = results.objectAtIndex(i);
+                let candidates: id = msg_send![observation, topCandidates: 1u64];
+                if candidates == nil || candidates.count() == 0 {
+                    continue;
+                }
+                let candidate = candidates.objectAtIndex(0);
+                let text: id = msg_send![candidate, string];
+                let text = std::ffi::CStr::from_ptr(text.UTF8String());
+                content.push_str(text.to_string_lossy().trim());
+                content.push('\n');
             }
         }
+        debug_println!("ocr content: {:?}", content);
+        Ok(content)
     }
 }
 
-#[cfg(target_os = "macos")]
-pub fn do_ocr() -> Result<(), Box<dyn std::error::Error>> {
-    use crate::{APP_HANDLE, CPU_VENDOR};
+// Linux has no OCR engine of its own, so the system tesseract is used
+#[cfg(target_os = "linux")]
+pub fn recognize_image_file(image_file_path: &Path) -> Result<String, String> {
+    debug_println!("ocr image file path: {:?}", image_file_path);
 
-    let mut rel_path = "resources/bin/ocr_intel".to_string();
-    if *CPU_VENDOR.lock() == "Apple" {
-        rel_path = "resources/bin/ocr_apple".to_string();
-    }
+    let output = std::process::Command::new("tesseract")
+        .arg(image_file_path)
+        .arg("stdout")
+        .arg("-l")
+        .arg(get_ocr_languages().join("+"))
+        .output()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => {
+                "tesseract is not installed; install tesseract-ocr to recognize text".to_string()
+            }
+            _ => format!("failed to execute tesseract: {}", e),
+        })?;
 
-    let app = APP_HANDLE.get().unwrap();
+    if output.status.success() {
+        String::from_utf8(output.stdout).map_err(|e| e.to_string())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains("Failed loading language") {
+            return Err(format!(
+                "tesseract has no data for some of {}; install it or change ocrLanguages",
+                get_ocr_languages().join(", ")
+            ));
+        }
+        Err(stderr.to_string())
+    }
+}
 
-    let bin_path = app
-        .path()
-        .resolve(rel_path, BaseDirectory::Resource)
-        .expect("failed to resolve ocr binary resource");
+const SUPPORTED_IMAGE_EXTENSIONS: [&str; 6] = ["png", "jpg", "jpeg", "webp", "gif", "bmp"];
 
-    let output = std::process::Command::new(bin_path)
-        .args(["-l", "zh"])
+// Renders a single PDF page to PNG with poppler's pdftoppm, which is the only
+// PDF rasterizer we can reasonably expect to find on the user's machine.
+fn render_pdf_page(pdf_path: &Path, page: u32, output_prefix: &Path) -> Result<PathBuf, String> {
+    let page = page.max(1).to_string();
+    let output = std::process::Command::new("pdftoppm")
+        .args(["-png", "-r", "200", "-singlefile", "-f", &page, "-l", &page])
+        .arg(pdf_path)
+        .arg(output_prefix)
         .output()
-        .expect("failed to execute ocr binary");
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => {
+                "pdftoppm is not installed; install poppler-utils to recognize PDFs".to_string()
+            }
+            _ => format!("failed to execute pdftoppm: {}", e),
+        })?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).to_string());
+    }
+    Ok(output_prefix.with_extension("png"))
+}
 
-    // check exit code
-    if output.status.success() {
-        // get output content
-        let content = String::from_utf8(output.stdout).expect("failed to parse ocr binary output");
-        crate::utils::send_text(content);
-        crate::windows::show_translator_window(false, true, true);
-        Ok(())
+// Decodes any supported input and re-encodes it as `cut.png`, so every OCR
+// backend only ever has to deal with a PNG file.
+pub fn prepare_ocr_input(path: &Path, page: Option<u32>) -> Result<PathBuf, String> {
+    let image_dir = get_image_dir()?;
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let source = if extension == "pdf" {
+        render_pdf_page(path, page.unwrap_or(1), &image_dir.join("pdf_page"))?
+    } else if SUPPORTED_IMAGE_EXTENSIONS.contains(&extension.as_str()) || extension.is_empty() {
+        path.to_path_buf()
     } else {
-        Err("ocr binary failed".into())
+        return Err(format!("Unsupported file type: {}", extension));
+    };
+    let img = image::open(&source).map_err(|e| format!("failed to open image: {}", e))?;
+    let cut_file_path = image_dir.join("cut.png");
+    img.save(&cut_file_path)
+        .map_err(|e| format!("failed to save image: {}", e))?;
+    Ok(cut_file_path)
+}
+
+// Whether an IPC body without a Content-Type is an image or PDF to OCR. Only
+// formats with unambiguous signatures count: BMP or PNM headers are plain
+// text such as "BMW" or "P1".
+pub fn has_upload_signature(bytes: &[u8]) -> bool {
+    bytes.starts_with(b"\x89PNG\r\n\x1a\n")
+        || bytes.starts_with(b"\xff\xd8\xff")
+        || (bytes.len() >= 12 && bytes.starts_with(b"RIFF") && &bytes[8..12] == b"WEBP")
+        || bytes.starts_with(b"%PDF-")
+}
+
+pub fn save_image_bytes(bytes: &[u8]) -> Result<PathBuf, String> {
+    let image_dir = get_image_dir()?;
+    let extension = if bytes.starts_with(b"%PDF") {
+        "pdf"
+    } else {
+        let format =
+            image::guess_format(bytes).map_err(|e| format!("unknown image format: {}", e))?;
+        format.extensions_str().first().copied().unwrap_or("png")
+    };
+    let file_path = image_dir.join(format!("upload.{}", extension));
+    std::fs::write(&file_path, bytes).map_err(|e| format!("failed to save upload: {}", e))?;
+    Ok(file_path)
+}
+
+pub fn recognize_with_preprocess(image_file_path: &Path) -> Result<String, String> {
+    let preprocess_config = crate::config::get_config()
+        .ok()
+        .and_then(|config| config.ocr_preprocess)
+        .unwrap_or_default();
+    let processed_file_path = preprocess_file(image_file_path, &preprocess_config)?;
+    recognize_image_file(&processed_file_path)
+}
+
+pub fn ocr_and_send(image_file_path: &Path, source_app: Option<String>) -> Result<(), String> {
+    let content = recognize_with_preprocess(image_file_path)?;
+    if content.trim().is_empty() {
+        return Err("No text recognized".to_string());
     }
+    match crate::history::record(image_file_path, &content, source_app) {
+        Ok(entry) => crate::history::notify_recorded(&entry),
+        Err(e) => eprintln!("failed to record ocr history: {}", e),
+    }
+    crate::utils::send_text(content);
+    crate::windows::show_translator_window(false, true, true);
+    Ok(())
+}
+
+#[tauri::command(async)]
+pub fn ocr_file(path: String, page: Option<u32>) -> Result<(), String> {
+    let cut_file_path = prepare_ocr_input(Path::new(&path), page)?;
+    ocr_and_send(&cut_file_path, None)
+}
+
+#[tauri::command(async)]
+pub fn ocr_clipboard_image() -> Result<(), String> {
+    use arboard::Clipboard;
+
+    let image = Clipboard::new()
+        .and_then(|mut clipboard| clipboard.get_image())
+        .map_err(|e| format!("No image in clipboard: {}", e))?;
+    let buffer = image::RgbaImage::from_raw(
+        image.width as u32,
+        image.height as u32,
+        image.bytes.into_owned(),
+    )
+    .ok_or_else(|| "Invalid clipboard image".to_string())?;
+    let cut_file_path = get_image_dir()?.join("cut.png");
+    buffer
+        .save(&cut_file_path)
+        .map_err(|e| format!("failed to save clipboard image: {}", e))?;
+    ocr_and_send(&cut_file_path, None)
+}
+
+#[cfg(target_os = "macos")]
+pub fn do_ocr() -> Result<(), Box<dyn std::error::Error>> {
+    let image_file_path = get_image_dir()?.join("cut.png");
+    // screencapture leaves no file when the selection is cancelled, so an old
+    // capture must not be picked up instead
+    if image_file_path.exists() {
+        std::fs::remove_file(&image_file_path)?;
+    }
+    let status = std::process::Command::new("screencapture")
+        .args(["-i", "-x"])
+        .arg(&image_file_path)
+        .status()?;
+    if !status.success() || !image_file_path.exists() {
+        debug_println!("screen capture cancelled");
+        return Ok(());
+    }
+    ocr_and_send(&image_file_path, crate::history::take_source_app())?;
+    Ok(())
 }
 
 #[tauri::command(async)]
@@ -165,27 +563,73 @@ pub fn ocr_command() {
 }
 
 pub fn ocr() {
-    do_ocr().unwrap();
+    crate::history::remember_foreground_app();
+    if let Err(e) = do_ocr() {
+        eprintln!("ocr failed: {}", e);
+    }
 }
 
 #[tauri::command(async)]
-pub fn finish_ocr() {
-    do_finish_ocr();
+pub fn finish_ocr() -> Result<(), String> {
+    let image_file_path = get_image_dir()?.join("cut.png");
+    ocr_and_send(&image_file_path, crate::history::take_source_app())
 }
 
-#[cfg(target_os = "windows")]
-fn do_finish_ocr() {
-    let app_handle = crate::APP_HANDLE.get().unwrap();
-    let image_dir = app_handle
-        .path()
-        .resolve("ocr_images", BaseDirectory::AppCache)
-        .unwrap();
-    let image_file_path = image_dir.join("cut.png");
-    do_ocr_with_cut_file_path(&image_file_path);
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-#[cfg(target_os = "linux")]
-fn do_finish_ocr() {}
+    #[test]
+    fn logical_to_physical_rect_scales_by_one_and_a_half() {
+        assert_eq!(
+            logical_to_physical_rect(100.0, 50.0, 200.0, 100.0, 1.5, 3840, 2160),
+            Some((150, 75, 300, 150))
+        );
+        // Fractional edges grow outwards so that no selected pixel is lost
+        assert_eq!(
+            logical_to_physical_rect(10.5, 10.5, 10.0, 10.0, 1.5, 3840, 2160),
+            Some((15, 15, 16, 16))
+        );
+        // Clamped to the image
+        assert_eq!(
+            logical_to_physical_rect(2500.0, 1400.0, 200.0, 200.0, 1.5, 3840, 2160),
+            Some((3750, 2100, 90, 60))
+        );
+        assert_eq!(
+            logical_to_physical_rect(3000.0, 10.0, 10.0, 10.0, 1.5, 3840, 2160),
+            None
+        );
+    }
 
-#[cfg(target_os = "macos")]
-fn do_finish_ocr() {}
+    #[test]
+    fn pixel_ratio_uses_the_scale_factor() {
+        // Windows reports the display size in physical pixels
+        let meta = ScreenshotMeta {
+            x: 0,
+            y: 0,
+            width: 2880,
+            height: 1620,
+            scale_factor: 1.5,
+            physical_width: 2880,
+            physical_height: 1620,
+        };
+        assert_eq!(meta.pixel_ratio(), 1.5);
+        let meta = ScreenshotMeta {
+            scale_factor: 0.0,
+            width: 1440,
+            ..meta
+        };
+        assert_eq!(meta.pixel_ratio(), 2.0);
+    }
+
+    #[test]
+    fn upload_signature_ignores_text() {
+        assert!(has_upload_signature(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"));
+        assert!(has_upload_signature(b"\xff\xd8\xff\xe0\0\x10JFIF"));
+        assert!(has_upload_signature(b"RIFF\x24\0\0\0WEBPVP8 "));
+        assert!(has_upload_signature(b"%PDF-1.7"));
+        assert!(!has_upload_signature(b"BMW is a car maker"));
+        assert!(!has_upload_signature(b"P1 is the first priority"));
+        assert!(!has_upload_signature(b"RIFF"));
+    }
+}
@@ -0,0 +1,452 @@
+use debug_print::debug_println;
+use image::imageops::FilterType;
+use image::{DynamicImage, GrayImage, Luma};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PreprocessConfig {
+    pub enabled: Option<bool>,
+    pub upscale: Option<bool>,
+    pub upscale_min_height: Option<u32>,
+    pub grayscale: Option<bool>,
+    pub binarize: Option<bool>,
+    pub deskew: Option<bool>,
+    pub invert_dark: Option<bool>,
+    pub padding: Option<u32>,
+}
+
+impl Default for PreprocessConfig {
+    fn default() -> Self {
+        Self {
+            enabled: Some(true),
+            upscale: Some(true),
+            upscale_min_height: Some(300),
+            grayscale: Some(true),
+            binarize: Some(true),
+            deskew: Some(true),
+            invert_dark: Some(true),
+            padding: Some(16),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct PreprocessReport {
+    pub steps: Vec<String>,
+    pub scale: u32,
+    pub skew_angle: f32,
+    pub inverted: bool,
+}
+
+const MAX_UPSCALE: u32 = 4;
+const BINARIZE_WINDOW: u32 = 31;
+const BINARIZE_OFFSET: f64 = 10.0;
+const MAX_SKEW_DEGREES: f32 = 5.0;
+const SKEW_STEP_DEGREES: f32 = 0.5;
+
+pub fn mean_luminance(img: &GrayImage) -> f64 {
+    let count = (img.width() as u64 * img.height() as u64).max(1);
+    let sum: u64 = img.pixels().map(|p| p.0[0] as u64).sum();
+    sum as f64 / count as f64
+}
+
+// Dark-mode screenshots have light text on a dark background, which most OCR
+// engines handle badly. A low average luminance is a good enough signal.
+pub fn is_dark(img: &GrayImage) -> bool {
+    mean_luminance(img) < 110.0
+}
+
+pub fn upscale_factor(height: u32, min_height: u32) -> u32 {
+    if height == 0 || height >= min_height {
+        return 1;
+    }
+    ((min_height + height - 1) / height).min(MAX_UPSCALE)
+}
+
+// Mean-C adaptive threshold computed with an integral image, so that uneven
+// backgrounds (gradients, shadows, selections) do not swallow the text.
+pub fn adaptive_binarize(img: &GrayImage, window: u32, offset: f64) -> GrayImage {
+    let (width, height) = img.dimensions();
+    let stride = width as usize + 1;
+    let mut integral = vec![0u64; stride * (height as usize + 1)];
+    for y in 0..height as usize {
+        let mut row_sum = 0u64;
+        for x in 0..width as usize {
+            row_sum += img.get_pixel(x as u32, y as u32).0[0] as u64;
+            integral[(y + 1) * stride + x + 1] = integral[y * stride + x + 1] + row_sum;
+        }
+    }
+    let half = (window / 2) as i64;
+    GrayImage::from_fn(width, height, |x, y| {
+        let x1 = (x as i64 - half).max(0) as usize;
+        let y1 = (y as i64 - half).max(0) as usize;
+        let x2 = (x as i64 + half + 1).min(width as i64) as usize;
+        let y2 = (y as i64 + half + 1).min(height as i64) as usize;
+        let area = ((x2 - x1) * (y2 - y1)) as f64;
+        let sum = integral[y2 * stride + x2] + integral[y1 * stride + x1]
+            - integral[y1 * stride + x2]
+            - integral[y2 * stride + x1];
+        let threshold = sum as f64 / area - offset;
+        if (img.get_pixel(x, y).0[0] as f64) < threshold {
+            Luma([0])
+        } else {
+            Luma([255])
+        }
+    })
+}
+
+// Finds the rotation (in degrees) that makes the rows of dark pixels line up
+// best, by maximizing the variance of the horizontal projection profile.
+pub fn estimate_skew(binary: &GrayImage) -> f32 {
+    let dark: Vec<(f32, f32)> = binary
+        .enumerate_pixels()
+        .filter(|(_, _, p)| p.0[0] < 128)
+        .map(|(x, y, _)| (x as f32, y as f32))
+        .collect();
+    if dark.len() < 32 {
+        return 0.0;
+    }
+    let height = binary.height() as f32;
+    let width = binary.width() as f32;
+    let rows = (height + width) as usize + 1;
+    let mut best_angle = 0.0;
+    let mut best_score = f64::MIN;
+    let steps = (MAX_SKEW_DEGREES / SKEW_STEP_DEGREES) as i32;
+    for step in -steps..=steps {
+        let angle = step as f32 * SKEW_STEP_DEGREES;
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let mut histogram = vec![0u32; rows];
+        for (x, y) in &dark {
+            let row = (y * cos - x * sin + width) as usize;
+            if row < rows {
+                histogram[row] += 1;
+            }
+        }
+        let mean = dark.len() as f64 / rows as f64;
+        let score: f64 = histogram
+            .iter()
+            .map(|&count| (count as f64 - mean).powi(2))
+            .sum();
+        if score > best_score {
+            best_score = score;
+            best_angle = angle;
+        }
+    }
+    best_angle
+}
+
+// Rotates around the center with bilinear sampling; pixels that fall outside
+// the source are filled with the background color.
+pub fn rotate(img: &GrayImage, degrees: f32, background: u8) -> GrayImage {
+    let (width, height) = img.dimensions();
+    let (sin, cos) = (-degrees).to_radians().sin_cos();
+    let (cx, cy) = (width as f32 / 2.0, height as f32 / 2.0);
+    GrayImage::from_fn(width, height, |x, y| {
+        let dx = x as f32 - cx;
+        let dy = y as f32 - cy;
+        let sx = dx * cos - dy * sin + cx;
+        let sy = dx * sin + dy * cos + cy;
+        if sx < 0.0 || sy < 0.0 || sx > (width - 1) as f32 || sy > (height - 1) as f32 {
+            return Luma([background]);
+        }
+        let (x0, y0) = (sx.floor() as u32, sy.floor() as u32);
+        let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+        let (fx, fy) = (sx - x0 as f32, sy - y0 as f32);
+        let p = |x, y| img.get_pixel(x, y).0[0] as f32;
+        let top = p(x0, y0) * (1.0 - fx) + p(x1, y0) * fx;
+        let bottom = p(x0, y1) * (1.0 - fx) + p(x1, y1) * fx;
+        Luma([(top * (1.0 - fy) + bottom * fy).round() as u8])
+    })
+}
+
+pub fn pad(img: &GrayImage, padding: u32, background: u8) -> GrayImage {
+    let mut padded = GrayImage::from_pixel(
+        img.width() + padding * 2,
+        img.height() + padding * 2,
+        Luma([background]),
+    );
+    image::imageops::replace(&mut padded, img, padding as i64, padding as i64);
+    padded
+}
+
+pub fn preprocess(
+    img: &DynamicImage,
+    config: &PreprocessConfig,
+) -> (DynamicImage, PreprocessReport) {
+    let mut report = PreprocessReport {
+        scale: 1,
+        ..Default::default()
+    };
+    if !config.enabled.unwrap_or(true) {
+        return (img.clone(), report);
+    }
+
+    let needs_gray = config.grayscale.unwrap_or(true)
+        || config.binarize.unwrap_or(true)
+        || config.deskew.unwrap_or(true)
+        || config.invert_dark.unwrap_or(true);
+    if !needs_gray {
+        let mut img = img.clone();
+        if config.upscale.unwrap_or(true) {
+            let factor = upscale_factor(img.height(), config.upscale_min_height.unwrap_or(300));
+            if factor > 1 {
+                img = img.resize(
+                    img.width() * factor,
+                    img.height() * factor,
+                    FilterType::CatmullRom,
+                );
+                report.scale = factor;
+                report.steps.push("upscale".to_string());
+            }
+        }
+        return (img, report);
+    }
+
+    // The other steps work on gray pixels, so the conversion also happens when
+    // only they are on, but then it isn't a step of its own
+    let mut gray = img.to_luma8();
+    if config.grayscale.unwrap_or(true) {
+        report.steps.push("grayscale".to_string());
+    }
+
+    if config.invert_dark.unwrap_or(true) && is_dark(&gray) {
+        image::imageops::invert(&mut gray);
+        report.inverted = true;
+        report.steps.push("invert".to_string());
+    }
+
+    if config.upscale.unwrap_or(true) {
+        let factor = upscale_factor(gray.height(), config.upscale_min_height.unwrap_or(300));
+        if factor > 1 {
+            gray = image::imageops::resize(
+                &gray,
+                gray.width() * factor,
+                gray.height() * factor,
+                FilterType::CatmullRom,
+            );
+            report.scale = factor;
+            report.steps.push("upscale".to_string());
+        }
+    }
+
+    if config.binarize.unwrap_or(true) {
+        gray = adaptive_binarize(&gray, BINARIZE_WINDOW, BINARIZE_OFFSET);
+        report.steps.push("binarize".to_string());
+    }
+
+    if config.deskew.unwrap_or(true) {
+        let probe = if config.binarize.unwrap_or(true) {
+            gray.clone()
+        } else {
+            adaptive_binarize(&gray, BINARIZE_WINDOW, BINARIZE_OFFSET)
+        };
+        let angle = estimate_skew(&probe);
+        if angle != 0.0 {
+            gray = rotate(&gray, -angle, 255);
+            report.skew_angle = angle;
+            report.steps.push("deskew".to_string());
+        }
+    }
+
+    let padding = config.padding.unwrap_or(16);
+    if padding > 0 {
+        gray = pad(&gray, padding, 255);
+        report.steps.push("pad".to_string());
+    }
+
+    (DynamicImage::ImageLuma8(gray), report)
+}
+
+// Runs the pipeline on `path` and writes the result next to it, together with
+// a JSON report of the steps that ran.
+pub fn preprocess_file(path: &Path, config: &PreprocessConfig) -> Result<PathBuf, String> {
+    let img = image::open(path).map_err(|e| format!("failed to open image: {}", e))?;
+    let (processed, report) = preprocess(&img, config);
+    debug_println!("preprocess report: {:?}", report);
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let processed_path = path.with_file_name(format!("{}_processed.png", stem));
+    processed
+        .save(&processed_path)
+        .map_err(|e| format!("failed to save preprocessed image: {}", e))?;
+    let report_content = serde_json::to_string(&report).map_err(|e| e.to_string())?;
+    std::fs::write(processed_path.with_extension("json"), report_content)
+        .map_err(|e| format!("failed to write preprocess report: {}", e))?;
+    Ok(processed_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // White lines of text: dark bars 3px high every 20px, broken into words
+    fn text_lines(width: u32, height: u32) -> GrayImage {
+        GrayImage::from_fn(width, height, |x, y| {
+            let in_line = y % 20 >= 8 && y % 20 < 11;
+            let in_word = x % 40 < 32;
+            let in_margin = x < 20 || x >= width - 20 || y < 20 || y >= height - 20;
+            if in_line && in_word && !in_margin {
+                Luma([0])
+            } else {
+                Luma([255])
+            }
+        })
+    }
+
+    fn only(steps: &[&str]) -> PreprocessConfig {
+        let has = |step: &str| Some(steps.contains(&step));
+        PreprocessConfig {
+            enabled: Some(true),
+            upscale: has("upscale"),
+            upscale_min_height: Some(300),
+            grayscale: has("grayscale"),
+            binarize: has("binarize"),
+            deskew: has("deskew"),
+            invert_dark: has("invert"),
+            padding: Some(0),
+        }
+    }
+
+    #[test]
+    fn upscale_factor_reaches_the_min_height() {
+        assert_eq!(upscale_factor(100, 300), 3);
+        assert_eq!(upscale_factor(120, 300), 3);
+        assert_eq!(upscale_factor(50, 300), MAX_UPSCALE);
+        assert_eq!(upscale_factor(300, 300), 1);
+        assert_eq!(upscale_factor(0, 300), 1);
+    }
+
+    #[test]
+    fn upscale_small_image() {
+        let img = DynamicImage::ImageLuma8(text_lines(200, 100));
+        let (processed, report) = preprocess(&img, &only(&["upscale"]));
+        assert_eq!((processed.width(), processed.height()), (600, 300));
+        assert_eq!(report.scale, 3);
+        assert_eq!(report.steps, vec!["upscale"]);
+
+        let img = DynamicImage::ImageLuma8(text_lines(400, 400));
+        let (processed, report) = preprocess(&img, &only(&["upscale"]));
+        assert_eq!((processed.width(), processed.height()), (400, 400));
+        assert_eq!(report.scale, 1);
+        assert!(report.steps.is_empty());
+    }
+
+    #[test]
+    fn grayscale_is_only_reported_when_enabled() {
+        let img = DynamicImage::ImageLuma8(text_lines(400, 400));
+        let (_, report) = preprocess(&img, &only(&["grayscale"]));
+        assert_eq!(report.steps, vec!["grayscale"]);
+        let (_, report) = preprocess(&img, &only(&["binarize"]));
+        assert_eq!(report.steps, vec!["binarize"]);
+        let (processed, report) = preprocess(&img, &only(&[]));
+        assert!(report.steps.is_empty());
+        assert_eq!(processed.to_luma8(), text_lines(400, 400));
+    }
+
+    #[test]
+    fn invert_dark_screenshots() {
+        let mut dark = text_lines(400, 400);
+        image::imageops::invert(&mut dark);
+        let img = DynamicImage::ImageLuma8(dark);
+        let (processed, report) = preprocess(&img, &only(&["invert"]));
+        assert!(report.inverted);
+        assert_eq!(report.steps, vec!["invert"]);
+        assert_eq!(processed.to_luma8(), text_lines(400, 400));
+
+        // Light images are left alone
+        let img = DynamicImage::ImageLuma8(text_lines(400, 400));
+        let (processed, report) = preprocess(&img, &only(&["invert"]));
+        assert!(!report.inverted);
+        assert!(report.steps.is_empty());
+        assert_eq!(processed.to_luma8(), text_lines(400, 400));
+    }
+
+    #[test]
+    fn pad_with_the_background() {
+        let img = DynamicImage::ImageLuma8(text_lines(400, 400));
+        let config = PreprocessConfig {
+            padding: Some(16),
+            ..only(&["grayscale"])
+        };
+        let (processed, report) = preprocess(&img, &config);
+        assert_eq!(report.steps, vec!["grayscale", "pad"]);
+        let processed = processed.to_luma8();
+        assert_eq!(processed.dimensions(), (432, 432));
+        for (x, y, pixel) in processed.enumerate_pixels() {
+            let expected = if (16..416).contains(&x) && (16..416).contains(&y) {
+                text_lines(400, 400).get_pixel(x - 16, y - 16).0[0]
+            } else {
+                255
+            };
+            assert_eq!(pixel.0[0], expected, "pixel at ({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn upscale_before_binarizing() {
+        let img = DynamicImage::ImageLuma8(text_lines(200, 100));
+        let (processed, report) = preprocess(&img, &only(&["upscale", "binarize"]));
+        assert_eq!(report.steps, vec!["upscale", "binarize"]);
+        assert_eq!(report.scale, 3);
+        let processed = processed.to_luma8();
+        assert_eq!(processed.dimensions(), (600, 300));
+        // Binarized at the upscaled size: every pixel is black or white, and
+        // the lines are three times as high
+        assert!(processed.pixels().all(|p| p.0[0] == 0 || p.0[0] == 255));
+        let dark_rows: Vec<u32> = (0..300)
+            .filter(|&y| processed.get_pixel(75, y).0[0] == 0)
+            .collect();
+        assert_eq!(dark_rows.first(), Some(&84));
+        assert_eq!(dark_rows.len() % 9, 0);
+    }
+
+    #[test]
+    fn binarize_uneven_background() {
+        // A left-to-right gradient with text 80 levels darker than the
+        // background around it; no global threshold separates the two.
+        let background = |x: u32| 100 + (x * 150 / 299) as u8;
+        let is_text = |x: u32, y: u32| x % 16 < 3 && y % 16 < 3 && x >= 16 && y >= 16;
+        let img = GrayImage::from_fn(300, 120, |x, y| {
+            if is_text(x, y) {
+                Luma([background(x) - 80])
+            } else {
+                Luma([background(x)])
+            }
+        });
+        let binary = adaptive_binarize(&img, BINARIZE_WINDOW, BINARIZE_OFFSET);
+        for (x, y, pixel) in binary.enumerate_pixels() {
+            let expected = if is_text(x, y) { 0 } else { 255 };
+            assert_eq!(pixel.0[0], expected, "pixel at ({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn estimate_skew_of_rotated_lines() {
+        let lines = text_lines(400, 300);
+        assert_eq!(estimate_skew(&lines), 0.0);
+        for angle in [-3.0, -1.5, 2.0, 4.0] {
+            let skewed = rotate(&lines, angle, 255);
+            assert_eq!(estimate_skew(&skewed), angle, "skewed by {}", angle);
+        }
+        // Too few dark pixels to tell
+        assert_eq!(
+            estimate_skew(&GrayImage::from_pixel(50, 50, Luma([255]))),
+            0.0
+        );
+    }
+
+    #[test]
+    fn deskew_straightens_the_lines() {
+        let skewed = rotate(&text_lines(400, 300), 3.0, 255);
+        let img = DynamicImage::ImageLuma8(skewed);
+        let (processed, report) = preprocess(&img, &only(&["binarize", "deskew"]));
+        assert_eq!(report.skew_angle, 3.0);
+        assert_eq!(report.steps, vec!["binarize", "deskew"]);
+        assert_eq!(estimate_skew(&processed.to_luma8()), 0.0);
+    }
+}
@@ -0,0 +1,63 @@
+// The mouse hook only sees X11 apps in Wayland sessions, so there the thumb
+// follows the PRIMARY selection instead, which `wl-paste --watch` reports every
+// change of.
+#[cfg(target_os = "linux")]
+mod wayland {
+    use std::io::{BufRead, BufReader};
+    use std::process::{Command, Stdio};
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    // PRIMARY changes with every step of a drag; the thumb shows once the
+    // selection has stayed the same for this long.
+    const SETTLE_TIME: Duration = Duration::from_millis(300);
+
+    pub fn watch() {
+        let mut child = match Command::new("wl-paste")
+            .args(["--primary", "--watch", "echo"])
+            .stdout(Stdio::piped())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(e) => {
+                eprintln!("failed to watch the primary selection: {}", e);
+                return;
+            }
+        };
+        let stdout = child.stdout.take().unwrap();
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for _ in BufReader::new(stdout).lines() {
+                if sender.send(()).is_err() {
+                    break;
+                }
+            }
+        });
+        // Taken as seen, so that what was selected before isn't shown
+        let _ = crate::utils::get_selected_text_for_thumb();
+        while receiver.recv().is_ok() {
+            while receiver.recv_timeout(SETTLE_TIME).is_ok() {}
+            let always_show_icons = crate::config::get_config()
+                .ok()
+                .and_then(|config| config.always_show_icons)
+                .unwrap_or(true);
+            if !always_show_icons {
+                continue;
+            }
+            // The pointer position is only known over X11 windows, so the
+            // thumb may show where the pointer last was over one
+            let (x, y) = crate::windows::get_mouse_location().unwrap_or_default();
+            crate::windows::show_thumb_for_selection(x, y);
+        }
+        eprintln!("stopped watching the primary selection");
+        child.wait().ok();
+    }
+}
+
+pub fn start_selection_watch() {
+    #[cfg(target_os = "linux")]
+    if crate::utils::is_wayland() {
+        std::thread::spawn(wayland::watch);
+    }
+}
@@ -1,5 +1,7 @@
 use std::sync::atomic::{AtomicBool, Ordering};
 
+use crate::app_rules::disable_thumb_for_current_app;
+use crate::clipboard_watch::{is_clipboard_watch_enabled, set_clipboard_watch_enabled};
 use crate::config::get_config;
 use crate::ocr::ocr;
 use crate::windows::{
@@ -13,6 +15,7 @@ use tauri::{
     tray::ClickType,
     Manager, Runtime,
 };
+use tauri_plugin_notification::NotificationExt;
 
 pub static TRAY_EVENT_REGISTERED: AtomicBool = AtomicBool::new(false);
 
@@ -33,6 +36,20 @@ pub fn create_tray<R: Runtime>(app: &tauri::AppHandle<R>) -> tauri::Result<()> {
     let ocr_i = MenuItem::with_id(app, "ocr", ocr_text, true, None);
     let show_i = MenuItem::with_id(app, "show", "Show", true, None);
     let hide_i = MenuItem::with_id(app, "hide", "Hide", true, None);
+    let disable_thumb_i = MenuItem::with_id(
+        app,
+        "disable_thumb_for_current_app",
+        "Disable Thumb for Current App",
+        true,
+        None,
+    );
+    let clipboard_watch_i =
+        MenuItem::with_id(app, "clipboard_watch", "Watch Clipboard", true, None);
+    if is_clipboard_watch_enabled() {
+        clipboard_watch_i
+            .set_text("Stop Watching Clipboard")
+            .unwrap();
+    }
     let pin_i = MenuItem::with_id(app, "pin", "Pin", true, None);
     if ALWAYS_ON_TOP.load(Ordering::Acquire) {
         pin_i.set_text("Unpin").unwrap();
@@ -46,6 +63,8 @@ pub fn create_tray<R: Runtime>(app: &tauri::AppHandle<R>) -> tauri::Result<()> {
             &ocr_i,
             &show_i,
             &hide_i,
+            &disable_thumb_i,
+            &clipboard_watch_i,
             &pin_i,
             &quit_i,
         ],
@@ -79,6 +98,23 @@ pub fn create_tray<R: Runtime>(app: &tauri::AppHandle<R>) -> tauri::Result<()> {
             window.unminimize().unwrap();
             window.hide().unwrap();
         }
+        "disable_thumb_for_current_app" => match disable_thumb_for_current_app() {
+            Ok(app_name) => {
+                app.notification()
+                    .builder()
+                    .title("OpenAI Translator")
+                    .body(format!("The thumb is disabled for {}", app_name))
+                    .show()
+                    .unwrap_or_default();
+            }
+            Err(e) => eprintln!("failed to disable the thumb: {}", e),
+        },
+        "clipboard_watch" => {
+            if let Err(e) = set_clipboard_watch_enabled(!is_clipboard_watch_enabled()) {
+                eprintln!("failed to toggle the clipboard watch: {}", e);
+            }
+            create_tray(app).unwrap();
+        }
         "pin" => {
             set_translator_window_always_on_top();
             create_tray(app).unwrap();
@@ -1,11 +1,360 @@
 use enigo::*;
 use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
 use std::{thread, time::Duration};
 use tauri::path::BaseDirectory;
 use tauri::Manager;
 
+use crate::key_chord::{press_key_chord, KeyChord};
 use crate::APP_HANDLE;
 
+// How keystrokes are synthesized. enigo, rdev and mouce talk to X11 on Linux,
+// which does nothing for native Wayland apps, so Wayland sessions go through
+// ydotool (uinput) for input and wl-clipboard for the clipboard and PRIMARY.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InputBackend {
+    Native,
+    Wayland,
+}
+
+pub fn is_wayland() -> bool {
+    if !cfg!(target_os = "linux") {
+        return false;
+    }
+    match std::env::var("XDG_SESSION_TYPE") {
+        Ok(session_type) => session_type == "wayland",
+        Err(_) => std::env::var("WAYLAND_DISPLAY").is_ok(),
+    }
+}
+
+pub fn get_input_backend() -> InputBackend {
+    if is_wayland() {
+        InputBackend::Wayland
+    } else {
+        InputBackend::Native
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod wayland {
+    use enigo::Key;
+    use std::time::Duration;
+
+    use crate::clipboard::{ClipboardTransaction, SystemClipboard};
+    use crate::key_chord::KeyChord;
+
+    const KEY_LEFTSHIFT: u16 = 42;
+
+    // Linux input event codes for a US layout, as expected by `ydotool key`
+    fn layout_code(c: char) -> Option<(u16, bool)> {
+        const LETTERS: &str = "qwertyuiop";
+        const LETTERS_2: &str = "asdfghjkl";
+        const LETTERS_3: &str = "zxcvbnm";
+        let lower = c.to_ascii_lowercase();
+        let shifted = c.is_ascii_uppercase();
+        if let Some(i) = LETTERS.find(lower) {
+            return Some((16 + i as u16, shifted));
+        }
+        if let Some(i) = LETTERS_2.find(lower) {
+            return Some((30 + i as u16, shifted));
+        }
+        if let Some(i) = LETTERS_3.find(lower) {
+            return Some((44 + i as u16, shifted));
+        }
+        let code = match c {
+            '1'..='9' => c as u16 - '1' as u16 + 2,
+            '0' => 11,
+            '-' => 12,
+            '=' => 13,
+            '[' => 26,
+            ']' => 27,
+            ';' => 39,
+            '\'' => 40,
+            '`' => 41,
+            '\\' => 43,
+            ',' => 51,
+            '.' => 52,
+            '/' => 53,
+            ' ' => 57,
+            _ => return None,
+        };
+        Some((code, false))
+    }
+
+    fn key_code(key: Key) -> Option<(u16, bool)> {
+        let code = match key {
+            Key::Layout(c) => return layout_code(c),
+            Key::Control => 29,
+            Key::Shift => KEY_LEFTSHIFT,
+            Key::Alt => 56,
+            Key::Meta => 125,
+            Key::Escape => 1,
+            Key::Backspace => 14,
+            Key::Tab => 15,
+            Key::Return => 28,
+            Key::Space => 57,
+            Key::CapsLock => 58,
+            Key::F1 => 59,
+            Key::F2 => 60,
+            Key::F3 => 61,
+            Key::F4 => 62,
+            Key::F5 => 63,
+            Key::F6 => 64,
+            Key::F7 => 65,
+            Key::F8 => 66,
+            Key::F9 => 67,
+            Key::F10 => 68,
+            Key::F11 => 87,
+            Key::F12 => 88,
+            Key::Home => 102,
+            Key::UpArrow => 103,
+            Key::PageUp => 104,
+            Key::LeftArrow => 105,
+            Key::RightArrow => 106,
+            Key::End => 107,
+            Key::DownArrow => 108,
+            Key::PageDown => 109,
+            Key::Delete => 111,
+            _ => return None,
+        };
+        Some((code, false))
+    }
+
+    fn ydotool(args: &[String]) {
+        match std::process::Command::new("ydotool").args(args).output() {
+            Ok(output) if !output.status.success() => eprintln!(
+                "ydotool failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+            Ok(_) => {}
+            Err(e) => eprintln!("failed to run ydotool: {}", e),
+        }
+    }
+
+    fn press(code: u16) -> String {
+        format!("{}:1", code)
+    }
+
+    fn release(code: u16) -> String {
+        format!("{}:0", code)
+    }
+
+    pub fn key_click(key: Key, n: usize) {
+        let (code, shifted) = match key_code(key) {
+            Some(code) => code,
+            None => return eprintln!("no key code for {:?}", key),
+        };
+        let mut args = vec!["key".to_string()];
+        if shifted {
+            args.push(press(KEY_LEFTSHIFT));
+        }
+        for _ in 0..n {
+            args.push(press(code));
+            args.push(release(code));
+        }
+        if shifted {
+            args.push(release(KEY_LEFTSHIFT));
+        }
+        ydotool(&args);
+    }
+
+    pub fn key_chord(chord: &KeyChord) {
+        let mut codes = Vec::new();
+        for modifier in &chord.modifiers {
+            if let Some((code, _)) = key_code(*modifier) {
+                codes.push(code);
+            }
+        }
+        let mut args = vec!["key".to_string()];
+        args.extend(codes.iter().map(|code| press(*code)));
+        if let Some(key) = chord.key {
+            match key_code(key) {
+                Some((code, shifted)) => {
+                    let shift = shifted && !codes.contains(&KEY_LEFTSHIFT);
+                    if shift {
+                        args.push(press(KEY_LEFTSHIFT));
+                    }
+                    args.push(press(code));
+                    args.push(release(code));
+                    if shift {
+                        args.push(release(KEY_LEFTSHIFT));
+                    }
+                }
+                None => eprintln!("no key code for {:?}", key),
+            }
+        }
+        args.extend(codes.iter().rev().map(|code| release(*code)));
+        ydotool(&args);
+    }
+
+    pub fn release_keys(keys: &[Key]) {
+        let mut args = vec!["key".to_string()];
+        args.extend(
+            keys.iter()
+                .filter_map(|key| key_code(*key))
+                .map(|(code, _)| release(code)),
+        );
+        ydotool(&args);
+    }
+
+    fn type_ascii(text: &str) {
+        ydotool(&["type".to_string(), "--".to_string(), text.to_string()]);
+    }
+
+    // Splits the text where it switches between ASCII and everything else
+    fn split_ascii_runs(text: &str) -> Vec<&str> {
+        let mut runs = Vec::new();
+        let mut start = 0;
+        let mut was_ascii = None;
+        for (i, c) in text.char_indices() {
+            if was_ascii.map_or(false, |was_ascii| was_ascii != c.is_ascii()) {
+                runs.push(&text[start..i]);
+                start = i;
+            }
+            was_ascii = Some(c.is_ascii());
+        }
+        if start < text.len() {
+            runs.push(&text[start..]);
+        }
+        runs
+    }
+
+    // `ydotool type` only has the keys of a US layout, so everything else is
+    // pasted through the clipboard, which is put back afterwards.
+    pub fn key_sequence(text: &str) {
+        if text.is_ascii() {
+            return type_ascii(text);
+        }
+        let mut clipboard = SystemClipboard;
+        let mut transaction = match ClipboardTransaction::begin(&mut clipboard) {
+            Ok(transaction) => transaction,
+            Err(e) => return eprintln!("failed to borrow the clipboard to type: {}", e),
+        };
+        for run in split_ascii_runs(text) {
+            if run.is_ascii() {
+                type_ascii(run);
+                continue;
+            }
+            let paste = || {
+                key_chord(&KeyChord {
+                    modifiers: vec![Key::Control],
+                    key: Some(Key::Layout('v')),
+                })
+            };
+            if let Err(e) = transaction.paste_text(run, paste, Duration::from_millis(100)) {
+                eprintln!("failed to paste {:?}: {}", run, e);
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn split_ascii_runs_at_script_changes() {
+            assert_eq!(split_ascii_runs("Hello 世界!"), vec!["Hello ", "世界", "!"]);
+            assert_eq!(
+                split_ascii_runs("café au lait"),
+                vec!["caf", "é", " au lait"]
+            );
+            assert_eq!(split_ascii_runs("日本"), vec!["日本"]);
+            assert_eq!(split_ascii_runs(""), Vec::<&str>::new());
+        }
+    }
+}
+
+pub fn key_click(enigo: &mut Enigo, key: Key, n: usize) {
+    #[cfg(target_os = "linux")]
+    if is_wayland() {
+        return wayland::key_click(key, n);
+    }
+    for _ in 0..n {
+        enigo.key_click(key);
+    }
+}
+
+pub fn key_chord(enigo: &mut Enigo, chord: &KeyChord) {
+    #[cfg(target_os = "linux")]
+    if is_wayland() {
+        return wayland::key_chord(chord);
+    }
+    press_key_chord(enigo, chord);
+}
+
+pub fn key_sequence(enigo: &mut Enigo, text: &str) {
+    #[cfg(target_os = "linux")]
+    if is_wayland() {
+        return wayland::key_sequence(text);
+    }
+    enigo.key_sequence(text);
+}
+
+fn find_in_path(name: &str) -> bool {
+    std::env::var_os("PATH")
+        .map(|paths| std::env::split_paths(&paths).any(|dir| dir.join(name).is_file()))
+        .unwrap_or(false)
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct InputBackendTool {
+    pub name: String,
+    pub available: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct InputBackendInfo {
+    pub backend: InputBackend,
+    pub session_type: Option<String>,
+    pub tools: Vec<InputBackendTool>,
+    pub warnings: Vec<String>,
+}
+
+// Reports which input backend is active and whether the helpers it needs are
+// installed, so that "writing does nothing" can be diagnosed from the app.
+#[tauri::command]
+pub fn get_input_backend_info() -> InputBackendInfo {
+    let backend = get_input_backend();
+    let tool_names: &[&str] = match backend {
+        InputBackend::Wayland => &["ydotool", "wl-copy", "wl-paste", "python3"],
+        InputBackend::Native if cfg!(target_os = "linux") => &["xclip", "python3"],
+        InputBackend::Native => &[],
+    };
+    let tools = tool_names
+        .iter()
+        .map(|name| InputBackendTool {
+            name: name.to_string(),
+            available: find_in_path(name),
+        })
+        .collect::<Vec<_>>();
+    let mut warnings = tools
+        .iter()
+        .filter(|tool| !tool.available)
+        .map(|tool| format!("{} is not installed", tool.name))
+        .collect::<Vec<_>>();
+    if backend == InputBackend::Wayland {
+        let socket =
+            std::env::var("YDOTOOL_SOCKET").unwrap_or_else(|_| "/tmp/.ydotool_socket".to_string());
+        if !std::path::Path::new(&socket).exists() {
+            warnings.push(format!("ydotoold is not running ({} not found)", socket));
+        }
+        warnings.push(
+            "the mouse hook and global shortcuts only see events from X11 apps; the thumb \
+             follows the PRIMARY selection instead"
+                .to_string(),
+        );
+    }
+    InputBackendInfo {
+        backend,
+        session_type: std::env::var("XDG_SESSION_TYPE").ok(),
+        tools,
+        warnings,
+    }
+}
+
 static SELECT_ALL: Mutex<()> = Mutex::new(());
 
 #[allow(dead_code)]
@@ -47,9 +396,13 @@ pub fn select_all(enigo: &mut Enigo) {
 
     crate::utils::up_control_keys(enigo);
 
-    enigo.key_down(Key::Control);
-    enigo.key_click(Key::Layout('a'));
-    enigo.key_up(Key::Control);
+    key_chord(
+        enigo,
+        &KeyChord {
+            modifiers: vec![Key::Control],
+            key: Some(Key::Layout('a')),
+        },
+    );
 }
 
 pub static INPUT_LOCK: Mutex<()> = Mutex::new(());
@@ -58,9 +411,7 @@ pub static INPUT_LOCK: Mutex<()> = Mutex::new(());
 pub fn left_arrow_click(enigo: &mut Enigo, n: usize) {
     let _guard = INPUT_LOCK.lock();
 
-    for _ in 0..n {
-        enigo.key_click(Key::LeftArrow);
-    }
+    key_click(enigo, Key::LeftArrow, n);
 }
 
 #[cfg(target_os = "macos")]
@@ -87,9 +438,7 @@ pub fn left_arrow_click(enigo: &mut Enigo, n: usize) {
 pub fn right_arrow_click(enigo: &mut Enigo, n: usize) {
     let _guard = INPUT_LOCK.lock();
 
-    for _ in 0..n {
-        enigo.key_click(Key::RightArrow);
-    }
+    key_click(enigo, Key::RightArrow, n);
 }
 
 #[cfg(target_os = "macos")]
@@ -116,9 +465,7 @@ pub fn right_arrow_click(enigo: &mut Enigo, n: usize) {
 pub fn backspace_click(enigo: &mut Enigo, n: usize) {
     let _guard = INPUT_LOCK.lock();
 
-    for _ in 0..n {
-        enigo.key_click(Key::Backspace);
-    }
+    key_click(enigo, Key::Backspace, n);
 }
 
 #[cfg(target_os = "macos")]
@@ -166,6 +513,9 @@ pub fn up_control_keys(enigo: &mut Enigo) {
 #[allow(dead_code)]
 #[cfg(target_os = "linux")]
 pub fn up_control_keys(enigo: &mut Enigo) {
+    if is_wayland() {
+        return wayland::release_keys(&[Key::Control, Key::Alt, Key::Shift, Key::Space, Key::Tab]);
+    }
     enigo.key_up(Key::Control);
     enigo.key_up(Key::Alt);
     enigo.key_up(Key::Shift);
@@ -216,6 +566,12 @@ pub fn copy(enigo: &mut Enigo) {
 
     up_control_keys(enigo);
 
+    if is_wayland() {
+        return wayland::key_chord(&KeyChord {
+            modifiers: vec![Key::Control],
+            key: Some(Key::Layout('c')),
+        });
+    }
     enigo.key_down(Key::Control);
     thread::sleep(Duration::from_millis(50));
     enigo.key_click(Key::Layout('c'));
@@ -262,84 +618,142 @@ pub fn paste(enigo: &mut Enigo) {
 
     crate::utils::up_control_keys(enigo);
 
-    enigo.key_down(Key::Control);
-    enigo.key_click(Key::Layout('v'));
-    enigo.key_up(Key::Control);
+    key_chord(
+        enigo,
+        &KeyChord {
+            modifiers: vec![Key::Control],
+            key: Some(Key::Layout('v')),
+        },
+    );
 }
 
-#[cfg(not(target_os = "macos"))]
+#[cfg(target_os = "windows")]
 pub fn get_selected_text() -> Result<String, Box<dyn std::error::Error>> {
     let mut enigo = Enigo::new();
     get_selected_text_by_clipboard(&mut enigo, false)
 }
 
-pub fn get_selected_text_by_clipboard(
-    enigo: &mut Enigo,
-    cancel_select: bool,
-) -> Result<String, Box<dyn std::error::Error>> {
-    use arboard::Clipboard;
-
-    let old_clipboard = (Clipboard::new()?.get_text(), Clipboard::new()?.get_image());
-
-    let mut write_clipboard = Clipboard::new()?;
-
-    let not_selected_placeholder = "";
+// Simulating Ctrl+C is the last resort on Linux: terminals treat it as an
+// interrupt, and most apps put their selection on PRIMARY or expose it
+// through AT-SPI anyway.
+#[cfg(target_os = "linux")]
+pub fn get_selected_text() -> Result<String, Box<dyn std::error::Error>> {
+    if let Some(text) = get_selected_text_without_copy(false) {
+        return Ok(text);
+    }
+    let mut enigo = Enigo::new();
+    get_selected_text_by_clipboard(&mut enigo, false)
+}
 
-    write_clipboard.set_text(not_selected_placeholder)?;
+// The selected text for the thumb, which is looked up after every selection
+// gesture and so must not simulate a copy on Linux.
+#[cfg(target_os = "linux")]
+pub fn get_selected_text_for_thumb() -> Result<String, Box<dyn std::error::Error>> {
+    Ok(get_selected_text_without_copy(true).unwrap_or_default())
+}
 
-    thread::sleep(Duration::from_millis(50));
+#[cfg(not(target_os = "linux"))]
+pub fn get_selected_text_for_thumb() -> Result<String, Box<dyn std::error::Error>> {
+    get_selected_text()
+}
 
-    copy(enigo);
+#[cfg(target_os = "linux")]
+static LAST_PRIMARY_SELECTION: Mutex<String> = Mutex::new(String::new());
 
-    if cancel_select {
-        right_arrow_click(enigo, 1);
+// PRIMARY keeps its content after the selection is gone, so with
+// `require_new_primary` a PRIMARY value that was already seen only counts if
+// AT-SPI confirms there is a selection.
+#[cfg(target_os = "linux")]
+pub fn get_selected_text_without_copy(require_new_primary: bool) -> Option<String> {
+    let primary = match get_primary_selection() {
+        Ok(text) => text,
+        Err(err) => {
+            println!("get_primary_selection error: {}", err);
+            String::new()
+        }
+    };
+    let is_new_primary = {
+        let mut last_primary_selection = LAST_PRIMARY_SELECTION.lock();
+        let is_new_primary = *last_primary_selection != primary;
+        *last_primary_selection = primary.clone();
+        is_new_primary
+    };
+    if !primary.trim().is_empty() && (is_new_primary || !require_new_primary) {
+        return Some(primary);
+    }
+    match get_selected_text_by_atspi() {
+        Ok(text) if !text.trim().is_empty() => Some(text),
+        Ok(_) => None,
+        Err(err) => {
+            println!("get_selected_text_by_atspi error: {}", err);
+            None
+        }
     }
+}
 
-    thread::sleep(Duration::from_millis(100));
+#[cfg(target_os = "linux")]
+pub fn get_primary_selection() -> Result<String, Box<dyn std::error::Error>> {
+    if is_wayland() {
+        let output = std::process::Command::new("wl-paste")
+            .args(["--primary", "--no-newline", "--type", "text"])
+            .output()?;
+        // wl-paste fails when the selection is empty
+        if !output.status.success() {
+            return Ok(String::new());
+        }
+        return Ok(String::from_utf8_lossy(&output.stdout).to_string());
+    }
+    use arboard::{Clipboard, GetExtLinux, LinuxClipboardKind};
+    match Clipboard::new()?
+        .get()
+        .clipboard(LinuxClipboardKind::Primary)
+        .text()
+    {
+        Ok(text) => Ok(text),
+        Err(arboard::Error::ContentNotAvailable) => Ok(String::new()),
+        Err(err) => Err(Box::new(err)),
+    }
+}
 
-    let new_text = Clipboard::new()?.get_text();
+#[cfg(target_os = "linux")]
+pub fn get_selected_text_by_atspi() -> Result<String, Box<dyn std::error::Error>> {
+    let script = APP_HANDLE
+        .get()
+        .ok_or("App handle not initialized")?
+        .path()
+        .resolve(
+            "resources/atspi-get-selected-text.py",
+            BaseDirectory::Resource,
+        )?;
+    let output = std::process::Command::new("python3").arg(script).output()?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr)
+            .trim()
+            .to_string()
+            .into())
+    }
+}
 
-    match old_clipboard {
-        (Ok(old_text), _) => {
-            // Old Content is Text
-            write_clipboard.set_text(old_text.clone())?;
-            if let Ok(new) = new_text {
-                if new.trim() == not_selected_placeholder.trim() {
-                    Ok(String::new())
-                } else {
-                    Ok(new)
-                }
-            } else {
-                Ok(String::new())
-            }
-        }
-        (_, Ok(image)) => {
-            // Old Content is Image
-            write_clipboard.set_image(image)?;
-            if let Ok(new) = new_text {
-                if new.trim() == not_selected_placeholder.trim() {
-                    Ok(String::new())
-                } else {
-                    Ok(new)
-                }
-            } else {
-                Ok(String::new())
-            }
-        }
-        _ => {
-            // Old Content is Empty
-            write_clipboard.clear()?;
-            if let Ok(new) = new_text {
-                if new.trim() == not_selected_placeholder.trim() {
-                    Ok(String::new())
-                } else {
-                    Ok(new)
-                }
-            } else {
-                Ok(String::new())
+pub fn get_selected_text_by_clipboard(
+    enigo: &mut Enigo,
+    cancel_select: bool,
+) -> Result<String, Box<dyn std::error::Error>> {
+    use crate::clipboard::{get_copy_timeout, ClipboardTransaction, SystemClipboard};
+
+    let mut clipboard = SystemClipboard;
+    let mut transaction = ClipboardTransaction::begin(&mut clipboard)?;
+    let new_text = transaction.copy_text(
+        || {
+            copy(enigo);
+            if cancel_select {
+                right_arrow_click(enigo, 1);
             }
-        }
-    }
+        },
+        get_copy_timeout(),
+    )?;
+    Ok(new_text.unwrap_or_default())
 }
 
 #[cfg(target_os = "macos")]
@@ -449,18 +863,52 @@ pub fn get_selected_text_by_clipboard_using_applescript(
     }
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeText {
+    pub text: String,
+    // Set when a target language rule matches the text
+    pub target_language: Option<String>,
+}
+
 pub fn send_text(text: String) {
+    let payload = ChangeText {
+        target_language: crate::lang_rules::route_target_language(&text),
+        text,
+    };
     match APP_HANDLE.get() {
-        Some(handle) => handle.emit("change-text", text).unwrap_or_default(),
+        Some(handle) => handle.emit("change-text", payload).unwrap_or_default(),
         None => {}
     }
 }
 
-pub fn writing_text(text: String) {
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WritingText {
+    pub text: String,
+    pub action: Option<String>,
+    pub target_language: Option<String>,
+}
+
+// Sends the text to write to the frontend and returns the target language it
+// was sent with, if any.
+pub fn writing_text(text: String, params: &crate::writing::WritingParams) -> Option<String> {
+    // A hotkey with its own target language wins over the rules
+    let target_language = match &params.target_language {
+        Some(target_language) => Some(target_language.clone()),
+        None if params.is_translate() => crate::lang_rules::route_target_language(&text),
+        None => None,
+    };
+    let payload = WritingText {
+        text,
+        action: params.action.clone(),
+        target_language: target_language.clone(),
+    };
     match APP_HANDLE.get() {
-        Some(handle) => handle.emit("writing-text", text).unwrap_or_default(),
+        Some(handle) => handle.emit("writing-text", payload).unwrap_or_default(),
         None => {}
     }
+    target_language
 }
 
 pub fn show() {
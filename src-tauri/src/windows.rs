@@ -1,4 +1,5 @@
 use crate::config;
+use crate::live_ocr::LiveOcrRegion;
 use crate::utils;
 use crate::UpdateResult;
 use crate::ALWAYS_ON_TOP;
@@ -8,9 +9,10 @@ use cocoa::appkit::NSWindow;
 use debug_print::debug_println;
 use enigo::*;
 use mouse_position::mouse_position::Mouse;
+use parking_lot::Mutex;
 use serde_json::json;
-use std::sync::atomic::Ordering;
-use tauri::{LogicalPosition, Manager, PhysicalPosition};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tauri::{LogicalPosition, LogicalSize, Manager, PhysicalPosition};
 use tauri_plugin_updater::UpdaterExt;
 #[cfg(not(target_os = "macos"))]
 use window_shadows::set_shadow;
@@ -21,6 +23,11 @@ pub const ACTION_MANAGER_WIN_NAME: &str = "action_manager";
 pub const UPDATER_WIN_NAME: &str = "updater";
 pub const THUMB_WIN_NAME: &str = "thumb";
 pub const SCREENSHOT_WIN_NAME: &str = "screenshot";
+pub const LIVE_OCR_WIN_NAME: &str = "live_ocr";
+pub const LEADER_HINT_WIN_NAME: &str = "leader_hint";
+
+const LEADER_HINT_WIDTH: f64 = 360.0;
+const LEADER_HINT_HEIGHT: f64 = 150.0;
 
 fn get_dummy_window() -> tauri::Window {
     let app_handle = APP_HANDLE.get().unwrap();
@@ -153,10 +160,207 @@ pub fn delete_thumb() {
     }
 }
 
+const THUMB_SIZE: f64 = 20.0;
+const THUMB_POPOVER_WIDTH: f64 = 360.0;
+const THUMB_POPOVER_MIN_HEIGHT: f64 = 120.0;
+const THUMB_POPOVER_MAX_HEIGHT: f64 = 420.0;
+const THUMB_POPOVER_MARGIN: f64 = 8.0;
+
+// Whether the thumb is showing the popover with the quick actions instead of
+// the icon.
+static THUMB_EXPANDED: AtomicBool = AtomicBool::new(false);
+// Where the thumb icon was when it expanded, in physical pixels. The popover
+// always opens from there, as resizing may have moved it away.
+static THUMB_ANCHOR: Mutex<Option<(f64, f64)>> = Mutex::new(None);
+
+pub fn is_thumb_expanded() -> bool {
+    THUMB_EXPANDED.load(Ordering::Acquire)
+}
+
+// Where a rect of the given size goes so that it starts at `anchor` but stays
+// inside the monitor: it opens to the left or above the anchor instead when
+// there is no room on the right or below, and is clamped as a last resort.
+// All values are physical pixels.
+fn fit_in_monitor(anchor: (f64, f64), size: (f64, f64), monitor: &tauri::Monitor) -> (f64, f64) {
+    let origin = monitor.position();
+    let bounds = monitor.size();
+    let margin = THUMB_POPOVER_MARGIN * monitor.scale_factor();
+    let place = |anchor: f64, size: f64, start: f64, length: f64| {
+        let end = start + length - margin;
+        let position = if anchor + size <= end {
+            anchor
+        } else {
+            anchor - size
+        };
+        position.min(end - size).max(start + margin)
+    };
+    (
+        place(anchor.0, size.0, origin.x as f64, bounds.width as f64),
+        place(anchor.1, size.1, origin.y as f64, bounds.height as f64),
+    )
+}
+
+fn set_thumb_size(window: &tauri::Window, width: f64, height: f64) {
+    let size = LogicalSize::new(width, height);
+    window.set_min_size(Some(size)).unwrap_or_default();
+    window.set_max_size(Some(size)).unwrap_or_default();
+    window.set_size(size).unwrap_or_default();
+}
+
+fn place_thumb_popover(window: &tauri::Window, anchor: (f64, f64), height: f64) {
+    let monitor = get_current_monitor();
+    let scale_factor = monitor.scale_factor();
+    set_thumb_size(window, THUMB_POPOVER_WIDTH, height);
+    let (x, y) = fit_in_monitor(
+        anchor,
+        (THUMB_POPOVER_WIDTH * scale_factor, height * scale_factor),
+        &monitor,
+    );
+    window
+        .set_position(PhysicalPosition::new(x, y))
+        .unwrap_or_default();
+}
+
+// Turns the thumb into the popover, which offers the actions for the selected
+// text and shows their result inline.
+pub fn expand_thumb() {
+    let window = match APP_HANDLE
+        .get()
+        .and_then(|handle| handle.get_window(THUMB_WIN_NAME))
+    {
+        Some(window) => window,
+        None => return,
+    };
+    let anchor = match window.outer_position() {
+        Ok(position) => (position.x as f64, position.y as f64),
+        Err(e) => {
+            eprintln!("failed to get the thumb position: {}", e);
+            return;
+        }
+    };
+    *THUMB_ANCHOR.lock() = Some(anchor);
+    THUMB_EXPANDED.store(true, Ordering::Release);
+    place_thumb_popover(&window, anchor, THUMB_POPOVER_MIN_HEIGHT);
+    window.set_focus().unwrap_or_default();
+    let selected_text = crate::SELECTED_TEXT.lock().to_string();
+    window
+        .emit("thumb-expanded", json!({ "text": selected_text }))
+        .unwrap_or_default();
+}
+
+fn collapse_thumb(window: &tauri::Window) {
+    if THUMB_EXPANDED.swap(false, Ordering::AcqRel) {
+        set_thumb_size(window, THUMB_SIZE, THUMB_SIZE);
+        if let Some((x, y)) = THUMB_ANCHOR.lock().take() {
+            window
+                .set_position(PhysicalPosition::new(x, y))
+                .unwrap_or_default();
+        }
+        window.emit("thumb-collapsed", ()).unwrap_or_default();
+    }
+}
+
+// Fits the popover to its content as the result streams in.
+#[tauri::command]
+pub fn resize_thumb_popover(height: f64) {
+    if !is_thumb_expanded() {
+        return;
+    }
+    let anchor = match *THUMB_ANCHOR.lock() {
+        Some(anchor) => anchor,
+        None => return,
+    };
+    if let Some(window) = APP_HANDLE
+        .get()
+        .and_then(|handle| handle.get_window(THUMB_WIN_NAME))
+    {
+        let height = height
+            .max(THUMB_POPOVER_MIN_HEIGHT)
+            .min(THUMB_POPOVER_MAX_HEIGHT);
+        place_thumb_popover(&window, anchor, height);
+    }
+}
+
+#[tauri::command]
+pub fn close_thumb_command() {
+    close_thumb();
+}
+
+// Leaves the popover for the full translator with the selected text.
+#[tauri::command]
+pub async fn open_thumb_text_in_translator() {
+    close_thumb();
+    let selected_text = crate::SELECTED_TEXT.lock().to_string();
+    if selected_text.is_empty() {
+        return;
+    }
+    let window = show_translator_window(false, true, false);
+    utils::send_text(selected_text);
+    window.set_focus().unwrap_or_default();
+}
+
+// Shows the thumb at the mouse for the selected text, unless there is none or
+// the thumb is disabled for the foreground app.
+pub fn show_thumb_for_selection(x: i32, y: i32) {
+    let _lock = crate::RELEASE_THREAD_ID.lock();
+    if !crate::app_rules::is_thumb_enabled_for_foreground_app() {
+        close_thumb();
+        return;
+    }
+    let selected_text = utils::get_selected_text_for_thumb().unwrap_or_default();
+    if !selected_text.is_empty() {
+        *crate::SELECTED_TEXT.lock() = selected_text;
+        show_thumb(x, y);
+    } else {
+        close_thumb();
+    }
+}
+
+// Opens the popover, or the translator with the selected text when the
+// popover is turned off.
+pub fn click_thumb() {
+    let thumb_popover = config::get_config()
+        .ok()
+        .and_then(|config| config.thumb_popover)
+        .unwrap_or(true);
+    if thumb_popover {
+        expand_thumb();
+        return;
+    }
+    close_thumb();
+    let selected_text = crate::SELECTED_TEXT.lock().to_string();
+    if selected_text.is_empty() {
+        return;
+    }
+    let window = show_translator_window(false, true, false);
+    utils::send_text(selected_text);
+    if cfg!(target_os = "windows") {
+        window.set_always_on_top(true).unwrap();
+        let always_on_top = ALWAYS_ON_TOP.load(Ordering::Acquire);
+        if !always_on_top {
+            std::thread::spawn(move || {
+                window.set_always_on_top(false).unwrap();
+            });
+        }
+    } else {
+        window.set_focus().unwrap();
+    }
+}
+
+// The mouse hook doesn't see clicks in Wayland sessions, so there the thumb
+// reports them itself.
+#[tauri::command]
+pub fn thumb_clicked() {
+    if utils::is_wayland() && !is_thumb_expanded() {
+        click_thumb();
+    }
+}
+
 pub fn close_thumb() {
     match APP_HANDLE.get() {
         Some(handle) => match handle.get_window(THUMB_WIN_NAME) {
             Some(window) => {
+                collapse_thumb(&window);
                 window
                     .set_position(LogicalPosition::new(-100.0, -100.0))
                     .unwrap();
@@ -180,6 +384,7 @@ pub fn get_thumb_window(x: i32, y: i32) -> tauri::Window {
     let window = match handle.get_window(THUMB_WIN_NAME) {
         Some(window) => {
             debug_println!("Thumb window already exists");
+            collapse_thumb(&window);
             window.unminimize().unwrap();
             window.set_always_on_top(true).unwrap();
             window
@@ -193,9 +398,9 @@ pub fn get_thumb_window(x: i32, y: i32) -> tauri::Window {
             )
             .fullscreen(false)
             .focused(false)
-            .inner_size(20.0, 20.0)
-            .min_inner_size(20.0, 20.0)
-            .max_inner_size(20.0, 20.0)
+            .inner_size(THUMB_SIZE, THUMB_SIZE)
+            .min_inner_size(THUMB_SIZE, THUMB_SIZE)
+            .max_inner_size(THUMB_SIZE, THUMB_SIZE)
             .visible(true)
             .resizable(false)
             .skip_taskbar(true)
@@ -587,3 +792,123 @@ pub fn get_screenshot_window() -> tauri::Window {
 
     window
 }
+
+pub fn show_live_ocr_window(region: &LiveOcrRegion) {
+    let window = get_live_ocr_window(region);
+    window.show().unwrap();
+}
+
+pub fn close_live_ocr_window() {
+    match APP_HANDLE.get() {
+        Some(handle) => match handle.get_window(LIVE_OCR_WIN_NAME) {
+            Some(window) => {
+                window.close().unwrap();
+            }
+            None => {}
+        },
+        None => {}
+    }
+}
+
+pub fn get_live_ocr_window(region: &LiveOcrRegion) -> tauri::Window {
+    let handle = APP_HANDLE.get().unwrap();
+    let window = match handle.get_window(LIVE_OCR_WIN_NAME) {
+        Some(window) => {
+            window.unminimize().unwrap();
+            window
+        }
+        None => {
+            let builder = tauri::WindowBuilder::new(
+                handle,
+                LIVE_OCR_WIN_NAME,
+                tauri::WindowUrl::App("src/tauri/index.html".into()),
+            )
+            .title("OpenAI Translator Live OCR")
+            .fullscreen(false)
+            .inner_size(region.width.max(320.0), 140.0)
+            .min_inner_size(240.0, 80.0)
+            .resizable(true)
+            .skip_taskbar(true)
+            .focused(false);
+
+            build_window(builder)
+        }
+    };
+
+    // The region origin is the physical position of its monitor, place the
+    // window right below the region in logical coordinates.
+    let scale_factor = window
+        .available_monitors()
+        .ok()
+        .and_then(|monitors| {
+            monitors.into_iter().find(|monitor| {
+                monitor.position().x == region.x && monitor.position().y == region.y
+            })
+        })
+        .map(|monitor| monitor.scale_factor())
+        .unwrap_or(1.0);
+    let origin: LogicalPosition<f64> =
+        PhysicalPosition::new(region.x, region.y).to_logical(scale_factor);
+    window
+        .set_position(LogicalPosition::new(
+            origin.x + region.left,
+            origin.y + region.top + region.height + 8.0,
+        ))
+        .unwrap();
+    window.set_always_on_top(true).unwrap();
+
+    window
+}
+
+pub fn show_leader_hint_window() {
+    let window = get_leader_hint_window();
+    window.show().unwrap();
+}
+
+pub fn close_leader_hint_window() {
+    match APP_HANDLE.get() {
+        Some(handle) => match handle.get_window(LEADER_HINT_WIN_NAME) {
+            Some(window) => {
+                window.close().unwrap();
+            }
+            None => {}
+        },
+        None => {}
+    }
+}
+
+pub fn get_leader_hint_window() -> tauri::Window {
+    let handle = APP_HANDLE.get().unwrap();
+    let window = match handle.get_window(LEADER_HINT_WIN_NAME) {
+        Some(window) => window,
+        None => {
+            let builder = tauri::WindowBuilder::new(
+                handle,
+                LEADER_HINT_WIN_NAME,
+                tauri::WindowUrl::App("src/tauri/index.html".into()),
+            )
+            .fullscreen(false)
+            .inner_size(LEADER_HINT_WIDTH, LEADER_HINT_HEIGHT)
+            .resizable(false)
+            .skip_taskbar(true)
+            .focused(false);
+
+            build_window(builder)
+        }
+    };
+
+    // Bottom center of the monitor with the mouse, out of the way of the
+    // selection the next key is going to act on.
+    let monitor = get_current_monitor();
+    let origin: LogicalPosition<f64> = monitor.position().to_logical(monitor.scale_factor());
+    let size: LogicalSize<f64> = monitor.size().to_logical(monitor.scale_factor());
+    window
+        .set_position(LogicalPosition::new(
+            origin.x + (size.width - LEADER_HINT_WIDTH) / 2.0,
+            origin.y + size.height - LEADER_HINT_HEIGHT - 120.0,
+        ))
+        .unwrap();
+    window.set_always_on_top(true).unwrap();
+
+    window
+}
@@ -1,11 +1,18 @@
+use crate::edit_plan::{plan_incremental_edits, retained_ratio, CursorMove, PlannedEdit};
+use crate::input_writer::{
+    cancel_typing, is_typing_cancelled, reset_typing_cancel, streams_chunks, write_text,
+    TypingSpeed,
+};
 use crate::utils::{backspace_click, left_arrow_click, right_arrow_click, select_all, INPUT_LOCK};
 use crate::APP_HANDLE;
 use debug_print::debug_println;
 use enigo::*;
 use parking_lot::Mutex;
-use similar::utils::diff_chars;
-use similar::{Algorithm, ChangeTag};
-use std::{thread, time::Duration};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::thread;
+use std::time::{Duration, Instant};
+use tauri::Manager;
 use tauri_plugin_aptabase::EventTracker;
 
 pub fn get_input_text(
@@ -20,30 +27,142 @@ static IS_WRITING: Mutex<bool> = Mutex::new(false);
 static TRANSLATE_SELECTED_TEXT_PLACEHOLDER: &str = "<Translating ✍️>";
 static IS_TRANSLATE_SELECTED_TEXT: Mutex<bool> = Mutex::new(false);
 static PREVIOUS_TRANSLATED_TEXT: Mutex<String> = Mutex::new(String::new());
-static ALL_TRANSLATED_FINGERPRINT: &str = "‌";
-static ALL_TRANSLATED_FINGERPRINT_COUNT: Mutex<usize> = Mutex::new(1);
-static NEED_TO_ADD_FINGERPRINT: Mutex<bool> = Mutex::new(false);
 
+// Identifies the field the last writing run wrote into by the window it was
+// in. The field still holds our translation, without marking the text itself,
+// when it's in the same window and most of PREVIOUS_TRANSLATED_TEXT is still
+// in it.
+#[derive(PartialEq)]
+struct WritingState {
+    window: Option<(String, String)>,
+}
+
+static LAST_WRITING_STATE: Mutex<Option<WritingState>> = Mutex::new(None);
+
+// The share of the previous translation that has to survive the user's edits
+// for the field to still count as translated.
+const MIN_RETAINED_RATIO: f64 = 0.5;
+
+fn get_focused_window() -> Option<(String, String)> {
+    active_win_pos_rs::get_active_window()
+        .ok()
+        .map(|window| (window.app_name, window.window_id))
+}
+
+fn is_all_translated_before(previous_translated_text: &str, content: &str) -> bool {
+    if previous_translated_text.is_empty() {
+        return false;
+    }
+    let expected_state = WritingState {
+        window: get_focused_window(),
+    };
+    match &*LAST_WRITING_STATE.lock() {
+        Some(state) if *state == expected_state => {
+            retained_ratio(previous_translated_text, content) >= MIN_RETAINED_RATIO
+        }
+        _ => false,
+    }
+}
+
+// What the current writing run does with the text, as chosen by the hotkey that
+// started it. Unset fields are resolved by the frontend (translate into the
+// writing target language).
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct WritingParams {
+    pub action: Option<String>,
+    pub target_language: Option<String>,
+}
+
+impl WritingParams {
+    pub fn is_translate(&self) -> bool {
+        self.action
+            .as_deref()
+            .map_or(true, |action| action == "translate")
+    }
+}
+
+static WRITING_PARAMS: Mutex<WritingParams> = Mutex::new(WritingParams {
+    action: None,
+    target_language: None,
+});
+
+// The language the text being written is translated into, for the Chinese
+// conversion of the result
+static WRITING_TARGET_LANGUAGE: Mutex<Option<String>> = Mutex::new(None);
+
+fn send_writing_text(text: String, params: &WritingParams) {
+    let target_language = crate::utils::writing_text(text, params).or_else(|| {
+        if params.is_translate() {
+            crate::config::get_config()
+                .ok()
+                .and_then(|config| config.writing_target_language)
+        } else {
+            None
+        }
+    });
+    *WRITING_TARGET_LANGUAGE.lock() = target_language;
+}
+
+static PLANNED_EDITS: Mutex<Vec<PlannedEdit>> = Mutex::new(Vec::new());
+
+const WRITING_HISTORY_LIMIT: usize = 20;
+
+// The input as it was before a writing run, so that the run can be undone.
+// `written` collects everything typed by the run; when only the selection was
+// translated it is used to find the part of the field to put `original` back.
 #[derive(Clone)]
-struct IncrementalAction {
-    left_arrow_click_count: usize,
-    right_arrow_click_count: usize,
-    insertion_content: String,
+struct WritingSnapshot {
+    original: String,
+    replaced_selection: bool,
+    written: String,
 }
 
-static INCREMENTAL_ACTIONS: Mutex<Vec<IncrementalAction>> = Mutex::new(Vec::new());
+static WRITING_HISTORY: Mutex<VecDeque<WritingSnapshot>> = Mutex::new(VecDeque::new());
+
+// Runs that failed or were cancelled before their first chunk left the field
+// alone; their snapshots are dropped so that they don't hide older runs.
+fn discard_unwritten_snapshots(writing_history: &mut VecDeque<WritingSnapshot>) {
+    while writing_history
+        .back()
+        .map_or(false, |snapshot| snapshot.written.is_empty())
+    {
+        writing_history.pop_back();
+    }
+}
+
+fn push_writing_snapshot(original: String, replaced_selection: bool) {
+    let mut writing_history = WRITING_HISTORY.lock();
+    discard_unwritten_snapshots(&mut writing_history);
+    if writing_history.len() >= WRITING_HISTORY_LIMIT {
+        writing_history.pop_front();
+    }
+    writing_history.push_back(WritingSnapshot {
+        original,
+        replaced_selection,
+        written: String::new(),
+    });
+}
 
 #[tauri::command]
-pub fn writing_command() {
+pub fn writing_command(action: Option<String>, target_language: Option<String>) {
     let is_writing = IS_WRITING.lock();
     if *is_writing {
         return;
     }
+    let params = WritingParams {
+        action,
+        target_language,
+    };
+    debug_println!("writing params: {:?}", params);
+    reset_typing_cancel();
+    PENDING_TEXT.lock().clear();
+    *WRITING_PARAMS.lock() = params.clone();
     let app_handle = APP_HANDLE.get().unwrap();
     app_handle.track_event("writing", None);
     {
-        let mut incremental_actions = INCREMENTAL_ACTIONS.lock();
-        incremental_actions.clear();
+        let mut planned_edits = PLANNED_EDITS.lock();
+        planned_edits.clear();
     }
     let mut is_translate_selected_text = IS_TRANSLATE_SELECTED_TEXT.lock();
     let mut enigo = Enigo::new();
@@ -51,12 +170,13 @@ pub fn writing_command() {
         crate::utils::get_selected_text_by_clipboard(&mut enigo, false).unwrap_or_default();
     if !selected_text.is_empty() {
         *is_translate_selected_text = true;
+        push_writing_snapshot(selected_text.clone(), true);
         do_write_to_input(
             &mut enigo,
             TRANSLATE_SELECTED_TEXT_PLACEHOLDER.to_owned(),
             false,
         );
-        crate::utils::writing_text(selected_text);
+        send_writing_text(selected_text, &params);
         return;
     }
     *is_translate_selected_text = false;
@@ -69,7 +189,7 @@ pub fn writing_command() {
     if content.ends_with("\r\n") {
         content = content[..content.len() - 2].to_owned();
     }
-    let mut content = content.replace("\r\n", "\n");
+    let content = content.replace("\r\n", "\n");
     if content.trim().is_empty() {
         *previous_translated_text = content;
         return;
@@ -79,315 +199,260 @@ pub fn writing_command() {
         "previous_translated_text: {:?}",
         previous_translated_text.chars()
     );
-    let changeset = diff_chars(Algorithm::Myers, &*previous_translated_text, &content);
-    debug_println!("changeset: {:?}", changeset);
-    let modifications_count = changeset
-        .iter()
-        .filter(|(change_tag, _)| match change_tag {
-            ChangeTag::Insert => true,
-            ChangeTag::Delete => true,
-            _ => false,
-        })
-        .count();
-    let translated_fingerprint_count = ALL_TRANSLATED_FINGERPRINT_COUNT.lock();
-    let mut is_all_translated_before = content
-        .starts_with(&ALL_TRANSLATED_FINGERPRINT.repeat(*translated_fingerprint_count))
-        && !content
-            .starts_with(&ALL_TRANSLATED_FINGERPRINT.repeat(*translated_fingerprint_count + 1));
-    let mut need_to_add_fingerprint = NEED_TO_ADD_FINGERPRINT.lock();
-    *need_to_add_fingerprint = false;
-
-    if !is_all_translated_before {
-        match changeset.iter().next().clone() {
-            Some(change) => match change {
-                (ChangeTag::Delete, text) => {
-                    if *text == ALL_TRANSLATED_FINGERPRINT.repeat(*translated_fingerprint_count) {
-                        *need_to_add_fingerprint = true;
-                        is_all_translated_before = true;
-                    }
-                }
-                _ => {}
-            },
-            None => {}
-        }
-        if !is_all_translated_before && changeset.len() >= 2 {
-            let first_change = changeset.iter().next().unwrap();
-            let second_change = changeset.iter().nth(1).unwrap();
-            match (first_change, second_change) {
-                ((ChangeTag::Insert, _), (ChangeTag::Equal, text)) => {
-                    if text.starts_with(
-                        &ALL_TRANSLATED_FINGERPRINT.repeat(*translated_fingerprint_count),
-                    ) && !text.starts_with(
-                        &ALL_TRANSLATED_FINGERPRINT.repeat(*translated_fingerprint_count + 1),
-                    ) {
-                        *need_to_add_fingerprint = true;
-                        is_all_translated_before = true;
-                    }
-                }
-                _ => {}
-            }
-        }
-    }
-
+    let is_all_translated_before = is_all_translated_before(&previous_translated_text, &content);
     debug_println!("is_all_translated_before: {:?}", is_all_translated_before);
-    if !previous_translated_text.is_empty()
-        && modifications_count > 0
-        && modifications_count < 10
-        && is_all_translated_before
-    {
-        let mut incremental_actions = Vec::new();
-        let mut is_first_insertion = true;
-        let mut prev_insertion_index = 0;
-        for i in 0..changeset.len() {
-            let change = &changeset[i];
-            let insertion_content = match change {
-                (ChangeTag::Insert, content) => content,
-                _ => continue,
-            };
-            if insertion_content.trim().is_empty() {
-                continue;
-            }
-            let mut prefix_newline_count = 0;
-            for c in insertion_content.chars() {
-                if c == '\n' {
-                    prefix_newline_count += 1;
-                } else {
-                    break;
-                }
-            }
-            let mut suffix_newline_count = 0;
-            for c in insertion_content.chars().rev() {
-                if c == '\n' {
-                    suffix_newline_count += 1;
-                } else {
-                    break;
-                }
-            }
-            let mut left_arrow_click_count = 0;
-            let mut right_arrow_click_count = 0;
-            if is_first_insertion {
-                is_first_insertion = false;
-                left_arrow_click_count =
-                    changeset[i + 1..]
-                        .iter()
-                        .fold(0, |acc, change| match change {
-                            (ChangeTag::Insert, text) => acc + text.chars().count(),
-                            (ChangeTag::Equal, text) => acc + text.chars().count(),
-                            _ => acc,
-                        })
-                        + suffix_newline_count;
-            } else {
-                right_arrow_click_count = changeset[prev_insertion_index + 1..i].iter().fold(
-                    0,
-                    |acc, change| match change {
-                        (ChangeTag::Insert, text) => acc + text.chars().count(),
-                        (ChangeTag::Equal, text) => acc + text.chars().count(),
-                        _ => acc,
-                    },
-                ) + prefix_newline_count;
-            }
-            prev_insertion_index = i;
-            let insertion_content = insertion_content
-                [prefix_newline_count..insertion_content.len() - suffix_newline_count]
-                .to_owned();
-            let incremental_action = IncrementalAction {
-                left_arrow_click_count,
-                right_arrow_click_count,
-                insertion_content,
-            };
-            incremental_actions.push(incremental_action);
-        }
-        if incremental_actions.is_empty() {
+    if is_all_translated_before && content != *previous_translated_text {
+        let planned_edits = plan_incremental_edits(&previous_translated_text, &content);
+        debug_println!("planned_edits: {:?}", planned_edits);
+        if planned_edits.is_empty() {
             *previous_translated_text = content;
             return;
         }
+        push_writing_snapshot(content.clone(), false);
         thread::spawn(move || {
-            let mut global_incremental_actions = INCREMENTAL_ACTIONS.lock();
-            let reversed_incremental_actions = incremental_actions
-                .iter()
-                .rev()
-                .map(|action| action.to_owned())
-                .collect::<Vec<_>>();
-            let incremental_action =
-                reversed_incremental_actions[reversed_incremental_actions.len() - 1].clone();
-            *global_incremental_actions = reversed_incremental_actions;
-            do_incremental_writing(&incremental_action);
+            let mut global_planned_edits = PLANNED_EDITS.lock();
+            let reversed_planned_edits = planned_edits.into_iter().rev().collect::<Vec<_>>();
+            let planned_edit = reversed_planned_edits[reversed_planned_edits.len() - 1].clone();
+            *global_planned_edits = reversed_planned_edits;
+            do_incremental_writing(&planned_edit);
         });
         return;
     }
+    push_writing_snapshot(content.clone(), false);
     select_all(&mut enigo);
     thread::sleep(Duration::from_millis(30));
     do_write_to_input(&mut enigo, "Translating... ✍️".to_string(), false);
 
-    if content.starts_with(&ALL_TRANSLATED_FINGERPRINT.repeat(*translated_fingerprint_count))
-        && !content
-            .starts_with(&ALL_TRANSLATED_FINGERPRINT.repeat(*translated_fingerprint_count + 1))
-    {
-        // use chars to get string slice
-        let mut translated_text = String::new();
-        for c in content.chars().skip(*translated_fingerprint_count) {
-            translated_text.push(c);
-        }
-        content = translated_text;
-    }
-    crate::utils::writing_text(content);
+    send_writing_text(content, &params);
 }
 
-fn do_incremental_writing(incremental_action: &IncrementalAction) {
+fn do_incremental_writing(planned_edit: &PlannedEdit) {
     let mut enigo = Enigo::new();
-    if incremental_action.left_arrow_click_count > 0 {
-        left_arrow_click(&mut enigo, incremental_action.left_arrow_click_count);
-    } else if incremental_action.right_arrow_click_count > 0 {
-        right_arrow_click(
-            &mut enigo,
-            incremental_action.right_arrow_click_count
-                + incremental_action.insertion_content.chars().count(),
-        );
+    match planned_edit.cursor_move {
+        CursorMove::Left(n) => left_arrow_click(&mut enigo, n),
+        CursorMove::Right(n) => right_arrow_click(&mut enigo, n),
+        CursorMove::None => {}
     }
-    backspace_click(
-        &mut enigo,
-        incremental_action.insertion_content.chars().count(),
-    );
+    backspace_click(&mut enigo, planned_edit.delete_count);
     do_write_to_input(
         &mut enigo,
         TRANSLATE_SELECTED_TEXT_PLACEHOLDER.to_owned(),
         false,
     );
-    crate::utils::writing_text(incremental_action.insertion_content.to_owned());
+    let params = WRITING_PARAMS.lock().clone();
+    send_writing_text(planned_edit.insertion.to_owned(), &params);
 }
 
 static IS_START_WRITING: Mutex<bool> = Mutex::new(false);
 
+// Streamed text that has not been typed yet because it doesn't end at a word
+// or sentence boundary.
+static PENDING_TEXT: Mutex<String> = Mutex::new(String::new());
+const MAX_PENDING_CHARS: usize = 40;
+
+// After this point in time the rest of the translation is pasted instead of
+// typed, so long translations don't take minutes.
+static WRITING_DEADLINE: Mutex<Option<Instant>> = Mutex::new(None);
+const DEFAULT_MAX_TYPING_MS: u64 = 30_000;
+
+fn is_chunk_boundary(c: char) -> bool {
+    c.is_whitespace()
+        || matches!(
+            c,
+            '.' | ',' | '!' | '?' | ';' | ':' | '。' | '，' | '！' | '？' | '；' | '：' | '、'
+        )
+}
+
+// Takes the pending text up to and including its last word or sentence
+// boundary. Text without any boundary (e.g. a long run of CJK characters) is
+// taken once it gets long enough, except for its last characters, which may
+// start a Chinese phrase that the next chunk completes.
+fn take_ready_chunk(pending: &mut String) -> String {
+    let end = pending
+        .char_indices()
+        .rev()
+        .find(|(_, c)| is_chunk_boundary(*c))
+        .map(|(i, c)| i + c.len_utf8());
+    let count = pending.chars().count();
+    match end {
+        Some(end) => pending.drain(..end).collect(),
+        None if count >= MAX_PENDING_CHARS => {
+            let hold_back = crate::chinese::max_phrase_len().saturating_sub(1);
+            let end = pending
+                .char_indices()
+                .nth(count.saturating_sub(hold_back))
+                .map_or(pending.len(), |(i, _)| i);
+            pending.drain(..end).collect()
+        }
+        None => String::new(),
+    }
+}
+
 fn do_write_to_input(enigo: &mut Enigo, text: String, animation: bool) {
     let _guard = INPUT_LOCK.lock();
     if animation {
-        for c in text.chars() {
-            let char = c.to_string();
-            if char == "\n" {
-                if let Ok(config) = crate::config::get_config() {
-                    if let Some(writing_newline_hotkey) = config.writing_newline_hotkey {
-                        let keys = writing_newline_hotkey
-                            .split("+")
-                            .map(|c| c.trim())
-                            .collect::<Vec<&str>>();
-                        for key in &keys {
-                            if key.len() == 1 {
-                                enigo.key_down(Key::Layout(key.chars().next().unwrap()));
-                            } else {
-                                match *key {
-                                    "ctrl" => enigo.key_down(Key::Control),
-                                    "alt" => enigo.key_down(Key::Alt),
-                                    "shift" => enigo.key_down(Key::Shift),
-                                    "meta" => enigo.key_down(Key::Meta),
-                                    "caps_lock" => enigo.key_down(Key::CapsLock),
-                                    "escape" => enigo.key_down(Key::Escape),
-                                    "enter" => enigo.key_down(Key::Return),
-                                    _ => {}
-                                }
-                            }
-                        }
-                        for key in keys.iter().rev() {
-                            if key.len() == 1 {
-                                enigo.key_up(Key::Layout(key.chars().next().unwrap()));
-                            } else {
-                                match *key {
-                                    "ctrl" => enigo.key_up(Key::Control),
-                                    "alt" => enigo.key_up(Key::Alt),
-                                    "shift" => enigo.key_up(Key::Shift),
-                                    "meta" => enigo.key_up(Key::Meta),
-                                    "caps_lock" => enigo.key_up(Key::CapsLock),
-                                    "escape" => enigo.key_up(Key::Escape),
-                                    "enter" => enigo.key_up(Key::Return),
-                                    _ => {}
-                                }
-                            }
-                        }
-                        continue;
-                    }
-                }
-            }
-            enigo.key_sequence(&char);
-            thread::sleep(Duration::from_millis(20));
-        }
+        let deadline = *WRITING_DEADLINE.lock();
+        write_text(enigo, &text, TypingSpeed::from_config(), deadline);
     } else {
-        enigo.key_sequence(&text);
+        write_text(enigo, &text, TypingSpeed::Instant, None);
     }
 }
 
-#[tauri::command]
+fn write_chunk(enigo: &mut Enigo, chunk: String) {
+    if chunk.is_empty() || is_typing_cancelled() {
+        return;
+    }
+    let chunk = crate::chinese::post_process(chunk, WRITING_TARGET_LANGUAGE.lock().as_deref());
+    if let Some(snapshot) = WRITING_HISTORY.lock().back_mut() {
+        snapshot.written.push_str(&chunk);
+    }
+    do_write_to_input(enigo, chunk, true);
+}
+
+// Gets rid of the placeholder before the first chunk: the whole field is
+// selected to be overwritten, or the placeholder typed in place of the
+// selection or insertion is deleted.
+fn clear_placeholder(enigo: &mut Enigo, is_full_writing: bool) {
+    if is_full_writing {
+        select_all(enigo);
+        thread::sleep(Duration::from_millis(50));
+    } else {
+        backspace_click(
+            enigo,
+            TRANSLATE_SELECTED_TEXT_PLACEHOLDER
+                .to_owned()
+                .chars()
+                .count()
+                - 1,
+        );
+    }
+}
+
+fn start_writing_deadline() {
+    let max_typing_ms = crate::config::get_config()
+        .ok()
+        .and_then(|config| config.writing_max_typing_ms)
+        .unwrap_or(DEFAULT_MAX_TYPING_MS);
+    *WRITING_DEADLINE.lock() = if max_typing_ms > 0 {
+        Some(Instant::now() + Duration::from_millis(max_typing_ms))
+    } else {
+        None
+    };
+}
+
+// Types on a worker thread, so that the main thread is free to handle the
+// cancel hotkey meanwhile.
+#[tauri::command(async)]
 pub fn write_to_input(text: String) {
+    if is_typing_cancelled() {
+        return;
+    }
+    if !streams_chunks() {
+        // The writer sets the whole translation at once in finish_writing
+        PENDING_TEXT.lock().push_str(&text);
+        return;
+    }
     let is_translate_selected_text = IS_TRANSLATE_SELECTED_TEXT.lock();
-    let incremental_contents = INCREMENTAL_ACTIONS.lock();
-    let is_incremental_translate = !incremental_contents.is_empty();
+    let planned_edits = PLANNED_EDITS.lock();
+    let is_incremental_translate = !planned_edits.is_empty();
     let mut is_start_writing = IS_START_WRITING.lock();
     let mut enigo = Enigo::new();
-    let mut new_text = text.clone();
     let is_first_writing = !*is_start_writing;
-    let mut need_to_add_fingerprint = false;
     if is_first_writing {
-        if !*is_translate_selected_text && !is_incremental_translate {
-            select_all(&mut enigo);
-            thread::sleep(Duration::from_millis(50));
-            need_to_add_fingerprint = true;
-        } else {
-            backspace_click(
-                &mut enigo,
-                TRANSLATE_SELECTED_TEXT_PLACEHOLDER
-                    .to_owned()
-                    .chars()
-                    .count()
-                    - 1,
-            );
-        }
-    }
-    let mut global_need_to_add_fingerprint = NEED_TO_ADD_FINGERPRINT.lock();
-    if need_to_add_fingerprint || *global_need_to_add_fingerprint {
-        *global_need_to_add_fingerprint = false;
-        let mut translated_fingerprint_count = ALL_TRANSLATED_FINGERPRINT_COUNT.lock();
-        *translated_fingerprint_count += 1;
-        if *translated_fingerprint_count > 7 {
-            *translated_fingerprint_count = 1;
-        }
-        new_text = format!(
-            "{}{}",
-            ALL_TRANSLATED_FINGERPRINT.repeat(*translated_fingerprint_count),
-            text
+        clear_placeholder(
+            &mut enigo,
+            !*is_translate_selected_text && !is_incremental_translate,
         );
+        start_writing_deadline();
     }
     *is_start_writing = true;
-    do_write_to_input(&mut enigo, new_text, true);
+    let chunk = {
+        let mut pending_text = PENDING_TEXT.lock();
+        pending_text.push_str(&text);
+        take_ready_chunk(&mut pending_text)
+    };
+    write_chunk(&mut enigo, chunk);
 }
 
-#[tauri::command]
+// Stops typing as soon as possible; the frontend stops the translation stream
+// when it receives `writing-cancelled`.
+#[tauri::command(async)]
+pub fn cancel_writing() {
+    cancel_typing();
+    if let Some(handle) = APP_HANDLE.get() {
+        handle.emit("writing-cancelled", "").unwrap_or_default();
+    }
+}
+
+// Ends a cancelled run. If nothing was typed yet the placeholder is replaced
+// with what it stood for; otherwise the partial translation stays and can be
+// undone like any other run.
+fn finish_cancelled_writing(enigo: &mut Enigo, is_started: bool) {
+    let planned_edit = {
+        let mut planned_edits = PLANNED_EDITS.lock();
+        let planned_edit = planned_edits.last().cloned();
+        planned_edits.clear();
+        planned_edit
+    };
+    if !is_started {
+        let is_full_writing = !*IS_TRANSLATE_SELECTED_TEXT.lock() && planned_edit.is_none();
+        let original = match planned_edit {
+            Some(planned_edit) => Some(planned_edit.insertion),
+            None => WRITING_HISTORY
+                .lock()
+                .pop_back()
+                .map(|snapshot| snapshot.original),
+        };
+        clear_placeholder(enigo, is_full_writing);
+        if let Some(original) = original {
+            do_write_to_input(enigo, original, false);
+        }
+    }
+    *WRITING_DEADLINE.lock() = None;
+    PREVIOUS_TRANSLATED_TEXT.lock().clear();
+    LAST_WRITING_STATE.lock().take();
+}
+
+#[tauri::command(async)]
 pub fn finish_writing() {
     let mut is_writing = IS_WRITING.lock();
     *is_writing = false;
+    // Read before IS_START_WRITING is locked, as write_to_input locks these first
+    let is_full_writing = !*IS_TRANSLATE_SELECTED_TEXT.lock() && PLANNED_EDITS.lock().is_empty();
     let mut is_start_writing = IS_START_WRITING.lock();
     let mut enigo = Enigo::new();
+    let pending_text = std::mem::take(&mut *PENDING_TEXT.lock());
+    if is_typing_cancelled() {
+        let is_started = *is_start_writing;
+        *is_start_writing = false;
+        finish_cancelled_writing(&mut enigo, is_started);
+        return;
+    }
+    if !*is_start_writing && !pending_text.is_empty() {
+        // Nothing was typed while streaming, as the writer takes the whole text
+        clear_placeholder(&mut enigo, is_full_writing);
+        start_writing_deadline();
+    }
+    write_chunk(&mut enigo, pending_text);
     *is_start_writing = false;
+    *WRITING_DEADLINE.lock() = None;
 
-    let mut incremental_actions = INCREMENTAL_ACTIONS.lock();
+    let mut planned_edits = PLANNED_EDITS.lock();
 
-    let is_incremental_translate_in_the_middle = {
-        if incremental_actions.is_empty() {
-            false
-        } else {
-            let incremental_action = &incremental_actions[incremental_actions.len() - 1];
-            incremental_action.left_arrow_click_count > 0
-        }
+    let is_incremental_translate_in_the_middle = match planned_edits.last() {
+        Some(planned_edit) => matches!(planned_edit.cursor_move, CursorMove::Left(_)),
+        None => false,
     };
     if is_incremental_translate_in_the_middle {
         do_write_to_input(&mut enigo, " ".to_string(), false);
     }
 
-    if incremental_actions.len() > 1 {
-        let mut new_incremental_actions = incremental_actions.clone();
-        new_incremental_actions.pop().unwrap();
-        let incremental_action = new_incremental_actions[new_incremental_actions.len() - 1].clone();
-        *incremental_actions = new_incremental_actions;
-        do_incremental_writing(&incremental_action);
+    if planned_edits.len() > 1 {
+        planned_edits.pop();
+        let planned_edit = planned_edits[planned_edits.len() - 1].clone();
+        do_incremental_writing(&planned_edit);
     } else {
-        incremental_actions.clear();
+        planned_edits.clear();
 
         do_write_to_input(&mut enigo, " ✅".to_string(), true);
         thread::sleep(Duration::from_millis(300));
@@ -398,17 +463,104 @@ pub fn finish_writing() {
         let input_text = get_input_text(&mut enigo, true).unwrap_or_default();
         let input_text = input_text.replace("\r\n", "\n");
 
-        let fingerprint_count = input_text
-            .chars()
-            .take_while(|c| *c == ALL_TRANSLATED_FINGERPRINT.chars().next().unwrap())
-            .count();
-        if fingerprint_count > 0 {
-            let mut global_all_translated_fingerprint_count =
-                ALL_TRANSLATED_FINGERPRINT_COUNT.lock();
-            *global_all_translated_fingerprint_count = fingerprint_count;
-        }
+        *LAST_WRITING_STATE.lock() = Some(WritingState {
+            window: get_focused_window(),
+        });
 
         let mut previous_translated_text = PREVIOUS_TRANSLATED_TEXT.lock();
         *previous_translated_text = input_text;
     }
 }
+
+// Puts the input of the last writing run back into the field.
+#[tauri::command(async)]
+pub fn undo_writing() -> Result<(), String> {
+    let snapshot = {
+        let mut writing_history = WRITING_HISTORY.lock();
+        discard_unwritten_snapshots(&mut writing_history);
+        writing_history.pop_back()
+    }
+    .ok_or_else(|| "Nothing to undo".to_string())?;
+    let mut enigo = Enigo::new();
+    let restored_text = if snapshot.replaced_selection {
+        let input_text = get_input_text(&mut enigo, true)
+            .map_err(|e| e.to_string())?
+            .replace("\r\n", "\n");
+        let written = snapshot.written.trim();
+        match input_text.rfind(written) {
+            Some(index) if !written.is_empty() => format!(
+                "{}{}{}",
+                &input_text[..index],
+                snapshot.original,
+                &input_text[index + written.len()..]
+            ),
+            _ => {
+                WRITING_HISTORY.lock().push_back(snapshot);
+                return Err("The written text has been changed".to_string());
+            }
+        }
+    } else {
+        snapshot.original
+    };
+    select_all(&mut enigo);
+    thread::sleep(Duration::from_millis(50));
+    do_write_to_input(&mut enigo, restored_text, false);
+    // The field no longer holds a translation
+    PREVIOUS_TRANSLATED_TEXT.lock().clear();
+    LAST_WRITING_STATE.lock().take();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(original: &str, written: &str) -> WritingSnapshot {
+        WritingSnapshot {
+            original: original.to_string(),
+            replaced_selection: false,
+            written: written.to_string(),
+        }
+    }
+
+    #[test]
+    fn unwritten_snapshots_do_not_hide_older_runs() {
+        let mut writing_history = VecDeque::from(vec![
+            snapshot("first", "erste"),
+            snapshot("second", ""),
+            snapshot("third", ""),
+        ]);
+        discard_unwritten_snapshots(&mut writing_history);
+        assert_eq!(writing_history.len(), 1);
+        assert_eq!(writing_history[0].original, "first");
+
+        // Only the runs after the last written one go
+        let mut writing_history =
+            VecDeque::from(vec![snapshot("first", ""), snapshot("second", "zweite")]);
+        discard_unwritten_snapshots(&mut writing_history);
+        assert_eq!(writing_history.len(), 2);
+    }
+
+    #[test]
+    fn long_runs_keep_a_phrase_back() {
+        let hold_back = crate::chinese::max_phrase_len() - 1;
+        // 一只 straddles the cut at MAX_PENDING_CHARS
+        let text = format!("{}一只猫", "这".repeat(MAX_PENDING_CHARS - 1));
+        let mut pending = text[..text.len() - "只猫".len()].to_string();
+        let chunk = take_ready_chunk(&mut pending);
+        assert_eq!(chunk.chars().count(), MAX_PENDING_CHARS - hold_back);
+        assert_eq!(pending.chars().count(), hold_back);
+        pending.push_str("只猫");
+        let to = crate::chinese::ChineseVariant::Traditional;
+        assert_eq!(
+            crate::chinese::convert(&chunk, to) + &crate::chinese::convert(&pending, to),
+            crate::chinese::convert(&text, to)
+        );
+
+        let mut pending = "这".repeat(MAX_PENDING_CHARS - 1);
+        assert_eq!(take_ready_chunk(&mut pending), "");
+        let mut pending = "猫 这只".to_string();
+        assert_eq!(take_ready_chunk(&mut pending), "猫 ");
+        assert_eq!(pending, "这只");
+    }
+}
@@ -20,7 +20,17 @@ import { LangCode, supportedLanguages } from '../lang'
 import { useRecordHotkeys } from 'react-hotkeys-hook'
 import { createUseStyles } from 'react-jss'
 import clsx from 'clsx'
-import { ISettings, IThemedStyleProps, LanguageDetectionEngine, ThemeType } from '../types'
+import {
+    IHotkeyError,
+    ISettings,
+    IThemedStyleProps,
+    IWritingHotkey,
+    LanguageDetectionEngine,
+    ThemeType,
+    ThumbAction,
+    WritingAction,
+    WritingSpeed,
+} from '../types'
 import { useTheme } from '../hooks/useTheme'
 import { IoCloseCircle, IoRefreshSharp, IoSettingsOutline } from 'react-icons/io5'
 import { useTranslation } from 'react-i18next'
@@ -1202,6 +1212,248 @@ function HotkeyRecorder({ value, onChange, onBlur, testId }: IHotkeyRecorderProp
     )
 }
 
+interface IThumbActionsSelectorProps {
+    value?: ThumbAction[]
+    onChange?: (value: ThumbAction[]) => void
+    onBlur?: () => void
+}
+
+function ThumbActionsSelector({ value, onChange, onBlur }: IThumbActionsSelectorProps) {
+    const { t } = useTranslation()
+
+    const options: { id: ThumbAction; label: string }[] = [
+        { id: 'translate', label: t('Translate') },
+        { id: 'explain', label: t('Explain') },
+        { id: 'polish', label: t('Polishing') },
+        { id: 'copy', label: t('Copy') },
+    ]
+
+    return (
+        <Select
+            size='compact'
+            onBlur={onBlur}
+            multi
+            searchable={false}
+            clearable={false}
+            value={(value ?? options.map((option) => option.id)).map((id) => ({ id }))}
+            onChange={(params) => {
+                onChange?.(params.value.map((option) => option.id as ThumbAction))
+            }}
+            options={options}
+        />
+    )
+}
+
+interface IWritingSpeedSelectorProps {
+    value?: WritingSpeed
+    onChange?: (value: WritingSpeed) => void
+    onBlur?: () => void
+}
+
+function WritingSpeedSelector({ value, onChange, onBlur }: IWritingSpeedSelectorProps) {
+    const { t } = useTranslation()
+
+    return (
+        <Select
+            size='compact'
+            onBlur={onBlur}
+            searchable={false}
+            clearable={false}
+            value={[{ id: value ?? 'per-char' }]}
+            onChange={(params) => {
+                onChange?.(params.value[0].id as WritingSpeed)
+            }}
+            options={[
+                { label: t('Instant'), id: 'instant' },
+                { label: t('Word by word'), id: 'per-word' },
+                { label: t('Character by character'), id: 'per-char' },
+            ]}
+        />
+    )
+}
+
+interface IWritingMaxTypingTimeInputProps {
+    value?: number
+    onChange?: (value?: number) => void
+    onBlur?: () => void
+}
+
+// Edited in seconds, stored in milliseconds; 0 types everything
+function WritingMaxTypingTimeInput({ value, onChange, onBlur }: IWritingMaxTypingTimeInputProps) {
+    const { t } = useTranslation()
+
+    return (
+        <Input
+            size='compact'
+            type='number'
+            min={0}
+            placeholder='30'
+            value={value === undefined ? '' : value / 1000}
+            endEnhancer={t('seconds')}
+            onChange={(e) => {
+                const seconds = e.currentTarget.value
+                onChange?.(seconds === '' ? undefined : Math.max(0, Math.round(Number(seconds) * 1000)))
+            }}
+            onBlur={onBlur}
+        />
+    )
+}
+
+interface IDictionaryDirInputProps {
+    value?: string
+    onChange?: (value: string) => void
+    onBlur?: () => void
+}
+
+function DictionaryDirInput({ value, onChange, onBlur }: IDictionaryDirInputProps) {
+    const { t } = useTranslation()
+    const [isLoading, setIsLoading] = useState(false)
+
+    const reload = async () => {
+        setIsLoading(true)
+        try {
+            const { invoke } = await import('@tauri-apps/api/primitives')
+            const count = await invoke<number>('reload_dictionaries')
+            toast.success(t('Loaded {{count}} dictionaries', { count }))
+        } catch (e) {
+            console.error(e)
+            toast.error(String(e), { duration: 8000 })
+        } finally {
+            setIsLoading(false)
+        }
+    }
+
+    return (
+        <div
+            style={{
+                display: 'flex',
+                alignItems: 'center',
+                gap: 10,
+            }}
+        >
+            <Input
+                size='compact'
+                placeholder={t('The dictionaries folder in the config dir')}
+                value={value ?? ''}
+                onChange={(e) => onChange?.(e.currentTarget.value)}
+                onBlur={onBlur}
+            />
+            <Button
+                size='compact'
+                kind='secondary'
+                isLoading={isLoading}
+                onClick={(e) => {
+                    e.preventDefault()
+                    e.stopPropagation()
+                    reload()
+                }}
+            >
+                <IoRefreshSharp size={16} />
+            </Button>
+        </div>
+    )
+}
+
+interface IWritingHotkeysSettingsProps {
+    value?: IWritingHotkey[]
+    onChange?: (value: IWritingHotkey[]) => void
+    onBlur?: () => void
+}
+
+function WritingHotkeysSettings({ value, onChange, onBlur }: IWritingHotkeysSettingsProps) {
+    const { t } = useTranslation()
+
+    const actionOptions: { id: WritingAction; label: string }[] = [
+        { id: 'translate', label: t('Translate') },
+        { id: 'polish', label: t('Polishing') },
+        { id: 'fix-grammar', label: t('Fix grammar') },
+        { id: 'summarize', label: t('Summarize') },
+    ]
+
+    const items = value ?? []
+
+    const updateItem = (idx: number, item: Partial<IWritingHotkey>) => {
+        onChange?.(items.map((old, i) => (i === idx ? { ...old, ...item } : old)))
+    }
+
+    return (
+        <div
+            style={{
+                display: 'flex',
+                flexDirection: 'column',
+                gap: 10,
+            }}
+        >
+            {items.map((item, idx) => (
+                <div
+                    key={idx}
+                    style={{
+                        display: 'flex',
+                        alignItems: 'flex-start',
+                        gap: 10,
+                    }}
+                >
+                    <div style={{ flex: 1 }}>
+                        <HotkeyRecorder
+                            value={item.hotkey}
+                            onChange={(hotkey) => {
+                                if (hotkey !== item.hotkey) {
+                                    updateItem(idx, { hotkey })
+                                }
+                            }}
+                            onBlur={onBlur}
+                            testId={`writing-hotkeys-recorder-${idx}`}
+                        />
+                    </div>
+                    <Select
+                        size='compact'
+                        clearable={false}
+                        searchable={false}
+                        options={actionOptions}
+                        value={[{ id: item.action ?? 'translate' }]}
+                        onChange={({ option }) => updateItem(idx, { action: option?.id as WritingAction })}
+                        onBlur={onBlur}
+                        overrides={{ Root: { style: { width: '130px', flexShrink: 0 } } }}
+                    />
+                    <div style={{ width: '150px', flexShrink: 0 }}>
+                        <LanguageSelector
+                            value={item.targetLanguage}
+                            onChange={(targetLanguage) => updateItem(idx, { targetLanguage })}
+                            onBlur={onBlur}
+                        />
+                    </div>
+                    <Button
+                        shape='circle'
+                        size='mini'
+                        overrides={{ Root: { style: { flexShrink: 0 } } }}
+                        onClick={(e) => {
+                            e.preventDefault()
+                            e.stopPropagation()
+                            onChange?.(items.filter((_, i) => i !== idx))
+                            onBlur?.()
+                        }}
+                    >
+                        <RiDeleteBin5Line />
+                    </Button>
+                </div>
+            ))}
+            <div>
+                <Button
+                    size='mini'
+                    startEnhancer={() => <IoMdAdd size={12} />}
+                    onClick={(e) => {
+                        e.preventDefault()
+                        e.stopPropagation()
+                        onChange?.([...items, { hotkey: '', action: 'translate' }])
+                    }}
+                >
+                    {t('Add')}
+                </Button>
+            </div>
+        </div>
+    )
+}
+
 function ProviderSelector({ value, onChange }: IProviderSelectorProps) {
     const options = utils.isDesktopApp()
         ? ([
@@ -1410,6 +1662,23 @@ export function InnerSettings({ onSave, showFooter = false }: IInnerSettingsProp
                 icon: '👍',
                 duration: 3000,
             })
+            if (isTauri) {
+                try {
+                    const { invoke } = await import('@tauri-apps/api/primitives')
+                    await invoke('clear_config_cache')
+                    const errors = await invoke<string[]>('get_config_errors')
+                    for (const error of errors) {
+                        toast.error(error, { duration: 8000 })
+                    }
+                    const hotkeyErrors = await invoke<IHotkeyError[]>('register_hotkeys_command')
+                    for (const error of hotkeyErrors) {
+                        toast.error(`${error.hotkey}: ${error.error}`, { duration: 8000 })
+                    }
+                } catch (e) {
+                    console.error(e)
+                    toast.error(String(e), { duration: 8000 })
+                }
+            }
             setLoading(false)
             setSettings(data)
             onSave?.(oldSettings)
@@ -2043,6 +2312,40 @@ export function InnerSettings({ onSave, showFooter = false }: IInnerSettingsProp
                         >
                             <AlwaysShowIconsCheckbox onBlur={onBlur} />
                         </FormItem>
+                        <FormItem
+                            style={{
+                                display: isDesktopApp ? 'block' : 'none',
+                            }}
+                            name='thumbPopover'
+                            label={t('Icon popover')}
+                            caption={t(
+                                'Clicking the icon next to the selected text shows the quick actions in place instead of opening the translator.'
+                            )}
+                        >
+                            <MyCheckbox onBlur={onBlur} />
+                        </FormItem>
+                        <FormItem
+                            style={{
+                                display: isDesktopApp ? 'block' : 'none',
+                            }}
+                            name='thumbActions'
+                            label={t('Icon quick actions')}
+                            caption={t('Actions offered when the icon next to the selected text is clicked.')}
+                        >
+                            <ThumbActionsSelector onBlur={onBlur} />
+                        </FormItem>
+                        <FormItem
+                            style={{
+                                display: isDesktopApp ? 'block' : 'none',
+                            }}
+                            name='dictionaryDir'
+                            label={t('Dictionaries')}
+                            caption={t(
+                                'Single words are looked up in the StarDict, dictd and CSV dictionaries of this folder. Put them in folders named by their language pair, e.g. en-zh, and reload after saving.'
+                            )}
+                        >
+                            <DictionaryDirInput onBlur={onBlur} />
+                        </FormItem>
                         <FormItem
                             style={{
                                 display: isDesktopApp && isMacOS ? 'block' : 'none',
@@ -2138,6 +2441,18 @@ export function InnerSettings({ onSave, showFooter = false }: IInnerSettingsProp
                         >
                             <HotkeyRecorder onBlur={onBlur} testId='writing-hotkey-recorder' />
                         </FormItem>
+                        <FormItem
+                            style={{
+                                display: isDesktopApp ? 'block' : 'none',
+                            }}
+                            name='writingHotkeys'
+                            label={t('More writing hotkeys')}
+                            caption={t(
+                                'Each hotkey runs its own action on the input box, with its own target language. The writing target language is used when none is selected.'
+                            )}
+                        >
+                            <WritingHotkeysSettings onBlur={onBlur} />
+                        </FormItem>
                         <FormItem
                             style={{
                                 display: isDesktopApp ? 'block' : 'none',
@@ -2148,6 +2463,48 @@ export function InnerSettings({ onSave, showFooter = false }: IInnerSettingsProp
                         >
                             <HotkeyRecorder onBlur={onBlur} testId='writing-newline-hotkey-recorder' />
                         </FormItem>
+                        <FormItem
+                            style={{
+                                display: isDesktopApp ? 'block' : 'none',
+                            }}
+                            name='writingUndoHotkey'
+                            label={t('Writing undo Hotkey')}
+                            caption={t('Restore the text that was in the input box before the last writing.')}
+                        >
+                            <HotkeyRecorder onBlur={onBlur} testId='writing-undo-hotkey-recorder' />
+                        </FormItem>
+                        <FormItem
+                            style={{
+                                display: isDesktopApp ? 'block' : 'none',
+                            }}
+                            name='writingCancelHotkey'
+                            label={t('Writing cancel Hotkey')}
+                            caption={t('Stop typing the translation that is being written.')}
+                        >
+                            <HotkeyRecorder onBlur={onBlur} testId='writing-cancel-hotkey-recorder' />
+                        </FormItem>
+                        <FormItem
+                            style={{
+                                display: isDesktopApp ? 'block' : 'none',
+                            }}
+                            name='writingSpeed'
+                            label={t('Writing speed')}
+                            caption={t(
+                                'Long translations are pasted instead of typed once typing takes longer than the time limit.'
+                            )}
+                        >
+                            <WritingSpeedSelector onBlur={onBlur} />
+                        </FormItem>
+                        <FormItem
+                            style={{
+                                display: isDesktopApp ? 'block' : 'none',
+                            }}
+                            name='writingMaxTypingMs'
+                            label={t('Writing time limit')}
+                            caption={t('The rest of the translation is pasted after this many seconds; 0 never pastes.')}
+                        >
+                            <WritingMaxTypingTimeInput onBlur={onBlur} />
+                        </FormItem>
                     </div>
                     <div
                         style={{
@@ -2169,6 +2526,18 @@ export function InnerSettings({ onSave, showFooter = false }: IInnerSettingsProp
                         >
                             <HotkeyRecorder onBlur={onBlur} testId='ocr-hotkey-recorder' />
                         </FormItem>
+                        <FormItem
+                            style={{
+                                display: isDesktopApp ? 'block' : 'none',
+                            }}
+                            name='leaderHotkey'
+                            label={t('Leader Hotkey')}
+                            caption={t(
+                                'Then press T to translate, O for OCR, W to write, P to polish or 1-9 for an action.'
+                            )}
+                        >
+                            <HotkeyRecorder onBlur={onBlur} testId='leader-hotkey-recorder' />
+                        </FormItem>
                     </div>
                 </div>
                 <div
@@ -35,7 +35,7 @@ import { listen, Event } from '@tauri-apps/api/event'
 import IpLocationNotification from '../components/IpLocationNotification'
 import { HighlightInTextarea } from '../highlight-in-textarea'
 import { LRUCache } from 'lru-cache'
-import { ISettings, IThemedStyleProps } from '../types'
+import { ISettings, IThemedStyleProps, IWordLookup } from '../types'
 import { useTheme } from '../hooks/useTheme'
 import { Tooltip } from './Tooltip'
 import { useSettings } from '../hooks/useSettings'
@@ -63,7 +63,6 @@ import { GlobalSuspense } from './GlobalSuspense'
 import { useLazyEffect } from '../usehooks'
 import LogoWithText, { type LogoWithTextRef } from './LogoWithText'
 import Toaster from './Toaster'
-import { readBinaryFile } from '@tauri-apps/plugin-fs'
 import { getCurrent } from '@tauri-apps/api/window'
 import { useDeepCompareCallback } from 'use-deep-compare'
 import { useTranslatorStore } from '../store'
@@ -72,6 +71,14 @@ import { IPromotionResponse, fetchPromotions, getPromotionItem } from '../servic
 import { usePromotionShowed } from '../hooks/usePromotionShowed'
 import { SpeakerIcon } from './SpeakerIcon'
 
+interface IOcrHistoryEntry {
+    id: string
+    text: string
+    translation?: string
+    // Reopened from the history, so the stored translation is shown instead of translating again
+    reopened?: boolean
+}
+
 const cache = new LRUCache({
     max: 500,
     maxSize: 5000,
@@ -92,6 +99,15 @@ function genLangOptions(langs: [LangCode, string][]): Value {
         ]
     }, [] as Value)
 }
+
+function formatWordLookup(lookup: IWordLookup): string {
+    const lines = lookup.lemma ? [`${lookup.word} → ${lookup.lemma}`] : []
+    for (const entry of lookup.entries) {
+        const phonetic = entry.phonetic ? ` /${entry.phonetic}/` : ''
+        lines.push(`${entry.headword}${phonetic} (${entry.dictionary})`, ...entry.senses)
+    }
+    return lines.join('\n')
+}
 const sourceLangOptions = genLangOptions(sourceLanguages)
 const targetLangOptions = genLangOptions(targetLanguages)
 
@@ -724,6 +740,8 @@ function InnerTranslator(props: IInnerTranslatorProps) {
     const [editableText, setEditableText] = useState('')
     const [tokenCount, setTokenCount] = useState(0)
     const [translatedText, setTranslatedText] = useState('')
+    // The OCR history entry of the recognized text, which keeps its translation
+    const ocrHistoryEntryRef = useRef<IOcrHistoryEntry>()
     const [translatedLines, setTranslatedLines] = useState<string[]>([])
     const [isWordMode, setIsWordMode] = useState(false)
     const [isCollectedWord, setIsCollectedWord] = useState(false)
@@ -744,13 +762,16 @@ function InnerTranslator(props: IInnerTranslatorProps) {
     })
 
     const getTranslateDeps = useCallback(
-        async function (text: string, action: Action): Promise<typeof translateDeps> {
+        async function (text: string, action: Action, routedTargetLang?: LangCode): Promise<typeof translateDeps> {
             const newSourceLang = await detectLang(text)
             setSourceLang(newSourceLang)
             return await new Promise((resolve) => {
                 const isTranslate = action.mode === 'translate'
                 setTargetLang((targetLang_) => {
                     const newTargetLang = (() => {
+                        if (isTranslate && routedTargetLang && !stopAutomaticallyChangeTargetLang.current) {
+                            return routedTargetLang
+                        }
                         if (
                             isTranslate &&
                             (!stopAutomaticallyChangeTargetLang.current || newSourceLang === targetLang_)
@@ -786,7 +807,7 @@ function InnerTranslator(props: IInnerTranslatorProps) {
         [settings.defaultTargetLanguage]
     )
 
-    const { externalOriginalText } = useTranslatorStore()
+    const { externalOriginalText, externalTargetLang } = useTranslatorStore()
 
     useEffect(() => {
         if (externalOriginalText === undefined) {
@@ -803,14 +824,14 @@ function InnerTranslator(props: IInnerTranslatorProps) {
                 return action
             }
             setEditableText(externalOriginalText)
-            getTranslateDeps(externalOriginalText, action).then((v) => {
+            getTranslateDeps(externalOriginalText, action, externalTargetLang).then((v) => {
                 setTranslateDeps(v)
             })
             return action
         })
         setSelectedWord('')
         setHighlightWords([])
-    }, [externalOriginalText, getTranslateDeps, props.uuid])
+    }, [externalOriginalText, externalTargetLang, getTranslateDeps, props.uuid])
 
     useEffect(() => {
         setEditableText(translateDeps.text)
@@ -1035,10 +1056,48 @@ function InnerTranslator(props: IInnerTranslatorProps) {
             }:${action.commandPrompt}:${
                 action.outputRenderingFormat
             }:${sourceLang}:${targetLang}:${text}:${selectedWord}:${translationFlag}`
+            const ocrHistoryEntry =
+                ocrHistoryEntryRef.current?.text.trim() === text.trim() ? ocrHistoryEntryRef.current : undefined
+            const recordOcrTranslation = (translation: string) => {
+                if (!ocrHistoryEntry || !translation) {
+                    return
+                }
+                ocrHistoryEntry.translation = translation
+                ;(async () => {
+                    const { invoke } = await import('@tauri-apps/api/primitives')
+                    await invoke('update_ocr_history_translation', { id: ocrHistoryEntry.id, translation })
+                })().catch((e) => console.error('failed to record the ocr translation', e))
+            }
+            if (ocrHistoryEntry?.reopened && ocrHistoryEntry.translation) {
+                ocrHistoryEntry.reopened = false
+                afterTranslate('stop')
+                setTranslatedText(ocrHistoryEntry.translation)
+                return
+            }
+            if (isTauri() && actionMode === 'translate' && !selectedWord) {
+                // Single words are answered from the local dictionaries without a request
+                const { invoke } = await import('@tauri-apps/api/primitives')
+                const lookup = await invoke<IWordLookup | null>('lookup_word', { text, sourceLang, targetLang }).catch(
+                    (e) => {
+                        console.error('failed to look the word up', e)
+                        return null
+                    }
+                )
+                if (signal.aborted) {
+                    return
+                }
+                if (lookup) {
+                    afterTranslate('stop')
+                    setIsWordMode(false)
+                    setTranslatedText(formatWordLookup(lookup))
+                    return
+                }
+            }
             const cachedValue = cache.get(cachedKey)
             if (cachedValue) {
                 afterTranslate('stop')
                 setTranslatedText(cachedValue as string)
+                recordOcrTranslation(cachedValue as string)
                 return
             }
             let isStopped = false
@@ -1071,6 +1130,9 @@ function InnerTranslator(props: IInnerTranslatorProps) {
                         setTranslatedText((translatedText) => {
                             const result = translatedText
                             cache.set(cachedKey, result)
+                            if (reason === 'stop') {
+                                recordOcrTranslation(result)
+                            }
                             return result
                         })
                     },
@@ -1153,6 +1215,48 @@ function InnerTranslator(props: IInnerTranslatorProps) {
         }
     }, [isOCRProcessing])
 
+    useEffect(() => {
+        if (!isTauri()) {
+            return
+        }
+        let unlistenRecorded: (() => void) | undefined = undefined
+        let unlistenReopen: (() => void) | undefined = undefined
+        ;(async () => {
+            // Both are followed by a `change-text` event with the recognized text
+            unlistenRecorded = await listen('ocr-history-recorded', (e: Event<IOcrHistoryEntry>) => {
+                ocrHistoryEntryRef.current = e.payload
+            })
+            unlistenReopen = await listen('ocr-history-reopen', (e: Event<IOcrHistoryEntry>) => {
+                ocrHistoryEntryRef.current = { ...e.payload, reopened: true }
+            })
+        })()
+
+        return () => {
+            unlistenRecorded?.()
+            unlistenReopen?.()
+        }
+    }, [])
+
+    useEffect(() => {
+        if (!isTauri()) {
+            return
+        }
+        let unlisten: (() => void) | undefined = undefined
+        ;(async () => {
+            // A digit pressed in leader-key mode picks one of the actions by its position
+            unlisten = await listen('leader-action', (e: Event<{ index: number }>) => {
+                const action = actions?.[e.payload.index]
+                if (action) {
+                    setActivateAction(action)
+                }
+            })
+        })()
+
+        return () => {
+            unlisten?.()
+        }
+    }, [actions])
+
     useEffect(() => {
         if (!isTauri()) {
             return
@@ -1172,25 +1276,11 @@ function InnerTranslator(props: IInnerTranslatorProps) {
                 }
 
                 const fileExtension = filePath.split('.').pop()?.toLowerCase() || ''
-                if (!['jpg', 'jpeg', 'png', 'gif'].includes(fileExtension)) {
+                if (!['jpg', 'jpeg', 'png', 'gif', 'webp', 'bmp', 'pdf'].includes(fileExtension)) {
                     alert('invalid file type')
                     return
                 }
 
-                const worker = createWorker()
-
-                const binaryFile = await readBinaryFile(filePath)
-
-                const file = new Blob([binaryFile.buffer], {
-                    type: `image/${fileExtension}`,
-                })
-
-                const fileSize = file.size / 1024 / 1024
-                if (fileSize > 1) {
-                    alert('File size must be less than 1MB')
-                    return
-                }
-
                 setTranslateDeps((v) => {
                     return {
                         ...v,
@@ -1199,20 +1289,15 @@ function InnerTranslator(props: IInnerTranslatorProps) {
                 })
                 setIsOCRProcessing(true)
 
-                await (await worker).loadLanguage('eng+chi_sim+chi_tra+jpn+rus+kor')
-                await (await worker).initialize('eng+chi_sim+chi_tra+jpn+rus+kor')
-
-                const { data } = await (await worker).recognize(file)
-
-                if (activateAction) {
-                    const newTranslateDeps = await getTranslateDeps(data.text, activateAction)
-
-                    setTranslateDeps(newTranslateDeps)
+                // The backend recognizes the file and sends the result back through the `change-text` event
+                try {
+                    const { invoke } = await import('@tauri-apps/api/primitives')
+                    await invoke('ocr_file', { path: filePath })
+                } catch (e) {
+                    alert(e)
+                } finally {
+                    setIsOCRProcessing(false)
                 }
-
-                setIsOCRProcessing(false)
-
-                await (await worker).terminate()
             })
         })()
 
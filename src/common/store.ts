@@ -1,11 +1,16 @@
 import { create } from 'zustand'
+import { LangCode } from './lang'
 
 interface ITranslatorState {
     externalOriginalText?: string
+    // Chosen by the target language rules for the external text
+    externalTargetLang?: LangCode
 }
 
 export const useTranslatorStore = create<ITranslatorState>()(() => ({
     externalOriginalText: undefined,
+    externalTargetLang: undefined,
 }))
 
-export const setExternalOriginalText = (text: string) => useTranslatorStore.setState({ externalOriginalText: text })
+export const setExternalOriginalText = (text: string, targetLang?: LangCode) =>
+    useTranslatorStore.setState({ externalOriginalText: text, externalTargetLang: targetLang })
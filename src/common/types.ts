@@ -47,6 +47,37 @@ export interface IThemedStyleProps {
 
 export type LanguageDetectionEngine = 'google' | 'baidu' | 'bing' | 'local'
 
+export type WritingAction = 'translate' | 'polish' | 'fix-grammar' | 'summarize'
+
+export type WritingSpeed = 'instant' | 'per-word' | 'per-char'
+
+export type ThumbAction = 'translate' | 'explain' | 'polish' | 'copy'
+
+export interface IDictionaryEntry {
+    dictionary: string
+    headword: string
+    phonetic?: string
+    senses: string[]
+}
+
+export interface IHotkeyError {
+    name: string
+    hotkey: string
+    error: string
+}
+
+export interface IWordLookup {
+    word: string
+    lemma?: string
+    entries: IDictionaryEntry[]
+}
+
+export interface IWritingHotkey {
+    hotkey: string
+    action?: WritingAction
+    targetLanguage?: string
+}
+
 export interface ISettings {
     automaticCheckForUpdates: boolean
     apiKeys: string
@@ -67,12 +98,21 @@ export interface ISettings {
     defaultTranslateMode: Exclude<TranslateMode, 'big-bang'> | 'nop'
     defaultTargetLanguage: string
     alwaysShowIcons: boolean
+    thumbPopover?: boolean
+    thumbActions?: ThumbAction[]
+    dictionaryDir?: string
     hotkey?: string
     displayWindowHotkey?: string
     ocrHotkey?: string
+    leaderHotkey?: string
     writingTargetLanguage: string
     writingHotkey?: string
+    writingHotkeys?: IWritingHotkey[]
     writingNewlineHotkey?: string
+    writingUndoHotkey?: string
+    writingCancelHotkey?: string
+    writingSpeed?: WritingSpeed
+    writingMaxTypingMs?: number
     themeType?: ThemeType
     i18n?: string
     tts?: {
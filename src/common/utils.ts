@@ -55,12 +55,21 @@ const settingKeys: Record<keyof ISettings, number> = {
     defaultTranslateMode: 1,
     defaultTargetLanguage: 1,
     alwaysShowIcons: 1,
+    thumbPopover: 1,
+    thumbActions: 1,
+    dictionaryDir: 1,
     hotkey: 1,
     displayWindowHotkey: 1,
     ocrHotkey: 1,
+    leaderHotkey: 1,
     writingTargetLanguage: 1,
     writingHotkey: 1,
+    writingHotkeys: 1,
     writingNewlineHotkey: 1,
+    writingUndoHotkey: 1,
+    writingCancelHotkey: 1,
+    writingSpeed: 1,
+    writingMaxTypingMs: 1,
     themeType: 1,
     i18n: 1,
     tts: 1,
@@ -111,6 +120,9 @@ export async function getSettings(): Promise<ISettings> {
     if (settings.alwaysShowIcons === undefined || settings.alwaysShowIcons === null) {
         settings.alwaysShowIcons = !isTauri()
     }
+    if (settings.thumbPopover === undefined || settings.thumbPopover === null) {
+        settings.thumbPopover = true
+    }
     if (!settings.i18n) {
         settings.i18n = defaulti18n
     }
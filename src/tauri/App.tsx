@@ -7,6 +7,8 @@ import { ActionManagerWindow } from './windows/ActionManagerWindow'
 import { ThumbWindow } from './windows/ThumbWindow'
 import { UpdaterWindow } from './windows/UpdaterWindow'
 import { ScreenshotWindow } from './windows/ScreenshotWindow'
+import { LiveOcrWindow } from './windows/LiveOcrWindow'
+import { LeaderHintWindow } from './windows/LeaderHintWindow'
 
 const windowsMap: Record<string, typeof TranslatorWindow> = {
     translator: TranslatorWindow,
@@ -15,6 +17,8 @@ const windowsMap: Record<string, typeof TranslatorWindow> = {
     thumb: ThumbWindow,
     updater: UpdaterWindow,
     screenshot: ScreenshotWindow,
+    live_ocr: LiveOcrWindow,
+    leader_hint: LeaderHintWindow,
 }
 
 export function App() {
@@ -0,0 +1,81 @@
+import { useEffect, useState } from 'react'
+import { BaseProvider } from 'baseui-sd'
+import { useTranslation } from 'react-i18next'
+import { useTheme } from '../../common/hooks/useTheme'
+import { actionService } from '../../common/services/action'
+import { Action } from '../../common/internal-services/db'
+import { invoke } from '@tauri-apps/api/primitives'
+import '../../common/i18n.js'
+
+export function LeaderHintWindow() {
+    const { theme } = useTheme()
+    const { t } = useTranslation()
+    const [actions, setActions] = useState<Action[]>([])
+    // Keys another app has as global shortcuts, which the leader mode can't use
+    const [unavailableKeys, setUnavailableKeys] = useState<string[]>([])
+
+    useEffect(() => {
+        actionService.list().then((actions) => setActions(actions.slice(0, 9)))
+        invoke<string[]>('get_unavailable_leader_keys').then(setUnavailableKeys)
+    }, [])
+
+    const keys: Array<[string, string]> = [
+        ['T', t('Translate')],
+        ['O', 'OCR'],
+        ['W', t('Writing')],
+        ['P', t('Polishing')],
+        ...actions.map((action, idx): [string, string] => [
+            String(idx + 1),
+            action.mode ? t(action.name) : action.name,
+        ]),
+    ]
+
+    return (
+        <BaseProvider theme={theme}>
+            <div
+                style={{
+                    background: theme.colors.backgroundPrimary,
+                    color: theme.colors.contentPrimary,
+                    height: '100vh',
+                    padding: '10px 14px',
+                    boxSizing: 'border-box',
+                    display: 'grid',
+                    gridTemplateColumns: 'repeat(3, 1fr)',
+                    alignContent: 'start',
+                    gap: '6px 12px',
+                    fontSize: '12px',
+                }}
+            >
+                {keys.map(([key, name]) => (
+                    <div
+                        key={key}
+                        title={unavailableKeys.includes(key) ? t('Used by another app') : undefined}
+                        style={{
+                            display: 'flex',
+                            alignItems: 'center',
+                            gap: '6px',
+                            overflow: 'hidden',
+                            opacity: unavailableKeys.includes(key) ? 0.4 : 1,
+                            textDecoration: unavailableKeys.includes(key) ? 'line-through' : 'none',
+                        }}
+                    >
+                        <kbd
+                            style={{
+                                minWidth: '18px',
+                                textAlign: 'center',
+                                borderRadius: '4px',
+                                padding: '1px 4px',
+                                background: theme.colors.backgroundTertiary,
+                            }}
+                        >
+                            {key}
+                        </kbd>
+                        <span style={{ whiteSpace: 'nowrap', overflow: 'hidden', textOverflow: 'ellipsis' }}>
+                            {name}
+                        </span>
+                    </div>
+                ))}
+            </div>
+        </BaseProvider>
+    )
+}
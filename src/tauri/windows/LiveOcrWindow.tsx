@@ -0,0 +1,88 @@
+import { useEffect, useRef, useState } from 'react'
+import { listen, Event } from '@tauri-apps/api/event'
+import { invoke } from '@tauri-apps/api/primitives'
+import { trackEvent } from '@aptabase/tauri'
+import { Window } from '../components/Window'
+import { useTheme } from '../../common/hooks/useTheme'
+import { translate } from '../../common/translate'
+import { detectLang, intoLangCode } from '../../common/lang'
+import { getSettings } from '../../common/utils'
+
+interface ILiveOcrText {
+    text: string
+    targetLanguage?: string
+}
+
+export function LiveOcrWindow() {
+    const { theme } = useTheme()
+    const [text, setText] = useState('')
+    const [translatedText, setTranslatedText] = useState('')
+    const abortController = useRef<AbortController | null>(null)
+
+    useEffect(() => {
+        trackEvent('screen_view', { name: 'LiveOcr' })
+    }, [])
+
+    useEffect(() => {
+        let unlisten: (() => void) | undefined = undefined
+        ;(async () => {
+            unlisten = await listen('live-ocr-text', async (event: Event<ILiveOcrText>) => {
+                const { text, targetLanguage } = event.payload
+                setText(text)
+                setTranslatedText('')
+                abortController.current?.abort()
+                const controller = new AbortController()
+                abortController.current = controller
+                const settings = await getSettings()
+                const sourceLang = await detectLang(text)
+                const targetLang = intoLangCode(targetLanguage || settings.defaultTargetLanguage)
+                await translate({
+                    action: {
+                        idx: 0,
+                        name: 'live-ocr',
+                        mode: 'translate',
+                        updatedAt: Date.now() + '',
+                        createdAt: Date.now() + '',
+                    },
+                    signal: controller.signal,
+                    text,
+                    detectFrom: sourceLang,
+                    detectTo: targetLang,
+                    onMessage: async (message) => {
+                        if (!message.content) {
+                            return
+                        }
+                        setTranslatedText((v) => (message.isFullText ? message.content : v + message.content))
+                    },
+                    onFinish: () => {},
+                    onError: () => {},
+                })
+            })
+        })()
+        return () => {
+            unlisten?.()
+            abortController.current?.abort()
+        }
+    }, [])
+
+    return (
+        <Window>
+            <div
+                data-tauri-drag-region
+                style={{
+                    color: theme.colors.contentPrimary,
+                    padding: '26px 12px 12px',
+                    height: '100vh',
+                    boxSizing: 'border-box',
+                    overflow: 'auto',
+                }}
+                onDoubleClick={() => invoke('stop_live_ocr')}
+            >
+                <div style={{ fontSize: '16px' }}>{translatedText || text}</div>
+                {translatedText && (
+                    <div style={{ fontSize: '12px', color: theme.colors.contentTertiary, marginTop: '6px' }}>{text}</div>
+                )}
+            </div>
+        </Window>
+    )
+}
@@ -22,6 +22,7 @@ export function ScreenshotWindow() {
     const [mouseDownY, setMouseDownY] = useState(0)
     const [mouseMoveX, setMouseMoveX] = useState(0)
     const [mouseMoveY, setMouseMoveY] = useState(0)
+    const [monitorPosition, setMonitorPosition] = useState({ x: 0, y: 0 })
     const imgRef = useRef<HTMLImageElement>(null)
     const appWindow = getCurrent()
 
@@ -36,6 +37,7 @@ export function ScreenshotWindow() {
             }
 
             const position = monitor.position
+            setMonitorPosition({ x: position.x, y: position.y })
 
             invoke('screenshot', { x: position.x, y: position.y }).then(() => {
                 appCacheDir().then((dir) => {
@@ -113,17 +115,22 @@ export function ScreenshotWindow() {
                     if (!imgRef.current) {
                         return
                     }
-                    const imgWidth = imgRef.current.naturalWidth
-                    const dpi = imgWidth / screen.width
-                    const left = Math.floor(Math.min(mouseDownX, e.clientX) * dpi)
-                    const top = Math.floor(Math.min(mouseDownY, e.clientY) * dpi)
-                    const right = Math.floor(Math.max(mouseDownX, e.clientX) * dpi)
-                    const bottom = Math.floor(Math.max(mouseDownY, e.clientY) * dpi)
+                    // The backend maps logical coordinates to physical pixels using the screenshot metadata
+                    const left = Math.min(mouseDownX, e.clientX)
+                    const top = Math.min(mouseDownY, e.clientY)
+                    const right = Math.max(mouseDownX, e.clientX)
+                    const bottom = Math.max(mouseDownY, e.clientY)
                     const width = right - left
                     const height = bottom - top
                     console.log(left, top, width, height)
                     if (width <= 0 || height <= 0) {
                         await appWindow.close()
+                    } else if (e.shiftKey) {
+                        // Shift+selection pins the region and keeps translating it
+                        await invoke('start_live_ocr', {
+                            region: { ...monitorPosition, left, top, width, height },
+                        })
+                        await appWindow.close()
                     } else {
                         await invoke('cut_image', { left, top, width, height })
                         await invoke('finish_ocr')
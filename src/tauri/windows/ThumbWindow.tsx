@@ -1,27 +1,236 @@
+import { useEffect, useRef, useState } from 'react'
+import { listen, Event } from '@tauri-apps/api/event'
+import { invoke } from '@tauri-apps/api/primitives'
 import icon from '../../common/assets/images/icon.png'
 import { useTheme } from '../../common/hooks/useTheme'
 import { BaseProvider } from 'baseui-sd'
+import { Button } from 'baseui-sd/button'
+import { useTranslation } from 'react-i18next'
+import { Action } from '../../common/internal-services/db'
+import { translate } from '../../common/translate'
+import { detectLang, intoLangCode } from '../../common/lang'
+import { getSettings } from '../../common/utils'
+import { IWordLookup, ThumbAction } from '../../common/types'
+import '../../common/i18n.js'
+
+const defaultThumbActions: ThumbAction[] = ['translate', 'explain', 'polish', 'copy']
+
+function getThumbAction(action: ThumbAction): Action {
+    const now = Date.now() + ''
+    const base = { idx: 0, name: 'thumb', updatedAt: now, createdAt: now }
+    switch (action) {
+        case 'polish':
+            return { ...base, mode: 'polishing' }
+        case 'explain':
+            return {
+                ...base,
+                rolePrompt: 'You are a helpful assistant who explains things clearly and briefly.',
+                commandPrompt:
+                    'Explain the meaning of the following text in ${targetLang} in a few sentences, and only return the explanation.',
+            }
+        default:
+            return { ...base, mode: 'translate' }
+    }
+}
 
 export function ThumbWindow() {
     const { theme } = useTheme()
+    const { t } = useTranslation()
+    const [text, setText] = useState<string | undefined>()
+    const [actions, setActions] = useState<ThumbAction[]>(defaultThumbActions)
+    const [result, setResult] = useState('')
+    const [wordLookup, setWordLookup] = useState<IWordLookup | null>(null)
+    const [isLoading, setIsLoading] = useState(false)
+    const abortController = useRef<AbortController | null>(null)
+    const popoverRef = useRef<HTMLDivElement>(null)
+
+    useEffect(() => {
+        const unlistens: Array<Promise<() => void>> = [
+            listen('thumb-expanded', async (event: Event<{ text: string }>) => {
+                const settings = await getSettings()
+                setActions(settings.thumbActions?.length ? settings.thumbActions : defaultThumbActions)
+                setResult('')
+                setWordLookup(null)
+                setText(event.payload.text)
+            }),
+            listen('thumb-collapsed', () => {
+                abortController.current?.abort()
+                setText(undefined)
+                setResult('')
+                setWordLookup(null)
+                setIsLoading(false)
+            }),
+        ]
+        return () => {
+            unlistens.forEach((unlisten) => unlisten.then((f) => f()))
+            abortController.current?.abort()
+        }
+    }, [])
+
+    useEffect(() => {
+        const popover = popoverRef.current
+        if (!popover) {
+            return
+        }
+        const observer = new ResizeObserver(() => {
+            invoke('resize_thumb_popover', { height: popover.scrollHeight })
+        })
+        observer.observe(popover)
+        return () => observer.disconnect()
+    }, [text])
+
+    useEffect(() => {
+        const onKeyDown = (event: KeyboardEvent) => {
+            if (event.key === 'Escape') {
+                invoke('close_thumb_command')
+            }
+        }
+        window.addEventListener('keydown', onKeyDown)
+        return () => window.removeEventListener('keydown', onKeyDown)
+    }, [])
+
+    const runAction = async (action: ThumbAction) => {
+        if (!text) {
+            return
+        }
+        if (action === 'copy') {
+            await navigator.clipboard.writeText(text)
+            invoke('close_thumb_command')
+            return
+        }
+        abortController.current?.abort()
+        const controller = new AbortController()
+        abortController.current = controller
+        setResult('')
+        setWordLookup(null)
+        const settings = await getSettings()
+        const sourceLang = await detectLang(text)
+        const targetLang = intoLangCode(settings.defaultTargetLanguage)
+        if (action === 'translate') {
+            // Single words are answered from the local dictionaries without a request
+            const lookup = await invoke<IWordLookup | null>('lookup_word', { text, sourceLang, targetLang })
+            if (lookup) {
+                setWordLookup(lookup)
+                return
+            }
+        }
+        setIsLoading(true)
+        await translate({
+            action: getThumbAction(action),
+            signal: controller.signal,
+            text,
+            detectFrom: sourceLang,
+            detectTo: targetLang,
+            onMessage: async (message) => {
+                if (!message.content) {
+                    return
+                }
+                setResult((v) => (message.isFullText ? message.content : v + message.content))
+            },
+            onFinish: () => {
+                setIsLoading(false)
+            },
+            onError: (error) => {
+                setIsLoading(false)
+                setResult(error)
+            },
+        })
+    }
+
+    if (text === undefined) {
+        return (
+            <BaseProvider theme={theme}>
+                <div
+                    className='thumb'
+                    onClick={() => invoke('thumb_clicked')}
+                    style={{
+                        background: theme.colors.backgroundPrimary,
+                    }}
+                >
+                    <img
+                        draggable={false}
+                        style={{
+                            display: 'block',
+                            width: '100%',
+                            height: '100%',
+                        }}
+                        src={icon}
+                    />
+                </div>
+            </BaseProvider>
+        )
+    }
 
     return (
         <BaseProvider theme={theme}>
             <div
-                className='thumb'
+                ref={popoverRef}
                 style={{
                     background: theme.colors.backgroundPrimary,
+                    color: theme.colors.contentPrimary,
+                    padding: '10px',
+                    boxSizing: 'border-box',
+                    display: 'flex',
+                    flexDirection: 'column',
+                    gap: '8px',
                 }}
             >
-                <img
-                    draggable={false}
-                    style={{
-                        display: 'block',
-                        width: '100%',
-                        height: '100%',
-                    }}
-                    src={icon}
-                />
+                <div style={{ display: 'flex', flexWrap: 'wrap', gap: '6px' }}>
+                    {actions.map((action) => (
+                        <Button key={action} size='mini' kind='secondary' onClick={() => runAction(action)}>
+                            {t(
+                                {
+                                    translate: 'Translate',
+                                    explain: 'Explain',
+                                    polish: 'Polishing',
+                                    copy: 'Copy',
+                                }[action]
+                            )}
+                        </Button>
+                    ))}
+                    <Button size='mini' kind='tertiary' onClick={() => invoke('open_thumb_text_in_translator')}>
+                        {t('Open in translator')}
+                    </Button>
+                </div>
+                {wordLookup && (
+                    <div style={{ fontSize: '13px', lineHeight: '1.5', maxHeight: '340px', overflow: 'auto' }}>
+                        {wordLookup.lemma && (
+                            <div style={{ color: theme.colors.contentTertiary }}>
+                                {wordLookup.word} → {wordLookup.lemma}
+                            </div>
+                        )}
+                        {wordLookup.entries.map((entry, idx) => (
+                            <div key={idx} style={{ marginTop: '6px' }}>
+                                <div>
+                                    <b>{entry.headword}</b>
+                                    {entry.phonetic && (
+                                        <span style={{ color: theme.colors.contentTertiary }}> /{entry.phonetic}/</span>
+                                    )}
+                                    <span style={{ color: theme.colors.contentTertiary, fontSize: '11px' }}>
+                                        {' '}
+                                        {entry.dictionary}
+                                    </span>
+                                </div>
+                                {entry.senses.map((sense, senseIdx) => (
+                                    <div key={senseIdx}>{sense}</div>
+                                ))}
+                            </div>
+                        ))}
+                    </div>
+                )}
+                {(result || isLoading) && (
+                    <div
+                        style={{
+                            fontSize: '13px',
+                            lineHeight: '1.5',
+                            whiteSpace: 'pre-wrap',
+                            maxHeight: '340px',
+                            overflow: 'auto',
+                        }}
+                    >
+                        {result || '...'}
+                    </div>
+                )}
             </div>
         </BaseProvider>
     )
@@ -3,7 +3,6 @@ import { Translator } from '../../common/components/Translator'
 import { Client as Styletron } from 'styletron-engine-atomic'
 import { listen, Event } from '@tauri-apps/api/event'
 import { invoke } from '@tauri-apps/api/primitives'
-import { bindDisplayWindowHotkey, bindHotkey, bindOCRHotkey, bindWritingHotkey } from '../utils'
 import { useMemoWindow } from '../../common/hooks/useMemoWindow'
 import { v4 as uuidv4 } from 'uuid'
 import { PREFIX } from '../../common/constants'
@@ -13,6 +12,40 @@ import { useSettings } from '../../common/hooks/useSettings'
 import { setupAnalysis } from '../../common/analysis'
 import { Window } from '../components/Window'
 import { setExternalOriginalText } from '../../common/store'
+import { WritingAction } from '../../common/types'
+import { Action } from '../../common/internal-services/db'
+
+interface IChangeText {
+    text: string
+    targetLanguage?: string
+}
+
+interface IWritingText {
+    text: string
+    action?: WritingAction
+    targetLanguage?: string
+}
+
+function getWritingAction(action?: WritingAction): Action {
+    const now = Date.now() + ''
+    const base = { idx: 0, name: 'writing', updatedAt: now, createdAt: now }
+    switch (action) {
+        case 'polish':
+            return { ...base, mode: 'polishing' }
+        case 'summarize':
+            return { ...base, mode: 'summarize' }
+        case 'fix-grammar':
+            return {
+                ...base,
+                rolePrompt:
+                    'You are a professional proofreader. You only correct grammar, spelling and punctuation mistakes.',
+                commandPrompt:
+                    'Correct the mistakes in the following text. Keep its language, meaning, wording and formatting, and only return the corrected text.',
+            }
+        default:
+            return { ...base, mode: 'translate' }
+    }
+}
 
 const engine = new Styletron({
     prefix: `${PREFIX}-styletron-`,
@@ -23,6 +56,7 @@ export function TranslatorWindow() {
     const [showSettings, setShowSettings] = useState(false)
     const writingQueue = useRef<Array<string | number>>([])
     const isWriting = useRef(false)
+    const writingAbortController = useRef<AbortController>()
 
     const [writingFlag, writing] = useReducer((x: number) => x + 1, 0)
 
@@ -73,12 +107,12 @@ export function TranslatorWindow() {
     useEffect(() => {
         let unlisten: (() => void) | undefined = undefined
         ;(async () => {
-            unlisten = await listen('change-text', async (event: Event<string>) => {
-                const selectedText = event.payload
+            unlisten = await listen('change-text', async (event: Event<IChangeText>) => {
+                const { text: selectedText, targetLanguage } = event.payload
                 if (selectedText) {
                     const uuid_ = uuidv4().replace(/-/g, '').slice(0, 6)
                     setUUID(uuid_)
-                    setExternalOriginalText(selectedText)
+                    setExternalOriginalText(selectedText, targetLanguage ? intoLangCode(targetLanguage) : undefined)
                 }
             })
         })()
@@ -104,26 +138,20 @@ export function TranslatorWindow() {
     const { settings } = useSettings()
 
     useEffect(() => {
-        if (!settings?.writingTargetLanguage) {
-            return
-        }
         let unlisten: () => void | undefined
         ;(async () => {
-            unlisten = await listen('writing-text', async (event: Event<string>) => {
-                const inputText = event.payload
-                const { signal } = new AbortController()
-                if (inputText) {
+            unlisten = await listen('writing-text', async (event: Event<IWritingText>) => {
+                const { text: inputText, action, targetLanguage } = event.payload
+                const targetLanguage_ = targetLanguage || settings?.writingTargetLanguage
+                const abortController = new AbortController()
+                const { signal } = abortController
+                if (inputText && targetLanguage_) {
+                    writingAbortController.current = abortController
                     const sourceLang = await detectLang(inputText)
-                    const targetLang = intoLangCode(settings.writingTargetLanguage)
+                    const targetLang = intoLangCode(targetLanguage_)
                     await translate({
                         writing: true,
-                        action: {
-                            idx: 0,
-                            name: 'writing',
-                            mode: 'translate',
-                            updatedAt: Date.now() + '',
-                            createdAt: Date.now() + '',
-                        },
+                        action: getWritingAction(action),
                         signal,
                         text: inputText,
                         detectFrom: sourceLang,
@@ -136,10 +164,18 @@ export function TranslatorWindow() {
                             writing()
                         },
                         onFinish: () => {
+                            if (signal.aborted) {
+                                return
+                            }
+                            writingAbortController.current = undefined
                             writingQueue.current.push(0)
                             writing()
                         },
                         onError: () => {
+                            if (signal.aborted) {
+                                return
+                            }
+                            writingAbortController.current = undefined
                             writingQueue.current.push(0)
                             writing()
                         },
@@ -152,6 +188,25 @@ export function TranslatorWindow() {
         }
     }, [settings?.writingTargetLanguage])
 
+    useEffect(() => {
+        let unlisten: () => void | undefined
+        ;(async () => {
+            unlisten = await listen('writing-cancelled', () => {
+                const abortController = writingAbortController.current
+                if (!abortController) {
+                    return
+                }
+                writingAbortController.current = undefined
+                abortController.abort()
+                writingQueue.current = [0]
+                writing()
+            })
+        })()
+        return () => {
+            unlisten?.()
+        }
+    }, [])
+
     useEffect(() => {
         let unlisten
         ;(async () => {
@@ -163,13 +218,6 @@ export function TranslatorWindow() {
         return unlisten
     }, [])
 
-    useEffect(() => {
-        bindHotkey()
-        bindDisplayWindowHotkey()
-        bindOCRHotkey()
-        bindWritingHotkey()
-    }, [])
-
     const [isSettingsOpen, setIsSettingsOpen] = useState(false)
 
     const onSettingsShow = useCallback((isShow: boolean) => {
@@ -187,12 +235,8 @@ export function TranslatorWindow() {
                 defaultShowSettings
                 editorRows={10}
                 containerStyle={{ paddingTop: '26px' }}
-                onSettingsSave={(oldSettings) => {
+                onSettingsSave={() => {
                     invoke('clear_config_cache')
-                    bindHotkey(oldSettings.hotkey)
-                    bindDisplayWindowHotkey(oldSettings.displayWindowHotkey)
-                    bindOCRHotkey(oldSettings.ocrHotkey)
-                    bindWritingHotkey(oldSettings.writingHotkey)
                 }}
                 onSettingsShow={onSettingsShow}
             />